@@ -0,0 +1,113 @@
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use regex::Regex;
+
+/// How long the border of the output flashes when an alert fires.
+pub const FLASH: Duration = Duration::from_millis(500);
+
+/// A regex that draws attention to the received lines matching it.
+pub struct Alert {
+    pub pattern: Regex,
+    pub enabled: bool,
+    /// The number of times it has fired.
+    pub count: usize,
+}
+
+/// Rings the bell and flashes the border when a received line matches an
+/// alert, so that it's noticed even when rterm isn't looked at.
+pub struct Alerts {
+    pub alerts: Vec<Alert>,
+    /// When the border started flashing.
+    pub flashed: Option<Instant>,
+    /// Whether the bell has to be rung.
+    pub ring: bool,
+}
+
+impl Alerts {
+    pub fn new(patterns: Vec<Regex>) -> Self {
+        Alerts {
+            alerts: patterns
+                .into_iter()
+                .map(|pattern| Alert {
+                    pattern,
+                    enabled: true,
+                    count: 0,
+                })
+                .collect(),
+            flashed: None,
+            ring: false,
+        }
+    }
+
+    /// Counts the enabled alerts that `line` matches. Returns whether any did.
+    pub fn check(&mut self, line: &str) -> bool {
+        let mut fired = false;
+        for alert in &mut self.alerts {
+            if alert.enabled && alert.pattern.is_match(line) {
+                alert.count += 1;
+                fired = true;
+            }
+        }
+        if fired {
+            self.flashed = Some(Instant::now());
+            self.ring = true;
+        }
+        fired
+    }
+
+    /// Turns the alert with the given number, counting from 1, on or off and
+    /// describes the change.
+    pub fn set_enabled(&mut self, number: usize, enabled: bool) -> anyhow::Result<String> {
+        let count = self.alerts.len();
+        let alert = number
+            .checked_sub(1)
+            .and_then(|idx| self.alerts.get_mut(idx))
+            .ok_or_else(|| anyhow!("there is no alert {number}, there are {count}"))?;
+        alert.enabled = enabled;
+        let state = if enabled { "on" } else { "off" };
+        Ok(format!("alert {number} '{}' is {state}", alert.pattern))
+    }
+
+    /// The alerts with their numbers and counts, e.g. "1:FAIL ×2  2:ERR off".
+    pub fn describe(&self) -> String {
+        let alerts: Vec<String> = self
+            .alerts
+            .iter()
+            .enumerate()
+            .map(|(idx, alert)| {
+                if alert.enabled {
+                    format!("{}:{} ×{}", idx + 1, alert.pattern, alert.count)
+                } else {
+                    format!("{}:{} off", idx + 1, alert.pattern)
+                }
+            })
+            .collect();
+        alerts.join("  ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fires_until_turned_off() {
+        let patterns = ["FAIL", r"temp=9\d"].map(|p| Regex::new(p).unwrap());
+        let mut alerts = Alerts::new(patterns.into());
+        assert!(!alerts.check("OK"));
+        assert!(!alerts.ring);
+        assert!(alerts.check("FAIL temp=95"));
+        assert!(std::mem::take(&mut alerts.ring));
+
+        assert_eq!(
+            alerts.set_enabled(1, false).unwrap(),
+            "alert 1 'FAIL' is off"
+        );
+        assert!(!alerts.check("FAIL again"));
+        assert!(alerts.check("temp=91"));
+        assert_eq!(alerts.describe(), r"1:FAIL off  2:temp=9\d ×2");
+        assert!(alerts.set_enabled(3, true).is_err());
+        assert!(alerts.set_enabled(0, true).is_err());
+    }
+}
@@ -1,58 +1,469 @@
 use std::{
     fs::File,
     io::{self, Read, Write},
-    sync::mpsc::{self, Receiver, Sender},
-    thread::{self},
-    time::Duration,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
 };
 
-use crossterm::event::{self, Event, KeyCode, KeyModifiers};
-use ordered_float::OrderedFloat;
-use regex::Regex;
+use clap::ArgEnum;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers, MouseButton};
 use tui::{
     backend::Backend,
+    buffer::Buffer,
     layout::{Constraint, Direction, Layout, Rect, Alignment},
     style::{Color, Modifier, Style},
-    symbols,
     text::{Span, Spans},
-    widgets::{Axis, Block, Borders, Chart, Dataset, GraphType, Paragraph},
+    widgets::{Block, Borders, Paragraph, Widget},
     Frame, Terminal,
 };
-use tui_textarea::TextArea;
+use tui_textarea::{CursorMove, TextArea};
+
+use anyhow::anyhow;
+use regex::Regex;
 
 use crate::{
-    termdev::TerminalDevice,
-    wraptext::{Position, WrapText, WrapTextState},
+    alert::{self, Alerts},
+    benchmark::{Pattern, Report, Verifier},
+    binary::FrameDecoder,
+    clipboard,
+    command::{self, Command},
+    completion::{Completer, Completion},
+    csv,
+    decoder::{Decoder, TxEncoding},
+    discovery::{Candidate, Watcher},
+    filter::FilterSet,
+    gauge::{self, GAUGE_COLUMNS, GAUGE_ROWS},
+    graph::{Extractor, Grapher},
+    hexdump,
+    jsonl::{Direction as JsonlDirection, JsonlLog},
+    logfile::{self, LogSink},
+    nmea,
+    picker::HistoryPicker,
+    pipe::{Pipe, Tap},
+    route::Routes,
+    stats::{Counters, StatsFile},
+    svg,
+    termdev::{ModemStatus, SerialConfig, TerminalDevice, TerminalWriter},
+    timefmt,
+    trigger::Triggers,
+    udp::UdpForward,
+    vim::{self, Action, Keymap, Mode},
+    vt100::Screen,
+    wraptext::{Line, Lines, Selection, Timestamps, WrapText, WrapTextState, Wrapping},
 };
 
+#[cfg(test)]
+pub mod harness;
+
+/// How often the overrun counters of the device are checked.
+const OVERRUN_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// The longest the io threads wait for data or for being stopped before
+/// checking whether to stop, which is how long stopping a connection takes.
+const IO_STOP_TIMEOUT: Duration = Duration::from_millis(20);
+/// The most bytes taken from the device at once.
+const READ_BUFFER_SIZE: usize = 4096;
+/// How often the modem status lines of the control device are read.
+const MODEM_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// The number of values in the sparkline of --graph-sparkline.
+const STATUS_SPARKLINE_LEN: usize = 30;
+/// How long the lock indicator flashes when sending while the input is locked.
+const LOCK_FLASH: Duration = Duration::from_millis(400);
+/// The fewest rows the output and graph panes are made, including their borders.
+const MIN_PANE_ROWS: u16 = 4;
+/// The smallest terminal the panes are drawn with borders in, apart from the input rows.
+const MIN_BORDERED_WIDTH: u16 = 20;
+const MIN_BORDERED_HEIGHT: u16 = 2 + MIN_PANE_ROWS + 2;
+/// The smallest terminal anything but a note that it's too small is drawn in.
+const MIN_WIDTH: u16 = 10;
+const MIN_HEIGHT: u16 = 3;
+/// How long notifications other than errors are shown.
+const NOTIFICATION_DURATION: Duration = Duration::from_secs(3);
+/// How long the benchmark waits for an echo before the bytes in flight count as dropped.
+const BENCHMARK_STALL: Duration = Duration::from_secs(1);
+/// How often the event loop wakes up for its timers when it waits for events.
+const TIMER_TICK: Duration = Duration::from_millis(50);
+
+/// How much a notification matters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    /// Shown until it's dismissed with Alt+d.
+    Error,
+}
+
+/// A message shown in the status bar for a moment.
+struct Notification {
+    message: String,
+    level: Level,
+    created: Instant,
+}
+
 pub struct App {
-    outfile: Option<File>,
-    history: Vec<String>,
+    outfile: Option<LogSink>,
+    history: Vec<HistoryEntry>,
     browsing_history: Option<usize>, // Index into history if we are browsing history.
+    /// The popup opened with Alt+h to pick a line from the history.
+    history_picker: Option<HistoryPicker>,
+    /// The input box takes bytes as hex.
+    hex_input: bool,
+    /// Why what was entered couldn't be sent, shown until the input changes.
+    input_error: Option<String>,
+    /// Keys typed in char mode that didn't fit in the buffer, until it has been sent.
+    keys_dropped: usize,
+    /// The completion repeated Tabs cycle through.
+    completion: Option<Completion>,
+    /// Nothing is sent while the input is locked, toggled with Ctrl+o.
+    input_locked: bool,
+    /// When something was about to be sent while the input was locked.
+    lock_flash: Option<Instant>,
     cur_line: String,
+    /// The number of characters each byte of `cur_line` was displayed as,
+    /// so that a backspace takes back all of them.
+    cur_widths: Vec<usize>,
+    /// The divider inserted when the device was last reconnected, which Alt+j
+    /// jumps to.
+    reconnect_line: Option<usize>,
+    /// The latest bytes received, for the hex view.
+    hex: hexdump::Dump,
+    hex_view: bool,
+    /// The commands matching what's typed in the command palette, if it's open.
+    command_hint: Option<String>,
+    /// The number of bytes received and written to the device, over all connections.
+    rx_bytes: usize,
+    tx_written: Arc<AtomicUsize>,
+    pub event_loop: EventLoop,
+    /// Wakes the event loop up, if it waits.
+    waker: Option<Sender<Wakeup>>,
+    /// The number of lines received, reconnects and errors, for the statistics file.
+    rx_lines: usize,
+    reconnects: usize,
+    errors: usize,
+    /// Received bytes that weren't displayed because of --nul or --controls.
+    stripped: usize,
+    /// A BEL was received, so the bell is rung.
+    bell: bool,
+    /// Where statistics are written every now and then.
+    pub stats: Option<StatsFile>,
+    /// The notifications still shown, the latest last.
+    notifications: Vec<Notification>,
+    /// A notification was added or removed since the last redraw.
+    notifications_changed: bool,
+    /// Whether the last byte was a CR.
+    after_cr: bool,
+    /// The previous completed line and how many times in a row it has been received.
+    prev_line: Option<(String, usize)>,
+    /// The logged text of the current line, when the log is deduplicated.
+    log_line: String,
+    /// The logged text of the current line, for the JSON lines log.
+    jsonl_line: String,
+    /// The last line for the log and its count. It's written once a
+    /// different line has been received.
+    held_log_line: Option<(String, usize)>,
+    /// When the last byte was received, or when the device was connected.
+    last_rx: Option<Instant>,
+    /// For how many seconds nothing has been received, once that's longer than `idle_timeout`.
+    idle_secs: Option<u64>,
     pub grapher: Option<Grapher>,
+    /// Creates the grapher the first time the graph or the gauge is shown in
+    /// a session that started without one.
+    pub new_grapher: Box<dyn Fn() -> Grapher>,
+    /// Whether the graph pane is shown. Hiding it keeps the grapher, which
+    /// goes on extracting values.
+    pub graph_visible: bool,
+    /// The graph is a sparkline in the status bar rather than a pane.
+    pub graph_sparkline: bool,
+    /// Whether the latest value is shown in big characters in a pane of its own.
+    pub gauge_visible: bool,
+    /// What the value of the gauge is in, e.g. "°C".
+    pub gauge_unit: String,
+    /// The share of the rows below the input that the output gets when the
+    /// graph is shown. Changed with Alt+Up and Alt+Down or by dragging the border.
+    pub graph_split: f64,
+    /// The border between the output and the graph is being dragged.
+    dragging_divider: bool,
+    /// The mouse is over the border between the output and the graph.
+    divider_hovered: bool,
+    /// Text in the output is being selected with the mouse.
+    selecting: bool,
+    /// Copy text to the primary selection as soon as it's selected.
+    pub copy_on_select: bool,
+    /// Esc switches to a vim-like normal mode for moving through the output
+    /// instead of quitting.
+    pub vim: bool,
+    /// The rows the output is scrolled by a notch of the mouse wheel.
+    pub scroll_step: u16,
+    mode: Mode,
+    keymap: Keymap,
+    /// What was last searched for with `/`.
+    search: Option<String>,
+    /// Where Alt+e exports the graph to and the size of the image.
+    pub graph_export: PathBuf,
+    pub graph_export_size: (u32, u32),
+    /// Write the times in CSV exports in UTC instead of the local time zone.
+    pub csv_utc: bool,
+    pub device_name: String,
+    /// The path of the device, used to reopen it when the baud rate is changed.
+    pub device_path: Option<PathBuf>,
+    /// Where the log file was created.
+    pub log_path: Option<PathBuf>,
+    /// What the log file was opened from, e.g. a directory, for opening the
+    /// next segment of a split log.
+    pub log_out: Option<String>,
+    /// Continue the log in a new file when nothing has been received for this long.
+    pub log_split_idle: Option<Duration>,
+    /// When data was last received for the log.
+    last_log_rx: Option<Instant>,
+    pub decoder: Decoder,
+    pub serial_config: SerialConfig,
+    pub tx_settings: TxSettings,
+    /// How typed text is encoded when it's sent.
+    pub tx_encoding: TxEncoding,
+    /// The maximum number of rows the input box grows to.
+    pub input_height: usize,
+    pub timestamps: Timestamps,
+    /// What relative timestamps count from, the start or when Alt+z was last pressed.
+    pub timestamp_origin: Instant,
+    /// Reset `timestamp_origin` when the device is reconnected.
+    pub zero_timestamps_on_reconnect: bool,
+    pub gap_threshold: Duration,
+    pub line_number_width: Option<usize>,
+    pub line_number_start: usize,
+    pub wrapping: Wrapping,
+    /// Used to find a device to connect to whenever no device is connected.
+    pub watcher: Option<Watcher>,
+    /// Validate the checksums of NMEA sentences, if enabled.
+    pub nmea: Option<nmea::Stats>,
+    /// What's received is drawn on this instead of shown as lines, with --vt100.
+    pub screen: Option<Screen>,
+    /// Highlight the values the graph extracts in the output.
+    pub highlight_values: bool,
+    /// Color lines by the thresholds of their values.
+    pub threshold_color: ThresholdColor,
+    /// Draw everything in color, or else only with the default colors.
+    pub color: bool,
+    /// Decodes the graphed values from the received bytes, instead of
+    /// extracting them from lines.
+    pub frames: Option<FrameDecoder>,
+    /// A second device whose modem status lines are shown in the status bar.
+    pub control_device: Option<TerminalDevice>,
+    /// The modem status lines of the control device when they were last read.
+    modem_status: Option<Result<ModemStatus, String>>,
+    /// How long to only read from the device after connecting, before the UI starts.
+    pub banner_wait: Option<Duration>,
+    /// Lines sent to the device right after connecting to it.
+    pub on_connect: Vec<String>,
+    /// Reset the device with DTR and RTS when connecting to it, before sending `on_connect`.
+    pub reset_on_connect: bool,
+    /// Also reset the device and send `on_connect` when it's reconnected.
+    pub rerun_on_reconnect: bool,
+    /// The byte that ends a line.
+    pub delimiter: u8,
+    /// Log the received bytes exactly as they are.
+    pub raw_log: bool,
+    /// The command received lines are piped through, while it works.
+    pub pipe: Option<Pipe>,
+    /// Runs commands when received lines match the rules of --on-match.
+    pub triggers: Option<Triggers>,
+    /// Draws attention to the received lines matching --alert, which are
+    /// shown as errors until they're dismissed.
+    pub alerts: Option<Alerts>,
+    /// Sends the received lines to other machines.
+    pub udp: Option<UdpForward>,
+    /// Appends the received lines to the files of the --route options they match.
+    pub routes: Option<Routes>,
+    /// Show the received lines as well as the piped ones.
+    pub pipe_alongside: bool,
+    pub pipe_log: Tap,
+    pub pipe_graph: Tap,
+    /// End what's entered as hex with a newline.
+    pub hex_newline: bool,
+    /// Send a line ending when Enter is pressed with nothing typed.
+    pub allow_empty_send: bool,
+    /// Only lines matching these are shown. Set with :filter, :addfilter and :rmfilter.
+    pub filters: FilterSet,
+    /// The indices of the received lines that match the filters. The line
+    /// being received is checked again every time, the others once.
+    filtered: Vec<usize>,
+    /// The number of lines that have been checked against the filters for good.
+    filtered_upto: usize,
+    /// Completes the word being typed with Tab.
+    pub completer: Option<Completer>,
+    /// Show what is sent in the output as well.
+    pub local_echo: bool,
+    /// Write the sent lines to the out file as well, after this prefix.
+    pub log_tx: Option<String>,
+    /// Every sent and received line as JSON.
+    pub jsonl: Option<JsonlLog>,
+    /// Send every key as it's typed instead of a line at a time.
+    pub char_mode: bool,
+    /// The arrow keys and j, k scroll the output instead of going to the input.
+    output_focused: bool,
+    /// The most bytes typed in char mode that may wait to be sent. Keys
+    /// typed beyond that, e.g. by holding one down, are dropped.
+    pub char_buffer: usize,
+    /// The number of lines Alt+c copies to the clipboard.
+    pub copy_lines: usize,
+    /// Collapse consecutive identical lines into one with a count.
+    pub dedup: bool,
+    /// Collapse them in the log file as well.
+    pub dedup_log: bool,
+    /// Show that the device is idle when nothing has been received for this long.
+    pub idle_timeout: Option<Duration>,
+    /// Write a marker to the log file when the device goes idle.
+    pub idle_log: bool,
+    /// Quit after running for this long.
+    pub duration: Option<Duration>,
+    /// Set when `run` returns because `duration` was up.
+    pub timed_out: bool,
+    /// The lines of the output, set when `run` returns.
+    pub output: Vec<Line>,
+}
+
+/// What the reader thread sends to the UI.
+pub enum Incoming {
+    Data(Vec<u8>),
+    /// Received data might be missing, for the given reason.
+    DataLoss(&'static str),
+}
+
+/// Why the event loop was woken up while waiting.
+pub enum Wakeup {
+    Input(io::Result<Event>),
+    /// Something was sent on the `read_rx` of the connection.
+    Received,
+}
+
+/// How the event loop finds out about events and received data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum)]
+pub enum EventLoop {
+    /// Sleeps until one of them arrives or a timer is due.
+    Wait,
+    /// Checks for them every 10 ms and takes one chunk of data at a time.
+    Poll,
 }
 
-pub struct Grapher {
-    pub data: Vec<(f64, f64)>,
-    pub value_pattern: Regex,
-    pub window_len: usize,
-    pub window: [f64; 2],
+/// The threads and channels used to communicate with a connected device.
+struct Connection {
+    stop_tx: Sender<()>,
+    read_rx: Receiver<Incoming>,
+    /// Queued data, with the cancellation generation it was queued in.
+    write_tx: Sender<(usize, Vec<u8>)>,
+    /// The number of bytes queued but not yet written to the device.
+    tx_pending: Arc<AtomicUsize>,
+    /// Increased to cancel everything queued so far.
+    tx_generation: Arc<AtomicUsize>,
+    /// For changing the settings of the device while the io threads use it.
+    device: TerminalDevice,
+    handle: JoinHandle<anyhow::Result<()>>,
 }
 
 pub struct UI {
     input_chunk: Rect,
     ouput_chunk: Rect,
     graph_chunk: Option<Rect>,
+    gauge_chunk: Option<Rect>,
     help_info_chunk: Rect,
+    /// The panes have borders, which smaller terminals leave out.
+    bordered: bool,
+    /// Only a note that the terminal is too small is drawn.
+    too_small: bool,
+    /// What the chunks were computed for.
+    size: Rect,
+    graph: bool,
+    gauge: bool,
+    input_rows: u16,
+    split: f64,
+}
+
+/// What is colored by how the values in a line compare to their thresholds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum)]
+pub enum ThresholdColor {
+    /// The whole line, by its most severe value.
+    Line,
+    Value,
+}
+
+/// When to use colors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum)]
+pub enum ColorChoice {
+    /// Unless NO_COLOR is set or the terminal is dumb.
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                    && std::env::var_os("TERM").is_none_or(|term| term != "dumb")
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// Takes the colors out of everything rendered before it. What only stood
+/// out by its background, like highlights, is reversed instead.
+struct Monochrome;
+
+impl Widget for Monochrome {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                let cell = buf.get_mut(x, y);
+                if cell.bg != Color::Reset {
+                    cell.modifier.insert(Modifier::REVERSED);
+                }
+                cell.fg = Color::Reset;
+                cell.bg = Color::Reset;
+            }
+        }
+    }
+}
+
+/// Sets the style of everything rendered before it.
+struct Restyle(Style);
+
+impl Widget for Restyle {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        buf.set_style(area, self.0);
+    }
+}
+
+/// Settings for how data is written to the device.
+#[derive(Clone, Copy, Default)]
+pub struct TxSettings {
+    /// Delay between every written byte, for devices that can't keep up.
+    pub char_delay: Option<Duration>,
+    /// The least time from writing the end of a line to starting the next
+    /// one, for devices that hang when flooded with lines.
+    pub line_interval: Option<Duration>,
 }
 
 pub fn term_io_loop(
     td: TerminalDevice,
     stop: Receiver<()>,
-    input: Receiver<Vec<u8>>,
-    output: Sender<Vec<u8>>,
+    input: Receiver<(usize, Vec<u8>)>,
+    output: Sender<Incoming>,
+    waker: Option<Sender<Wakeup>>,
+    tx_settings: TxSettings,
+    counters: TxCounters,
 ) -> anyhow::Result<()> {
+    let TxCounters {
+        pending: tx_pending,
+        written: tx_written,
+        generation: tx_generation,
+    } = counters;
     let (mut term_reader, mut term_writer) = td.split();
 
     let (read_thread_stop_tx, read_thread_stop_rx) = mpsc::channel();
@@ -60,16 +471,34 @@ pub fn term_io_loop(
 
     // Reads from the terminal and sends the data to output.
     let term_reader_handle = thread::spawn(move || -> anyhow::Result<()> {
+        // Overruns happen in the driver, so they are caught even if rterm keeps up.
+        let mut overruns = term_reader.overruns();
+        let mut last_overrun_poll = Instant::now();
+        let mut buf = vec![0; READ_BUFFER_SIZE];
         loop {
             if let Ok(()) = read_thread_stop_rx.try_recv() {
                 return Ok(());
             }
-            let mut buf = vec![0; 8];
+            if overruns.is_some() && last_overrun_poll.elapsed() >= OVERRUN_POLL_INTERVAL {
+                last_overrun_poll = Instant::now();
+                let new_overruns = term_reader.overruns();
+                if new_overruns > overruns {
+                    output.send(Incoming::DataLoss("overrun"))?;
+                    wake(&waker);
+                }
+                overruns = new_overruns;
+            }
+            if !term_reader.wait(IO_STOP_TIMEOUT)? {
+                continue;
+            }
             match term_reader.read(&mut buf) {
                 Ok(n) => {
-                    if n != 0 {
-                        output.send(Vec::from(&buf[..n]))?;
+                    if n == 0 {
+                        // The device has been hung up, e.g. by unplugging it.
+                        return Err(anyhow!("device disconnected"));
                     }
+                    output.send(Incoming::Data(Vec::from(&buf[..n])))?;
+                    wake(&waker);
                 }
                 Err(e) => {
                     if e.kind() != io::ErrorKind::WouldBlock {
@@ -77,34 +506,99 @@ pub fn term_io_loop(
                     }
                 }
             }
-            thread::sleep(Duration::from_millis(1));
         }
     });
 
     // Takes the data form input and reads if to the terminal device.
     let term_writer_handle = thread::spawn(move || -> anyhow::Result<()> {
+        // When the last line ended, lines written slower than line_interval
+        // aren't held up at all.
+        let mut last_line_end: Option<Instant> = None;
         loop {
             if let Ok(()) = write_thread_stop_rx.try_recv() {
                 return Ok(());
             }
-            let data: Vec<u8> = input.recv()?;
-            term_writer.write_all(&data)?;
-            term_writer.flush()?;
+            // The input is closed when the connection is stopped.
+            let Ok((generation, data)) = input.recv() else {
+                return Ok(());
+            };
+            // What was cancelled has been taken off tx_pending already.
+            let cancelled = || tx_generation.load(Ordering::Relaxed) != generation;
+            if cancelled() {
+                continue;
+            }
+            let interrupted = || {
+                if let Ok(()) = write_thread_stop_rx.try_recv() {
+                    Some(Interrupted::Stopped)
+                } else if cancelled() {
+                    Some(Interrupted::Cancelled)
+                } else {
+                    None
+                }
+            };
+            'lines: for line in data.split_inclusive(|&b| b == b'\n') {
+                if let (Some(interval), Some(end)) = (tx_settings.line_interval, last_line_end) {
+                    // Waits in steps so that stopping and cancelling aren't held up.
+                    while let Some(left) = (end + interval).checked_duration_since(Instant::now()) {
+                        if let Ok(()) = write_thread_stop_rx.try_recv() {
+                            return Ok(());
+                        }
+                        if cancelled() {
+                            break 'lines;
+                        }
+                        thread::sleep(left.min(Duration::from_millis(10)));
+                    }
+                }
+                match tx_settings.char_delay {
+                    Some(delay) => {
+                        for byte in line {
+                            if let Ok(()) = write_thread_stop_rx.try_recv() {
+                                return Ok(());
+                            }
+                            if cancelled() {
+                                break 'lines;
+                            }
+                            match write_interruptibly(&mut term_writer, &[*byte], &interrupted)? {
+                                Some(Interrupted::Stopped) => return Ok(()),
+                                Some(Interrupted::Cancelled) => break 'lines,
+                                None => {}
+                            }
+                            sub_pending(&tx_pending, 1);
+                            tx_written.fetch_add(1, Ordering::Relaxed);
+                            thread::sleep(delay);
+                        }
+                    }
+                    None => {
+                        if cancelled() {
+                            break 'lines;
+                        }
+                        match write_interruptibly(&mut term_writer, line, &interrupted)? {
+                            Some(Interrupted::Stopped) => return Ok(()),
+                            Some(Interrupted::Cancelled) => break 'lines,
+                            None => {}
+                        }
+                        sub_pending(&tx_pending, line.len());
+                        tx_written.fetch_add(line.len(), Ordering::Relaxed);
+                    }
+                }
+                if line.ends_with(b"\n") {
+                    last_line_end = Some(Instant::now());
+                }
+            }
             thread::sleep(Duration::from_millis(1));
         }
     });
 
+    // The io threads ending are noticed when the wait for stop times out.
     loop {
-        match stop.try_recv() {
+        match stop.recv_timeout(IO_STOP_TIMEOUT) {
             Ok(()) => break,
-            Err(mpsc::TryRecvError::Disconnected) => break,
-            Err(mpsc::TryRecvError::Empty) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {}
         }
         if term_reader_handle.is_finished() || term_writer_handle.is_finished() {
             break;
         }
-        thread::sleep(Duration::from_millis(1));
-
     }
     let _ = read_thread_stop_tx.send(());
     let _ = write_thread_stop_tx.send(());
@@ -113,87 +607,898 @@ pub fn term_io_loop(
     Ok(())
 }
 
+fn wake(waker: &Option<Sender<Wakeup>>) {
+    if let Some(waker) = waker {
+        // Only fails once the event loop has stopped.
+        let _ = waker.send(Wakeup::Received);
+    }
+}
+
+/// Reads the events of the terminal and wakes the event loop up with them,
+/// until it stops.
+fn read_events(waker: Sender<Wakeup>) {
+    loop {
+        let event = event::read();
+        let failed = event.is_err();
+        if waker.send(Wakeup::Input(event)).is_err() || failed {
+            return;
+        }
+    }
+}
+
+/// The counters the writer thread shares with its connection.
+pub struct TxCounters {
+    /// The number of bytes queued but not yet written to the device.
+    pending: Arc<AtomicUsize>,
+    /// The number of bytes written to the device, over all connections.
+    written: Arc<AtomicUsize>,
+    /// Increased to cancel everything queued so far.
+    generation: Arc<AtomicUsize>,
+}
+
+/// Why writing to the device stopped before everything was sent.
+enum Interrupted {
+    Stopped,
+    Cancelled,
+}
+
+/// Writes all of `data` and waits until the device has transmitted it. A
+/// device that doesn't keep up, or never reads at all, is waited for in steps
+/// so that it can be `interrupted` instead of blocking the writer for good.
+fn write_interruptibly(
+    writer: &mut TerminalWriter,
+    mut data: &[u8],
+    interrupted: &dyn Fn() -> Option<Interrupted>,
+) -> io::Result<Option<Interrupted>> {
+    while !data.is_empty() {
+        match writer.write(data) {
+            Ok(n) => data = &data[n..],
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                if let Some(interrupted) = interrupted() {
+                    return Ok(Some(interrupted));
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    while writer.queued()? > 0 {
+        if let Some(interrupted) = interrupted() {
+            return Ok(Some(interrupted));
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    Ok(None)
+}
+
+/// Takes `n` bytes that have been written off the pending count, which may
+/// have been reset by cancelling in the meantime.
+fn sub_pending(tx_pending: &AtomicUsize, n: usize) {
+    let _ = tx_pending.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |pending| {
+        Some(pending.saturating_sub(n))
+    });
+}
+
+/// Formats a number of bytes, e.g. "512 B" or "1.2 MB".
+fn byte_count(bytes: usize) -> String {
+    match bytes {
+        0..=999 => format!("{bytes} B"),
+        1000..=999_999 => format!("{:.1} kB", bytes as f64 / 1e3),
+        _ => format!("{:.1} MB", bytes as f64 / 1e6),
+    }
+}
+
+/// Writes the text of `lines` to the file at `path`.
+fn save_lines(path: &Path, lines: &[Line]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    for line in lines {
+        writeln!(file, "{}", line.saved_text())?;
+    }
+    Ok(())
+}
+
+/// Lists the commands matching what's typed in the command palette.
+fn command_hint(input: &str) -> String {
+    command::matching(input)
+        .iter()
+        .map(|(name, args)| format!(":{name} {args}").trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("    ")
+}
+
+/// A line entered in the input box.
+#[derive(Clone, PartialEq, Eq)]
+struct HistoryEntry {
+    text: String,
+    /// Entered as hex bytes.
+    hex: bool,
+}
+
+/// The character sent for a key typed in char mode, or `None` if it isn't sent.
+fn key_char(key: &KeyEvent) -> Option<char> {
+    // Keys with modifiers keep doing what they do in line mode.
+    if !(key.modifiers - KeyModifiers::SHIFT).is_empty() {
+        return None;
+    }
+    match key.code {
+        KeyCode::Char(c) => Some(c),
+        KeyCode::Enter => Some('\n'),
+        KeyCode::Tab => Some('\t'),
+        KeyCode::Backspace => Some('\x7F'),
+        _ => None,
+    }
+}
+
+/// Replaces the content of the input box with `text` and moves the cursor to the end.
+fn set_input(textarea: &mut TextArea, text: &str) {
+    *textarea = TextArea::new(text.split('\n').map(String::from).collect());
+    textarea.move_cursor(CursorMove::Bottom);
+    textarea.move_cursor(CursorMove::End);
+}
+
+impl Connection {
+    fn start(
+        td: TerminalDevice,
+        tx_settings: TxSettings,
+        tx_written: Arc<AtomicUsize>,
+        waker: Option<Sender<Wakeup>>,
+    ) -> Self {
+        let (stop_tx, stop_rx) = mpsc::channel();
+        let (read_thread_tx, read_rx) = mpsc::channel();
+        let (write_tx, write_thread_rx) = mpsc::channel();
+        let tx_pending = Arc::new(AtomicUsize::new(0));
+        let tx_generation = Arc::new(AtomicUsize::new(0));
+        let device = td.handle();
+        let counters = TxCounters {
+            pending: tx_pending.clone(),
+            written: tx_written,
+            generation: tx_generation.clone(),
+        };
+        let handle = thread::spawn(move || {
+            term_io_loop(
+                td,
+                stop_rx,
+                write_thread_rx,
+                read_thread_tx,
+                waker,
+                tx_settings,
+                counters,
+            )
+        });
+        Connection {
+            stop_tx,
+            read_rx,
+            write_tx,
+            tx_pending,
+            tx_generation,
+            device,
+            handle,
+        }
+    }
+
+    /// Queues data to be written to the device.
+    fn send(&self, data: Vec<u8>) {
+        self.tx_pending.fetch_add(data.len(), Ordering::Relaxed);
+        let generation = self.tx_generation.load(Ordering::Relaxed);
+        if self.write_tx.send((generation, data)).is_err() {
+            self.tx_pending.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Discards everything queued that hasn't been written to the device yet
+    /// and returns the number of bytes. What the kernel has been handed is
+    /// still sent.
+    fn cancel_pending(&self) -> usize {
+        self.tx_generation.fetch_add(1, Ordering::Relaxed);
+        self.tx_pending.swap(0, Ordering::Relaxed)
+    }
+
+    /// Waits for the io threads to finish and returns the reason they stopped.
+    fn join(self) -> anyhow::Result<()> {
+        let _ = self.stop_tx.send(());
+        // Unblocks the writer thread if it's waiting for data.
+        drop(self.write_tx);
+        self.handle
+            .join()
+            .unwrap_or_else(|_| Err(anyhow!("io thread panicked")))
+    }
+}
+
 impl App {
-    pub fn new(outfile: Option<File>) -> Self {
+    pub fn new(outfile: Option<LogSink>, serial_config: SerialConfig) -> Self {
         App {
             outfile,
             cur_line: String::new(),
+            cur_widths: Vec::new(),
+            reconnect_line: None,
+            hex: hexdump::Dump::new(),
+            hex_view: false,
+            filters: FilterSet::new(),
+            filtered: Vec::new(),
+            filtered_upto: 0,
+            command_hint: None,
+            rx_bytes: 0,
+            rx_lines: 0,
+            reconnects: 0,
+            errors: 0,
+            stripped: 0,
+            bell: false,
+            stats: None,
+            tx_written: Arc::new(AtomicUsize::new(0)),
+            event_loop: EventLoop::Wait,
+            waker: None,
+            notifications: Vec::new(),
+            notifications_changed: false,
+            after_cr: false,
+            prev_line: None,
+            log_line: String::new(),
+            jsonl_line: String::new(),
+            held_log_line: None,
+            last_rx: None,
+            idle_secs: None,
             history: Vec::new(),
             browsing_history: None,
+            history_picker: None,
+            hex_input: false,
+            input_error: None,
+            keys_dropped: 0,
+            completion: None,
+            input_locked: false,
+            lock_flash: None,
             grapher: None,
+            new_grapher: Box::new(|| Grapher::new(vec![Extractor::first_number()], 60)),
+            graph_visible: false,
+            graph_sparkline: false,
+            gauge_visible: false,
+            gauge_unit: String::new(),
+            graph_split: 0.5,
+            dragging_divider: false,
+            divider_hovered: false,
+            selecting: false,
+            copy_on_select: false,
+            vim: false,
+            scroll_step: 3,
+            mode: Mode::Insert,
+            keymap: Keymap::default(),
+            search: None,
+            graph_export: PathBuf::from("graph.svg"),
+            graph_export_size: (800, 480),
+            csv_utc: false,
+            device_name: String::new(),
+            device_path: None,
+            log_path: None,
+            log_out: None,
+            log_split_idle: None,
+            last_log_rx: None,
+            decoder: Decoder::new(),
+            serial_config,
+            tx_settings: TxSettings::default(),
+            tx_encoding: TxEncoding::Utf8,
+            input_height: 1,
+            timestamps: Timestamps::Off,
+            timestamp_origin: Instant::now(),
+            zero_timestamps_on_reconnect: false,
+            gap_threshold: Duration::from_secs(1),
+            line_number_width: None,
+            line_number_start: 0,
+            wrapping: Wrapping::default(),
+            watcher: None,
+            nmea: None,
+            screen: None,
+            highlight_values: false,
+            threshold_color: ThresholdColor::Line,
+            color: true,
+            frames: None,
+            control_device: None,
+            modem_status: None,
+            banner_wait: None,
+            on_connect: Vec::new(),
+            reset_on_connect: false,
+            rerun_on_reconnect: false,
+            delimiter: b'\n',
+            raw_log: false,
+            pipe: None,
+            triggers: None,
+            alerts: None,
+            udp: None,
+            routes: None,
+            pipe_alongside: false,
+            pipe_log: Tap::Raw,
+            pipe_graph: Tap::Raw,
+            hex_newline: false,
+            allow_empty_send: false,
+            completer: None,
+            local_echo: false,
+            log_tx: None,
+            jsonl: None,
+            char_mode: false,
+            output_focused: false,
+            char_buffer: 16,
+            copy_lines: 20,
+            dedup: false,
+            dedup_log: false,
+            idle_timeout: None,
+            idle_log: false,
+            duration: None,
+            timed_out: false,
+            output: Vec::new(),
         }
     }
 
-    pub fn run<B: Backend>(
+    /// Sends `command` and waits for a line matching `expect`, without the UI.
+    /// Returns the matching line, or `None` if there was none within `timeout`.
+    pub fn query(
         &mut self,
         td: TerminalDevice,
+        command: &str,
+        expect: &Regex,
+        timeout: Duration,
+    ) -> anyhow::Result<Option<String>> {
+        let mut wraptext = WrapText::new();
+        let bytes = self.tx_encoding.encode(&format!("{command}\n"))?;
+        let connection = Connection::start(td, self.tx_settings, self.tx_written.clone(), None);
+        connection.send(bytes);
+        if let Some(jsonl) = &mut self.jsonl {
+            jsonl.write(JsonlDirection::Tx, SystemTime::now(), command)?;
+        }
+        let deadline = Instant::now() + timeout;
+        let mut reply = None;
+        while let (None, Some(left)) = (&reply, deadline.checked_duration_since(Instant::now())) {
+            // Disconnected means that the io threads have stopped.
+            let Ok(incoming) = connection.read_rx.recv_timeout(left) else {
+                break;
+            };
+            // The last line is the one still being received.
+            let start = wraptext.lines.len() - 1;
+            self.handle_incoming(incoming, &mut wraptext)?;
+            let end = wraptext.lines.len() - 1;
+            reply = wraptext.lines[start.min(end)..end]
+                .iter()
+                .find(|line| expect.is_match(&line.text))
+                .map(|line| line.text.clone());
+        }
+        let stopped = connection.join();
+        self.finish_log()?;
+        match reply {
+            Some(reply) => Ok(Some(reply)),
+            None => stopped.map(|()| None),
+        }
+    }
+
+    /// Sends a pseudorandom pattern for `duration` and verifies that it's
+    /// echoed back, with at most `window` bytes in flight so that slow echoes
+    /// aren't overrun. Chunks of a quarter of the window are sent whenever
+    /// there's room for them.
+    pub fn benchmark(
+        &self,
+        td: TerminalDevice,
+        duration: Duration,
+        window: usize,
+    ) -> anyhow::Result<Report> {
+        let chunk = (window / 4).max(1);
+        let connection = Connection::start(td, self.tx_settings, self.tx_written.clone(), None);
+        let mut pattern = Pattern::new();
+        let mut verifier = Verifier::new();
+        let start = Instant::now();
+        let mut last_progress = start;
+        loop {
+            let now = Instant::now();
+            let sending = now - start < duration;
+            if sending
+                && verifier.in_flight() + chunk <= window
+                && connection.tx_pending.load(Ordering::Relaxed) == 0
+            {
+                if verifier.in_flight() == 0 {
+                    last_progress = now;
+                }
+                let bytes = pattern.take(chunk);
+                verifier.sent(&bytes, now);
+                connection.send(bytes);
+            } else if !sending && verifier.in_flight() == 0 {
+                break;
+            }
+            match connection.read_rx.recv_timeout(Duration::from_millis(1)) {
+                Ok(Incoming::Data(data)) => {
+                    last_progress = Instant::now();
+                    verifier.received(&data, last_progress);
+                }
+                // Shows up as dropped bytes.
+                Ok(Incoming::DataLoss(_)) | Err(RecvTimeoutError::Timeout) => {}
+                // The io threads have stopped.
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if verifier.in_flight() > 0 && last_progress.elapsed() >= BENCHMARK_STALL {
+                verifier.flush(Instant::now());
+                last_progress = Instant::now();
+            }
+        }
+        let elapsed = start.elapsed();
+        connection.join()?;
+        verifier.flush(Instant::now());
+        Ok(verifier.report(elapsed))
+    }
+
+    pub fn run<B: Backend>(
+        &mut self,
+        td: Option<TerminalDevice>,
         terminal: &mut Terminal<B>,
     ) -> anyhow::Result<()> {
+        let deadline = self.duration.map(|duration| Instant::now() + duration);
         let mut ui = None;
 
         let mut textarea = TextArea::default();
-        let mut wraptext = WrapText {
-            lines: vec![String::new()],
-            block: None,
-        };
-        let mut text_state = WrapTextState {
-            position: Position::Follow,
-            movement_queue: Vec::new(),
-        };
+        // The command palette, replacing the input box while it's open.
+        let mut command: Option<TextArea> = None;
+        // The prompt of a search in the vim normal mode, also replacing the input box.
+        let mut search_prompt: Option<TextArea> = None;
+        let mut wraptext = WrapText::new();
+        wraptext.timestamps = self.timestamps;
+        wraptext.origin = self.timestamp_origin;
+        wraptext.gap_threshold = self.gap_threshold;
+        wraptext.line_number_width = self.line_number_width;
+        wraptext.line_number_start = self.line_number_start;
+        wraptext.wrapping = self.wrapping;
+        let mut text_state = WrapTextState::new();
+        // Used instead of text_state when a filter or the hex view is active.
+        let mut view_state = WrapTextState::new();
+        let mut shown_lines = 0;
 
-        let (stop_rx, stop_rc) = mpsc::channel();
-        let (read_thread_tx, read_rx) = mpsc::channel();
-        let (write_tx, write_thread_rx) = mpsc::channel();
+        // Events and received data wake the loop up right away instead of
+        // being polled for.
+        let wakeups = match self.event_loop {
+            EventLoop::Wait => {
+                let (waker, wakeups) = mpsc::channel();
+                self.waker = Some(waker.clone());
+                thread::spawn(move || read_events(waker));
+                Some(wakeups)
+            }
+            EventLoop::Poll => None,
+        };
+        let mut connection = td.map(|td| self.connect(td, true, &mut wraptext));
+        let mut connected_before = connection.is_some();
+        if connection.is_none() {
+            self.push_notice(&mut wraptext, "waiting for device");
+        }
+        let mut banner_wait = self.banner_wait;
         let mut update = true;
+        let mut last_tx_pending = 0;
+        let mut graph_stalled = false;
+        let mut last_modem_poll: Option<Instant> = None;
 
-        let _ = thread::spawn(|| term_io_loop(td, stop_rc, write_thread_rx, read_thread_tx));
         let res = 'event: loop {
-            thread::sleep(Duration::from_millis(10));
+            // Collects what the device prints right away, like a boot log, before
+            // anything else can get in the way.
+            if let (Some(wait), Some(connection)) = (banner_wait.take(), &connection) {
+                let deadline = Instant::now() + wait;
+                while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+                    let Ok(incoming) = connection.read_rx.recv_timeout(left) else {
+                        break;
+                    };
+                    if let Err(e) = self.handle_incoming(incoming, &mut wraptext) {
+                        break 'event Err(e);
+                    }
+                }
+            }
+            let pending_event = match &wakeups {
+                Some(wakeups) => {
+                    let woken = wakeups.recv_timeout(TIMER_TICK).ok();
+                    // All the received data is taken below, so only the next event matters.
+                    woken
+                        .into_iter()
+                        .chain(wakeups.try_iter())
+                        .find_map(|wakeup| match wakeup {
+                            Wakeup::Input(event) => Some(event),
+                            Wakeup::Received => None,
+                        })
+                }
+                None => {
+                    thread::sleep(Duration::from_millis(10));
+                    None
+                }
+            };
+            // Stops the same way as quitting, so the log is flushed.
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                self.timed_out = true;
+                break 'event Ok(());
+            }
             if update {
                 update = false;
+                let input_rows = textarea.lines().len().clamp(1, self.input_height) as u16;
+                self.update_filtered(&wraptext);
                 terminal.draw(|b| {
-                    if ui.is_none() {
-                        ui = Some(UI::new(b, self.grapher.is_some()));
+                    match ui.as_mut() {
+                        None => {
+                            ui = Some(UI::new(
+                                b,
+                                self.graph_shown(),
+                                self.gauge_shown(),
+                                input_rows,
+                                self.graph_split,
+                            ))
+                        }
+                        Some(ui) => ui.update_layout(
+                            b.size(),
+                            self.graph_shown(),
+                            self.gauge_shown(),
+                            input_rows,
+                            self.graph_split,
+                        ),
                     }
+                    let mut view =
+                        self.take_view(&wraptext, ui.as_ref().unwrap().ouput_chunk.width);
+                    let output = view.as_mut().unwrap_or(&mut wraptext);
+                    shown_lines = Lines::new(&output.lines, self.filtered_lines()).len();
                     ui.as_mut().unwrap().render(
                         b,
-                        &mut textarea,
-                        &mut wraptext,
-                        &mut text_state,
-                        &mut self.grapher,
-                    )
+                        command
+                            .as_mut()
+                            .or(search_prompt.as_mut())
+                            .unwrap_or(&mut textarea),
+                        output,
+                        self.output_state(&mut text_state, &mut view_state),
+                        self,
+                        connection
+                            .as_ref()
+                            .map_or(0, |c| c.tx_pending.load(Ordering::Relaxed)),
+                    );
+                    self.put_back_view(view);
                 })?;
             }
 
+            // Keeps the pending TX indicator up to date.
+            let tx_pending = connection
+                .as_ref()
+                .map_or(0, |c| c.tx_pending.load(Ordering::Relaxed));
+            if tx_pending != last_tx_pending {
+                last_tx_pending = tx_pending;
+                if tx_pending == 0 {
+                    self.keys_dropped = 0;
+                }
+                update = true;
+            }
+
+            self.expire_notifications();
+            if self.stats.as_ref().is_some_and(StatsFile::due) {
+                self.write_stats();
+            }
+            for failure in self
+                .triggers
+                .as_ref()
+                .map_or_else(Vec::new, Triggers::failures)
+            {
+                self.notify(failure, Level::Error);
+            }
+            if std::mem::take(&mut self.notifications_changed) {
+                update = true;
+            }
+
+            if self
+                .lock_flash
+                .is_some_and(|flash| flash.elapsed() >= LOCK_FLASH)
+            {
+                self.lock_flash = None;
+                update = true;
+            }
+            let alert_rung = self
+                .alerts
+                .as_mut()
+                .is_some_and(|alerts| std::mem::take(&mut alerts.ring));
+            if std::mem::take(&mut self.bell) || alert_rung {
+                // Written past the backend, tmux flags the window of an unfocused pane.
+                let mut stdout = io::stdout();
+                let _ = stdout.write_all(b"\x07").and_then(|()| stdout.flush());
+                update = true;
+            }
+            if let Some(alerts) = &mut self.alerts {
+                if alerts
+                    .flashed
+                    .is_some_and(|flash| flash.elapsed() >= alert::FLASH)
+                {
+                    alerts.flashed = None;
+                    update = true;
+                }
+            }
+
+            if let Some(control_device) = &self.control_device {
+                if last_modem_poll.is_none_or(|poll| poll.elapsed() >= MODEM_POLL_INTERVAL) {
+                    last_modem_poll = Some(Instant::now());
+                    let status = Some(control_device.modem_status().map_err(|e| e.to_string()));
+                    if status != self.modem_status {
+                        self.modem_status = status;
+                        update = true;
+                    }
+                }
+            }
+
+            // Shows that the samples have stopped, even though nothing else happens.
+            let stalled = self.grapher.as_ref().is_some_and(|g| g.stalled());
+            if stalled != graph_stalled {
+                graph_stalled = stalled;
+                update = true;
+            }
+
+            if connection.as_ref().is_some_and(|c| c.handle.is_finished()) {
+                let reason = match connection.take().unwrap().join() {
+                    Ok(()) => "disconnected".to_string(),
+                    Err(e) => format!("disconnected: {e}"),
+                };
+                self.push_notice(&mut wraptext, &reason);
+                if let Some(watcher) = &mut self.watcher {
+                    watcher.rescan_soon();
+                }
+                update = true;
+            }
+
+            if connection.is_none() {
+                if let Some(candidate) = self.watcher.as_mut().and_then(|w| w.poll()) {
+                    match TerminalDevice::open(&candidate.path, &self.serial_config) {
+                        Ok(td) => {
+                            match self.connect_found(
+                                &candidate,
+                                td,
+                                connected_before,
+                                &mut wraptext,
+                            ) {
+                                Ok(found) => connection = Some(found),
+                                Err(e) => break 'event Err(e),
+                            }
+                            connected_before = true;
+                            update = true;
+                        }
+                        // The permissions of the device might not have been
+                        // set up yet if it was just plugged in.
+                        Err(_) => self.watcher.as_mut().unwrap().rescan_soon(),
+                    }
+                }
+            }
+
+            // Lets a FIFO reader catch up on what has been held back.
+            if let Some(outfile) = &mut self.outfile {
+                let _ = outfile.flush();
+            }
+
+            match self.handle_piped(&mut wraptext) {
+                Ok(changed) => update |= changed,
+                Err(e) => break 'event Err(e),
+            }
+
+            match self.check_idle(connection.is_some()) {
+                Ok(changed) => update |= changed,
+                Err(e) => break 'event Err(e),
+            }
+
             // Checke for any incoming bytes from the terminal device.
-            if let Ok(res) = read_rx.try_recv() {
+            while let Some(Ok(incoming)) = connection.as_ref().map(|c| c.read_rx.try_recv()) {
                 update = true;
-                for byte in &res {
-                    if let Err(e) = self.parse_byte(*byte, &mut wraptext) {
-                        break 'event Err(e);
-                    };
+                if let Err(e) = self.handle_incoming(incoming, &mut wraptext) {
+                    break 'event Err(e);
+                }
+                if wakeups.is_none() {
+                    break;
                 }
             }
 
-            if let Ok(true) = event::poll(Duration::from_millis(1)) {
-                let event = event::read()?;
+            let event = match wakeups {
+                Some(_) => pending_event,
+                None => match event::poll(Duration::from_millis(1)) {
+                    Ok(true) => Some(event::read()),
+                    _ => None,
+                },
+            };
+            if let Some(event) = event {
+                let event = event?;
                 let mut should_update = true;
                 match event {
+                    Event::Key(key) if self.history_picker.is_some() => {
+                        let history: Vec<&str> = self
+                            .history
+                            .iter()
+                            .map(|entry| entry.text.as_str())
+                            .collect();
+                        let picker = self.history_picker.as_mut().unwrap();
+                        match key.code {
+                            KeyCode::Esc => self.history_picker = None,
+                            // Only inserted, so that it can be edited before it's sent.
+                            KeyCode::Enter => {
+                                if let Some(idx) = picker.selected() {
+                                    set_input(&mut textarea, &self.history[idx].text);
+                                    self.hex_input = self.history[idx].hex;
+                                    self.browsing_history = None;
+                                    self.input_error = None;
+                                    self.completion = None;
+                                }
+                                self.history_picker = None;
+                            }
+                            KeyCode::Up => picker.move_selection(-1),
+                            KeyCode::Down => picker.move_selection(1),
+                            KeyCode::PageUp => picker.move_selection(-10),
+                            KeyCode::PageDown => picker.move_selection(10),
+                            KeyCode::Backspace => picker.pop(&history),
+                            KeyCode::Char(c)
+                                if (key.modifiers - KeyModifiers::SHIFT).is_empty() =>
+                            {
+                                picker.push(c, &history)
+                            }
+                            _ => should_update = false,
+                        }
+                    }
+                    Event::Key(key) if command.is_some() => {
+                        let palette = command.as_mut().unwrap();
+                        let input = palette.lines()[0]
+                            .strip_prefix(':')
+                            .unwrap_or_default()
+                            .to_string();
+                        let mut command_error = None;
+                        match key.code {
+                            KeyCode::Esc => command = None,
+                            // A command that can't be parsed stays open with the error shown.
+                            KeyCode::Enter => match Command::parse(&input) {
+                                Ok(Command::Quit) => break 'event Ok(()),
+                                Ok(parsed) => {
+                                    command = None;
+                                    self.run_command(
+                                        parsed,
+                                        &mut wraptext,
+                                        &mut connection,
+                                        &mut text_state,
+                                        &mut view_state,
+                                    );
+                                }
+                                Err(e) => command_error = Some(e.to_string()),
+                            },
+                            KeyCode::Tab => {
+                                if let Some(completed) = command::complete(&input) {
+                                    set_input(palette, &format!(":{completed}"));
+                                }
+                            }
+                            _ => {
+                                palette.input(key);
+                                // Deleting the ':' closes the palette.
+                                if !palette.lines()[0].starts_with(':') {
+                                    command = None;
+                                }
+                            }
+                        }
+                        self.command_hint = command_error.or_else(|| {
+                            command.as_ref().map(|palette| {
+                                command_hint(
+                                    palette.lines()[0].strip_prefix(':').unwrap_or_default(),
+                                )
+                            })
+                        });
+                    }
+                    Event::Key(key) if search_prompt.is_some() => {
+                        let prompt = search_prompt.as_mut().unwrap();
+                        match key.code {
+                            KeyCode::Esc => search_prompt = None,
+                            KeyCode::Enter => {
+                                let pattern = prompt.lines()[0]
+                                    .strip_prefix('/')
+                                    .unwrap_or_default()
+                                    .to_string();
+                                search_prompt = None;
+                                // An empty search repeats the last one, like in vim.
+                                if !pattern.is_empty() {
+                                    self.search = Some(pattern);
+                                }
+                                self.vim_action(
+                                    Action::NextMatch,
+                                    &wraptext,
+                                    &mut text_state,
+                                    &mut view_state,
+                                    ui.as_ref(),
+                                    shown_lines,
+                                );
+                            }
+                            _ => {
+                                prompt.input(key);
+                                // Deleting the '/' closes the prompt.
+                                if !prompt.lines()[0].starts_with('/') {
+                                    search_prompt = None;
+                                }
+                            }
+                        }
+                    }
+                    // Nothing typed in the normal and visual modes is sent.
+                    Event::Key(key) if self.mode != Mode::Insert => match self.keymap.action(key) {
+                        Some(Action::Search) => {
+                            let mut prompt = TextArea::default();
+                            prompt.insert_char('/');
+                            search_prompt = Some(prompt);
+                        }
+                        Some(Action::Command) => {
+                            let mut palette = TextArea::default();
+                            palette.insert_char(':');
+                            self.command_hint = Some(command_hint(""));
+                            command = Some(palette);
+                        }
+                        Some(Action::SendAgain) => {
+                            self.send_again(connection.as_ref(), &mut wraptext)
+                        }
+                        Some(action) => self.vim_action(
+                            action,
+                            &wraptext,
+                            &mut text_state,
+                            &mut view_state,
+                            ui.as_ref(),
+                            shown_lines,
+                        ),
+                        None => should_update = false,
+                    },
+                    // Keys with modifiers still do what they do in the input.
+                    Event::Key(key)
+                        if self.output_focused
+                            && (key.modifiers - KeyModifiers::SHIFT).is_empty() =>
+                    {
+                        let state = self.output_state(&mut text_state, &mut view_state);
+                        match key.code {
+                            KeyCode::Up | KeyCode::Char('k') => state.scroll_up_by(1),
+                            KeyCode::Down | KeyCode::Char('j') => {
+                                state.scroll_down_by(1);
+                                // Like Ctrl+d, so that new lines are shown again.
+                                state.follow_at_bottom();
+                            }
+                            KeyCode::Left => state.scroll_left(),
+                            KeyCode::Right => state.scroll_right(),
+                            KeyCode::Esc => self.output_focused = false,
+                            // Nothing is typed while the output is focused.
+                            _ => should_update = false,
+                        }
+                    }
+                    Event::Key(key) if self.char_mode && key_char(&key).is_some() => {
+                        self.send_key(&connection, key_char(&key).unwrap());
+                    }
                     Event::Key(key) => {
+                        if key.code != KeyCode::Tab {
+                            self.completion = None;
+                        }
                         match key.code {
+                            KeyCode::Esc if self.vim => {
+                                let state = self.output_state(&mut text_state, &mut view_state);
+                                state.cursor = Some(
+                                    state
+                                        .bottom_line()
+                                        .unwrap_or(0)
+                                        .min(shown_lines.saturating_sub(1)),
+                                );
+                                self.mode = Mode::Normal;
+                            }
                             KeyCode::Esc => {
-                                return Ok(());
+                                break 'event Ok(());
                             },
+                            KeyCode::Char(':') if textarea.is_empty() => {
+                                let mut palette = TextArea::default();
+                                palette.insert_char(':');
+                                self.command_hint = Some(command_hint(""));
+                                command = Some(palette);
+                            }
+                            KeyCode::Enter if key.modifiers == KeyModifiers::ALT => {
+                                textarea.insert_newline();
+                            }
+                            // What's typed is kept until the input is unlocked.
+                            KeyCode::Enter if self.input_locked => {
+                                self.lock_flash = Some(Instant::now())
+                            }
+                            KeyCode::Tab
+                                if self.completer.is_some() && key.modifiers.is_empty() =>
+                            {
+                                self.complete(&mut textarea);
+                            }
                             KeyCode::Enter => {
-                                let mut line = textarea.lines()[0].clone();
-                                textarea = TextArea::default();
-                                if Some(&line) != self.history.last() {
-                                    self.history.push(line.clone());                                    
+                                self.send_input(connection.as_ref(), &mut textarea, &mut wraptext)
+                            }
+                            KeyCode::Up | KeyCode::Down
+                                if key.modifiers == KeyModifiers::ALT && self.graph_shown() =>
+                            {
+                                if let Some(ui) = &ui {
+                                    let rows = if key.code == KeyCode::Up { -1 } else { 1 };
+                                    self.graph_split = ui.moved_split(rows);
                                 }
-                                self.browsing_history = None;
-                                line.push('\n');
-                                write_tx.send(line.bytes().collect())?;
-                            },
+                            }
+                            // Moves the cursor when composing several lines.
+                            KeyCode::Up | KeyCode::Down if textarea.lines().len() > 1 => {
+                                textarea.input(key);
+                            }
                             KeyCode::Up => {
                                 if textarea.is_empty() && self.browsing_history.is_none() {
                                     self.browsing_history = Some(self.history.len() - 1);
@@ -202,9 +1507,8 @@ impl App {
                                 }
 
                                 if let Some(idx) = self.browsing_history {
-                                    textarea.delete_line_by_end();
-                                    textarea.delete_line_by_head();
-                                    textarea.insert_str(&self.history[idx]);
+                                    set_input(&mut textarea, &self.history[idx].text);
+                                    self.hex_input = self.history[idx].hex;
                                 }
 
                             },
@@ -213,17 +1517,157 @@ impl App {
                                     *idx = (*idx as i64 + 1).clamp(0, self.history.len() as i64-1) as usize;
                                 }
                                 if let Some(idx) = self.browsing_history {
-                                    textarea.delete_line_by_end();
-                                    textarea.delete_line_by_head();
-                                    textarea.insert_str(&self.history[idx]);
+                                    set_input(&mut textarea, &self.history[idx].text);
+                                    self.hex_input = self.history[idx].hex;
                                 }
 
                             },
                             _ => {
-                                if key.code == KeyCode::Char('d') && key.modifiers == KeyModifiers::CONTROL {
-                                    text_state.follow();
+                                // Deletes the next word in the input otherwise.
+                                if key.code == KeyCode::Char('d')
+                                    && key.modifiers == KeyModifiers::ALT
+                                    && self.error_shown()
+                                {
+                                    self.dismiss_notifications();
+                                } else if key.code == KeyCode::Char('d')
+                                    && key.modifiers == KeyModifiers::CONTROL
+                                {
+                                    self.output_state(&mut text_state, &mut view_state).follow();
+                                } else if key.code == KeyCode::Char('j')
+                                    && key.modifiers == KeyModifiers::ALT
+                                {
+                                    // The line numbers only match the unfiltered output.
+                                    if let (Some(line), false) =
+                                        (self.reconnect_line, self.view_active())
+                                    {
+                                        text_state.jump_to(line);
+                                    }
+                                } else if key.code == KeyCode::Char('t')
+                                    && key.modifiers == KeyModifiers::CONTROL
+                                {
+                                    wraptext.timestamps = wraptext.timestamps.next();
+                                } else if key.code == KeyCode::Char('z')
+                                    && key.modifiers == KeyModifiers::ALT
+                                {
+                                    wraptext.timestamps = Timestamps::Relative;
+                                    self.zero_timestamps(&mut wraptext);
+                                    self.push_notice(&mut wraptext, "timestamps zeroed");
+                                } else if key.code == KeyCode::Char('g')
+                                    && key.modifiers == KeyModifiers::CONTROL
+                                {
+                                    self.toggle_graph();
+                                } else if key.code == KeyCode::Char('g')
+                                    && key.modifiers == KeyModifiers::ALT
+                                {
+                                    self.toggle_gauge();
+                                } else if key.code == KeyCode::Char('m')
+                                    && key.modifiers == KeyModifiers::ALT
+                                {
+                                    self.toggle_bookmark(&mut wraptext, &text_state);
+                                } else if key.code == KeyCode::Char('n')
+                                    && key.modifiers == KeyModifiers::ALT
+                                {
+                                    self.jump_to_bookmark(&wraptext, &mut text_state, true);
+                                } else if key.code == KeyCode::Char('p')
+                                    && key.modifiers == KeyModifiers::ALT
+                                {
+                                    self.jump_to_bookmark(&wraptext, &mut text_state, false);
+                                } else if key.code == KeyCode::Char('o')
+                                    && key.modifiers == KeyModifiers::CONTROL
+                                {
+                                    self.input_locked = !self.input_locked;
+                                } else if key.code == KeyCode::Char('o')
+                                    && key.modifiers == KeyModifiers::ALT
+                                {
+                                    self.output_focused = !self.output_focused;
+                                } else if key.code == KeyCode::Char('.')
+                                    && key.modifiers == KeyModifiers::ALT
+                                {
+                                    // Leaves whatever is being typed alone.
+                                    self.send_again(connection.as_ref(), &mut wraptext);
+                                } else if key.code == KeyCode::Char('x')
+                                    && key.modifiers == KeyModifiers::CONTROL
+                                {
+                                    let notice =
+                                        match connection.as_ref().map_or(0, |c| c.cancel_pending())
+                                        {
+                                            0 => "nothing to cancel".to_string(),
+                                            n => format!("cancelled sending {n} bytes"),
+                                        };
+                                    self.notify(notice, Level::Info);
+                                } else if key.code == KeyCode::Char('h')
+                                    && key.modifiers == KeyModifiers::ALT
+                                {
+                                    if self.history.is_empty() {
+                                        self.notify("nothing in the history", Level::Info);
+                                    } else {
+                                        self.history_picker =
+                                            Some(HistoryPicker::new(&self.history_texts()));
+                                    }
+                                } else if key.code == KeyCode::Char('k')
+                                    && key.modifiers == KeyModifiers::ALT
+                                {
+                                    self.char_mode = !self.char_mode;
+                                } else if key.code == KeyCode::Char('x')
+                                    && key.modifiers == KeyModifiers::ALT
+                                {
+                                    self.hex_input = !self.hex_input;
+                                    self.input_error = None;
+                                } else if self.hex_input
+                                    && (key.modifiers - KeyModifiers::SHIFT).is_empty()
+                                    && matches!(key.code, KeyCode::Char(c) if !hexdump::is_input_char(c))
+                                {
+                                    // Rejected right away instead of when sending.
+                                } else if key.code == KeyCode::Char('c')
+                                    && key.modifiers == KeyModifiers::ALT
+                                {
+                                    let state = self.output_state(&mut text_state, &mut view_state);
+                                    match self.selected_text(&wraptext, state) {
+                                        Some(text) => self.copy_selection(&text, false),
+                                        None => {
+                                            self.copy_last_lines(&mut wraptext, self.copy_lines)
+                                        }
+                                    }
+                                } else if key.code == KeyCode::Left
+                                    && key.modifiers == KeyModifiers::ALT
+                                {
+                                    self.output_state(&mut text_state, &mut view_state)
+                                        .scroll_left();
+                                } else if key.code == KeyCode::Right
+                                    && key.modifiers == KeyModifiers::ALT
+                                {
+                                    self.output_state(&mut text_state, &mut view_state)
+                                        .scroll_right();
+                                } else if key.code == KeyCode::Char('e')
+                                    && key.modifiers == KeyModifiers::ALT
+                                {
+                                    let path = self.graph_export.clone();
+                                    self.export_graph(&path);
+                                } else if key.code == KeyCode::Char('l')
+                                    && key.modifiers == KeyModifiers::ALT
+                                {
+                                    if let Some(grapher) = &mut self.grapher {
+                                        grapher.reset_peaks();
+                                    }
+                                } else if key.code == KeyCode::Char('r')
+                                    && key.modifiers == KeyModifiers::ALT
+                                {
+                                    self.reapply_config(connection.as_ref());
+                                } else if key.code == KeyCode::Char('a')
+                                    && key.modifiers == KeyModifiers::ALT
+                                {
+                                    if let Some(grapher) = &mut self.grapher {
+                                        grapher.refit();
+                                    }
+                                } else if let (
+                                    KeyCode::Char(digit @ '1'..='9'),
+                                    KeyModifiers::ALT,
+                                ) = (key.code, key.modifiers)
+                                {
+                                    self.toggle_series(digit as usize - '1' as usize);
                                 } else {
                                     self.browsing_history = None;
+                                    self.input_error = None;
                                     textarea.input(key);
                                 }
                             }
@@ -231,19 +1675,75 @@ impl App {
                     }
                     Event::Mouse(mouse_event) => match mouse_event.kind {
                         event::MouseEventKind::ScrollDown => {
-                            text_state.scroll_down();
+                            let rows = self.scroll_rows(mouse_event.modifiers, ui.as_ref());
+                            self.output_state(&mut text_state, &mut view_state)
+                                .scroll_down_by(rows);
                         }
                         event::MouseEventKind::ScrollUp => {
-                            text_state.scroll_up();
+                            let rows = self.scroll_rows(mouse_event.modifiers, ui.as_ref());
+                            self.output_state(&mut text_state, &mut view_state)
+                                .scroll_up_by(rows);
                         }
-                        _ => should_update = false,
-                    },
-                    Event::Resize(w, h) => {
-                        if let Some(ui) = ui.as_mut() {
-                            ui.update_size(w, h, self.grapher.is_some());
+                        event::MouseEventKind::Down(MouseButton::Left)
+                            if ui.as_ref().is_some_and(|ui| ui.on_divider(mouse_event.row)) =>
+                        {
+                            self.dragging_divider = true;
                         }
-                    }
-                    _ => should_update = false,
+                        event::MouseEventKind::Drag(MouseButton::Left) if self.dragging_divider => {
+                            if let Some(ui) = &ui {
+                                self.graph_split = ui.split_at(mouse_event.row);
+                            }
+                        }
+                        event::MouseEventKind::Up(MouseButton::Left) if self.dragging_divider => {
+                            self.dragging_divider = false;
+                        }
+                        event::MouseEventKind::Moved => {
+                            let hovered =
+                                ui.as_ref().is_some_and(|ui| ui.on_divider(mouse_event.row));
+                            should_update = hovered != self.divider_hovered;
+                            self.divider_hovered = hovered;
+                        }
+                        event::MouseEventKind::Down(MouseButton::Left)
+                            if ui.as_ref().is_some_and(|ui| {
+                                ui.in_output_text(mouse_event.column, mouse_event.row)
+                            }) =>
+                        {
+                            self.selecting = self
+                                .output_state(&mut text_state, &mut view_state)
+                                .start_selection(mouse_event.column, mouse_event.row);
+                        }
+                        event::MouseEventKind::Drag(MouseButton::Left) if self.selecting => {
+                            self.output_state(&mut text_state, &mut view_state)
+                                .extend_selection(mouse_event.column, mouse_event.row);
+                        }
+                        event::MouseEventKind::Up(MouseButton::Left) if self.selecting => {
+                            self.selecting = false;
+                            let state = self.output_state(&mut text_state, &mut view_state);
+                            // Nothing is copied if the mouse didn't move off the first character.
+                            match self.selected_text(&wraptext, state) {
+                                Some(text) if self.copy_on_select => {
+                                    self.copy_selection(&text, true)
+                                }
+                                _ => should_update = false,
+                            }
+                        }
+                        event::MouseEventKind::Down(MouseButton::Left)
+                        | event::MouseEventKind::Drag(MouseButton::Left) => {
+                            let state = self.output_state(&mut text_state, &mut view_state);
+                            match state.scrollbar_line(
+                                mouse_event.column,
+                                mouse_event.row,
+                                shown_lines,
+                            ) {
+                                Some(line) => state.jump_to(line),
+                                None => should_update = false,
+                            }
+                        }
+                        _ => should_update = false,
+                    },
+                    // The layout is updated when drawing.
+                    Event::Resize(_, _) => {}
+                    _ => should_update = false,
                 }
                 if should_update {
                     update = true;
@@ -251,52 +1751,1261 @@ impl App {
             }
         };
 
-        let _ = stop_rx.send(());
+        if let Some(connection) = connection {
+            let _ = connection.stop_tx.send(());
+        }
+        self.output = std::mem::take(&mut wraptext.lines);
+        // Closes the input of the pipe command and waits for it to exit.
+        self.pipe = None;
+
+        res.and_then(|()| self.finish_log())
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    /// Whether the output is shown through a filter or as hex instead of as it is.
+    fn view_active(&self) -> bool {
+        self.hex_view || !self.filters.is_empty()
+    }
+
+    /// The scroll state of what's currently shown in the output pane.
+    fn output_state<'s>(
+        &self,
+        text_state: &'s mut WrapTextState,
+        view_state: &'s mut WrapTextState,
+    ) -> &'s mut WrapTextState {
+        if self.view_active() {
+            view_state
+        } else {
+            text_state
+        }
+    }
+
+    /// How far a notch of the mouse wheel scrolls, half the output with Shift or Ctrl held.
+    fn scroll_rows(&self, modifiers: KeyModifiers, ui: Option<&UI>) -> u16 {
+        if modifiers.intersects(KeyModifiers::SHIFT | KeyModifiers::CONTROL) {
+            ui.map_or(1, |ui| (ui.ouput_chunk.height.saturating_sub(2) / 2).max(1))
+        } else {
+            self.scroll_step
+        }
+    }
+
+    /// Takes the hex rows out to draw them instead of the received lines when
+    /// the hex view is on, after updating them for an output pane `width`
+    /// columns wide. They're given back with `put_back_view`.
+    fn take_view(&mut self, wraptext: &WrapText, width: u16) -> Option<WrapText<'static>> {
+        if !self.hex_view {
+            return None;
+        }
+        // Borders, scrollbar and line numbers.
+        let width = (width as usize).saturating_sub(7);
+        self.hex
+            .update(hexdump::bytes_per_row(width), &self.filters);
+        let mut view = WrapText::new();
+        view.line_number_width = wraptext.line_number_width;
+        view.wrapping = wraptext.wrapping;
+        // The rows are made to fit the whole width.
+        view.wrapping.column = None;
+        view.lines = std::mem::take(&mut self.hex.rows);
+        Some(view)
+    }
+
+    fn put_back_view(&mut self, view: Option<WrapText>) {
+        if let Some(view) = view {
+            self.hex.rows = view.lines;
+        }
+    }
+
+    /// The indices of the lines that are shown when only some of them are.
+    fn filtered_lines(&self) -> Option<&[usize]> {
+        match self.hex_view {
+            true => (!self.filters.is_empty()).then_some(&self.hex.filtered),
+            false => (!self.filters.is_empty()).then_some(&self.filtered),
+        }
+    }
+
+    /// The lines shown in the output pane, the hex rows as they were last drawn
+    /// when the hex view is on.
+    fn shown_lines<'a>(&'a self, wraptext: &'a WrapText) -> Lines<'a> {
+        match self.hex_view {
+            true => Lines::new(&self.hex.rows, self.filtered_lines()),
+            false => Lines::new(&wraptext.lines, self.filtered_lines()),
+        }
+    }
+
+    /// Checks the lines that ended since the last time against the filters,
+    /// and the one being received again.
+    fn update_filtered(&mut self, wraptext: &WrapText) {
+        if self.filters.is_empty() {
+            return;
+        }
+        let receiving = wraptext.lines.len() - 1;
+        if self.filtered_upto > receiving {
+            // The lines have been cleared.
+            return self.refilter(wraptext);
+        }
+        if self
+            .filtered
+            .last()
+            .is_some_and(|&idx| idx >= self.filtered_upto)
+        {
+            self.filtered.pop();
+        }
+        for idx in self.filtered_upto..wraptext.lines.len() {
+            if self.filters.matches(&wraptext.lines[idx].text) {
+                self.filtered.push(idx);
+            }
+        }
+        self.filtered_upto = receiving;
+    }
+
+    /// Checks every line against the filters, after they've changed.
+    fn refilter(&mut self, wraptext: &WrapText) {
+        self.filtered.clear();
+        self.filtered_upto = 0;
+        self.update_filtered(wraptext);
+    }
+
+    /// The text of every entry in the history, the oldest first.
+    fn history_texts(&self) -> Vec<&str> {
+        self.history
+            .iter()
+            .map(|entry| entry.text.as_str())
+            .collect()
+    }
+
+    /// Executes a command from the command palette.
+    fn run_command(
+        &mut self,
+        command: Command,
+        wraptext: &mut WrapText,
+        connection: &mut Option<Connection>,
+        text_state: &mut WrapTextState,
+        view_state: &mut WrapTextState,
+    ) {
+        let filters_changed = matches!(
+            command,
+            Command::AddFilter(_)
+                | Command::Filter(_)
+                | Command::FilterMode(_)
+                | Command::RemoveFilter(_)
+                | Command::Clear
+        );
+        match command {
+            Command::Baud { rate, baud_rate } => {
+                self.serial_config.baud_rate = baud_rate;
+                match self.reopen(connection) {
+                    Ok(()) => self.notify(format!("baud rate set to {rate}"), Level::Info),
+                    Err(e) => self.notify(format!("reopening the device: {e}"), Level::Error),
+                }
+            }
+            Command::Alert { number, enabled } => {
+                let Some(alerts) = &mut self.alerts else {
+                    return self.notify("no alerts are set, add them with --alert", Level::Error);
+                };
+                match alerts.set_enabled(number, enabled) {
+                    Ok(notice) => self.notify(notice, Level::Info),
+                    Err(e) => self.notify(e.to_string(), Level::Error),
+                }
+            }
+            Command::Reconfigure => self.reapply_config(connection.as_ref()),
+            // Handled by the event loop, which it ends.
+            Command::Quit => {}
+            Command::Clear => {
+                wraptext.lines = vec![Line::default()];
+                self.hex.clear();
+                self.cur_line.clear();
+                self.cur_widths.clear();
+                self.jsonl_line.clear();
+                self.prev_line = None;
+                self.reconnect_line = None;
+                if let Some(screen) = &mut self.screen {
+                    *screen = Screen::new();
+                }
+                text_state.follow();
+            }
+            Command::Copy(n) => self.copy_last_lines(wraptext, n.unwrap_or(self.copy_lines)),
+            Command::AddFilter(pattern) => self.filters.patterns.push(pattern),
+            Command::Filter(filter) => self.filters.patterns = filter.into_iter().collect(),
+            Command::FilterMode(combine) => self.filters.combine = combine,
+            Command::RemoveFilter(pattern) => {
+                if !self.filters.remove(pattern.as_deref()) {
+                    let notice = match pattern {
+                        Some(pattern) => format!("there is no filter '{pattern}'"),
+                        None => "there are no filters".to_string(),
+                    };
+                    self.notify(notice, Level::Error);
+                }
+            }
+            Command::Export(path) => {
+                let path = path.unwrap_or_else(|| self.graph_export.clone());
+                self.export_graph(&path);
+            }
+            Command::Goto(number) => self.goto_line(wraptext, text_state, number),
+            Command::Gauge => self.toggle_gauge(),
+            Command::Graph => self.toggle_graph(),
+            Command::Hex => self.hex_view = !self.hex_view,
+            Command::Highlight => {
+                self.create_grapher();
+                self.highlight_values = !self.highlight_values;
+            }
+            Command::Log(out) => self.switch_log(out.as_deref()),
+            Command::LogY => {
+                if let Some(grapher) = &mut self.grapher {
+                    grapher.log_y = !grapher.log_y;
+                    grapher.refit();
+                }
+            }
+            Command::Save(path) => match save_lines(&path, &wraptext.lines) {
+                Ok(()) => self.notify(
+                    format!("saved {} lines to {}", wraptext.lines.len(), path.display()),
+                    Level::Info,
+                ),
+                Err(e) => self.notify(format!("saving {}: {e}", path.display()), Level::Error),
+            },
+            Command::Timestamps => wraptext.timestamps = wraptext.timestamps.next(),
+            Command::Wrap(wrap) => {
+                wraptext.wrapping.wrap = wrap;
+                text_state.h_scroll = 0;
+            }
+        }
+        // What the view shows might have changed.
+        *view_state = WrapTextState::new();
+        if filters_changed {
+            self.refilter(wraptext);
+            self.hex.reformat();
+        }
+    }
+
+    /// Logs to `out` from now on, which is opened like --out-file, or stops
+    /// logging.
+    fn switch_log(&mut self, out: Option<&str>) {
+        let opened = match out {
+            Some(out) => match self.open_log(out) {
+                Ok(opened) => Some(opened),
+                Err(e) => return self.notify(format!("{e:#}"), Level::Error),
+            },
+            None if self.outfile.is_none() => return self.notify("not logging", Level::Info),
+            None => None,
+        };
+        if let Err(e) = self.finish_log() {
+            self.notify(format!("finishing the log: {e}"), Level::Error);
+        }
+        let notice = match &opened {
+            Some((_, path)) => format!("logging to {}", path.display()),
+            None => "stopped logging".to_string(),
+        };
+        (self.outfile, self.log_path) = opened.unzip();
+        self.log_out = out.map(str::to_string);
+        self.notify(notice, Level::Info);
+    }
+
+    fn open_log(&self, out: &str) -> anyhow::Result<(LogSink, PathBuf)> {
+        if self.log_split_idle.is_some() {
+            logfile::open_segment(out, self.device_path.as_deref())
+        } else {
+            logfile::open(out, self.device_path.as_deref())
+        }
+    }
+
+    /// Continues the log in a new file since the data has stopped for a while.
+    fn split_log(&mut self) {
+        let (Some(out), Some(LogSink::File(_))) = (self.log_out.clone(), &self.outfile) else {
+            return;
+        };
+        if let Err(e) = self.finish_log() {
+            self.notify(format!("finishing the log: {e}"), Level::Error);
+        }
+        match self.open_log(&out) {
+            Ok((sink, path)) => {
+                self.notify(format!("logging to {}", path.display()), Level::Info);
+                self.outfile = Some(sink);
+                self.log_path = Some(path);
+            }
+            // Logging goes on in the old file rather than not at all.
+            Err(e) => self.notify(format!("{e:#}"), Level::Error),
+        }
+    }
+
+    /// Starts the connection to `td` at `candidate`, which the watcher found.
+    /// A reconnect is marked in the output, and only sets the device up like
+    /// the first connection does with `rerun_on_reconnect`.
+    fn connect_found(
+        &mut self,
+        candidate: &Candidate,
+        td: TerminalDevice,
+        connected_before: bool,
+        wraptext: &mut WrapText,
+    ) -> io::Result<Connection> {
+        if connected_before {
+            self.reconnects += 1;
+            self.push_data_loss(wraptext, "reconnect")?;
+            self.push_reconnect_divider(wraptext);
+        }
+        if connected_before && self.zero_timestamps_on_reconnect {
+            self.zero_timestamps(wraptext);
+        }
+        let startup = !connected_before || self.rerun_on_reconnect;
+        self.device_name = candidate.display_name();
+        self.device_path = Some(candidate.path.clone());
+        let notice = format!("connected to {}", self.device_name);
+        self.push_notice(wraptext, &notice);
+        Ok(self.connect(td, startup, wraptext))
+    }
+
+    /// Starts the connection to `td`. With `startup` the device is first reset
+    /// if `reset_on_connect` is set, and then sent the `on_connect` lines.
+    fn connect(
+        &mut self,
+        td: TerminalDevice,
+        startup: bool,
+        wraptext: &mut WrapText,
+    ) -> Connection {
+        if startup && self.reset_on_connect {
+            if let Err(e) = td.pulse_reset() {
+                self.push_notice(wraptext, &format!("couldn't reset the device: {e}"));
+            }
+        }
+        let connection = Connection::start(
+            td,
+            self.tx_settings,
+            self.tx_written.clone(),
+            self.waker.clone(),
+        );
+        if startup {
+            for text in self.on_connect.clone() {
+                if let Err(e) = self.send_entry(
+                    Some(&connection),
+                    &HistoryEntry { text, hex: false },
+                    wraptext,
+                ) {
+                    self.push_notice(
+                        wraptext,
+                        &format!("couldn't send the on-connect lines: {e}"),
+                    );
+                    break;
+                }
+            }
+        }
+        connection
+    }
+
+    /// Sets the serial settings of the connected device again, for when the
+    /// driver or the device has reset them and the output is garbled.
+    fn reapply_config(&mut self, connection: Option<&Connection>) {
+        match connection.map(|connection| connection.device.reapply_config()) {
+            Some(Ok(())) => self.notify("serial settings applied again", Level::Info),
+            Some(Err(e)) => self.notify(format!("applying the serial settings: {e}"), Level::Error),
+            None => self.notify("not connected", Level::Error),
+        }
+    }
+
+    /// Reopens the connected device so that it's configured according to `serial_config`.
+    fn reopen(&mut self, connection: &mut Option<Connection>) -> anyhow::Result<()> {
+        let (Some(old), Some(path)) = (connection.take(), self.device_path.clone()) else {
+            // The settings are used the next time a device is connected.
+            return Ok(());
+        };
+        let _ = old.join();
+        let td = TerminalDevice::open(&path, &self.serial_config)?;
+        *connection = Some(Connection::start(
+            td,
+            self.tx_settings,
+            self.tx_written.clone(),
+            self.waker.clone(),
+        ));
+        Ok(())
+    }
+
+    /// Moves the output so that the line with the given number is at the top.
+    /// Numbers outside of the output go to the first or last line.
+    fn goto_line(
+        &mut self,
+        wraptext: &mut WrapText,
+        text_state: &mut WrapTextState,
+        number: usize,
+    ) {
+        // The numbers of a view aren't the ones in wraptext.
+        if self.view_active() {
+            return self.notify(
+                "line numbers can't be jumped to with a filter or the hex view",
+                Level::Error,
+            );
+        }
+        let first = self.line_number_start;
+        let last = first + wraptext.lines.len() - 1;
+        let line = number.clamp(first, last);
+        if number > last {
+            text_state.follow();
+            self.notify(
+                format!("there is no line {number}, the last one is {last}"),
+                Level::Info,
+            );
+        } else {
+            text_state.jump_to(line - first);
+        }
+        if number < first {
+            self.notify(
+                format!("there is no line {number}, went to line {line}"),
+                Level::Info,
+            );
+        }
+    }
+
+    /// Bookmarks the line at the top of the output, or removes its bookmark.
+    fn toggle_bookmark(&mut self, wraptext: &mut WrapText, text_state: &WrapTextState) {
+        // The lines of a view aren't the ones in wraptext.
+        if self.view_active() {
+            return;
+        }
+        if let Some(line) = wraptext.lines.get_mut(text_state.top_line) {
+            line.bookmarked = !line.bookmarked;
+        }
+    }
+
+    /// Moves the output to the next or previous bookmark, wrapping around at the ends.
+    fn jump_to_bookmark(&self, wraptext: &WrapText, text_state: &mut WrapTextState, forward: bool) {
+        if self.view_active() {
+            return;
+        }
+        let top = text_state.top_line;
+        let bookmarks = || {
+            wraptext
+                .lines
+                .iter()
+                .enumerate()
+                .filter(|(_, line)| line.bookmarked)
+                .map(|(i, _)| i)
+        };
+        let target = if forward {
+            bookmarks()
+                .find(|i| *i > top)
+                .or_else(|| bookmarks().next())
+        } else {
+            bookmarks()
+                .rev()
+                .find(|i| *i < top)
+                .or_else(|| bookmarks().next_back())
+        };
+        if let Some(line) = target {
+            text_state.jump_to(line);
+        }
+    }
+
+    fn graph_shown(&self) -> bool {
+        self.graph_visible && !self.graph_sparkline && self.grapher.is_some()
+    }
+
+    /// Shows or hides the graph pane.
+    fn toggle_graph(&mut self) {
+        self.create_grapher();
+        self.graph_visible = !self.graph_visible;
+    }
+
+    /// Creates the grapher if there isn't one yet, so that values are only
+    /// extracted once something uses them.
+    fn create_grapher(&mut self) {
+        if self.grapher.is_none() {
+            self.grapher = Some((self.new_grapher)());
+        }
+    }
+
+    fn gauge_shown(&self) -> bool {
+        self.gauge_visible && self.grapher.is_some()
+    }
+
+    /// Shows or hides the gauge.
+    fn toggle_gauge(&mut self) {
+        self.create_grapher();
+        self.gauge_visible = !self.gauge_visible;
+    }
+
+    /// Sends the last entry of the history again, encoded with the current
+    /// settings like when it was entered.
+    fn send_again(&mut self, connection: Option<&Connection>, wraptext: &mut WrapText) {
+        match self.history.last().cloned() {
+            Some(_) if self.input_locked => self.lock_flash = Some(Instant::now()),
+            Some(entry) => {
+                // Entries that were sent can be sent again.
+                let _ = self.send_entry(connection, &entry, wraptext);
+            }
+            None => self.notify("nothing to send again", Level::Info),
+        }
+    }
+
+    /// Sends what was entered in the input box. Text has every line ended by
+    /// a newline, hex only gets one if `hex_newline` is set.
+    fn send_entry(
+        &mut self,
+        connection: Option<&Connection>,
+        entry: &HistoryEntry,
+        wraptext: &mut WrapText,
+    ) -> anyhow::Result<()> {
+        let bytes = if entry.hex {
+            let mut bytes = hexdump::parse(&entry.text)?;
+            if self.hex_newline {
+                bytes.push(b'\n');
+            }
+            bytes
+        } else {
+            self.tx_encoding.encode(&format!("{}\n", entry.text))?
+        };
+        // What's typed is shown and logged as it is, whatever it's encoded as.
+        let sent = if entry.hex {
+            bytes.strip_suffix(b"\n").unwrap_or(&bytes)
+        } else {
+            entry.text.as_bytes()
+        };
+        if let Some(prefix) = self.log_tx.clone() {
+            // Stamped when the output shows timestamps, the received lines in
+            // the log aren't.
+            let time = match wraptext.timestamps {
+                Timestamps::Off => String::new(),
+                _ => format!("{} ", timefmt::clock_time(SystemTime::now())),
+            };
+            for line in sent.split(|&b| b == b'\n') {
+                let text = self.decoder.sent_log_text(line);
+                self.log_marker(&format!("{prefix}{time}{text}"))?;
+            }
+        }
+        if let Some(jsonl) = &mut self.jsonl {
+            for line in sent.split(|&b| b == b'\n') {
+                let text = self.decoder.sent_log_text(line);
+                jsonl.write(JsonlDirection::Tx, SystemTime::now(), &text)?;
+            }
+        }
+        if self.local_echo {
+            for line in sent.split(|&b| b == b'\n') {
+                let text = self.decoder.echo_text(line);
+                self.push_marker(wraptext, text, Style::default().fg(Color::LightBlue));
+            }
+        }
+        if let Some(connection) = connection {
+            connection.send(bytes);
+        }
+        Ok(())
+    }
+
+    /// Sends what's typed when Enter is pressed and adds it to the history.
+    /// Nothing is sent while the input is empty, unless `allow_empty_send`.
+    fn send_input(
+        &mut self,
+        connection: Option<&Connection>,
+        textarea: &mut TextArea,
+        wraptext: &mut WrapText,
+    ) {
+        // Many devices take a bare line ending as a command.
+        if !self.allow_empty_send && textarea.lines().iter().all(|line| line.is_empty()) {
+            return;
+        }
+        let entry = HistoryEntry {
+            text: textarea.lines().join("\n"),
+            hex: self.hex_input,
+        };
+        match self.send_entry(connection, &entry, wraptext) {
+            Ok(()) => {
+                *textarea = TextArea::default();
+                if Some(&entry) != self.history.last() {
+                    self.history.push(entry);
+                }
+                self.browsing_history = None;
+            }
+            // The input is kept so that it can be fixed.
+            Err(e) => self.input_error = Some(e.to_string()),
+        }
+    }
+
+    /// Completes the word before the cursor, or replaces it with the next
+    /// candidate if Tab was pressed right before.
+    fn complete(&mut self, textarea: &mut TextArea) {
+        let replaced = match &mut self.completion {
+            Some(completion) => {
+                let len = completion.current().chars().count();
+                completion.advance();
+                len
+            }
+            None => {
+                let (row, col) = textarea.cursor();
+                let completer = self.completer.as_ref().unwrap();
+                let Some(completion) = completer.complete(&textarea.lines()[row], col) else {
+                    return;
+                };
+                let len = col - completion.start;
+                self.completion = Some(completion);
+                len
+            }
+        };
+        let completion = self.completion.as_ref().unwrap();
+        textarea.delete_str(completion.start, replaced);
+        textarea.insert_str(completion.current());
+        if !completion.ambiguous() {
+            self.completion = None;
+        }
+    }
+
+    /// Queues a key typed in char mode, paced like everything else that is
+    /// sent, unless too much is waiting already.
+    fn send_key(&mut self, connection: &Option<Connection>, c: char) {
+        if self.input_locked {
+            self.lock_flash = Some(Instant::now());
+            return;
+        }
+        let Some(connection) = connection else {
+            return;
+        };
+        let bytes = match self.tx_encoding.encode(c.encode_utf8(&mut [0; 4])) {
+            Ok(bytes) => bytes,
+            Err(e) => return self.notify(e.to_string(), Level::Error),
+        };
+        if connection.tx_pending.load(Ordering::Relaxed) + bytes.len() > self.char_buffer {
+            self.keys_dropped += 1;
+        } else {
+            connection.send(bytes);
+        }
+    }
+
+    /// Copies the last `n` received lines to the clipboard.
+    fn copy_last_lines(&mut self, wraptext: &mut WrapText, n: usize) {
+        // The last line is the one still being received.
+        let lines = match wraptext.lines.split_last() {
+            Some((last, rest)) if last.text.is_empty() => rest,
+            _ => &wraptext.lines[..],
+        };
+        let lines = &lines[lines.len().saturating_sub(n)..];
+        let text: String = lines
+            .iter()
+            .map(|line| format!("{}\n", line.text))
+            .collect();
+        match clipboard::copy(&text) {
+            Ok(()) => self.notify(
+                format!("copied {} lines to the clipboard", lines.len()),
+                Level::Info,
+            ),
+            Err(e) => self.notify(format!("can't copy to the clipboard: {e}"), Level::Error),
+        }
+    }
 
-        res.map_err(|e| anyhow::anyhow!(e))
+    /// Carries out what a key does in the vim normal and visual modes.
+    fn vim_action(
+        &mut self,
+        action: Action,
+        wraptext: &WrapText,
+        text_state: &mut WrapTextState,
+        view_state: &mut WrapTextState,
+        ui: Option<&UI>,
+        shown_lines: usize,
+    ) {
+        let last = shown_lines.saturating_sub(1);
+        let half_page = ui.map_or(1, |ui| {
+            (ui.ouput_chunk.height.saturating_sub(2) as usize / 2).max(1)
+        });
+        let state = self.output_state(text_state, view_state);
+        let cursor = state.cursor.unwrap_or(last).min(last);
+        let moved = match action {
+            Action::Down => cursor + 1,
+            Action::Up => cursor.saturating_sub(1),
+            Action::HalfPageDown => cursor + half_page,
+            Action::HalfPageUp => cursor.saturating_sub(half_page),
+            Action::Top => 0,
+            Action::Bottom => last,
+            Action::NextMatch | Action::PreviousMatch => {
+                let Some(pattern) = &self.search else {
+                    return self.notify("nothing has been searched for", Level::Info);
+                };
+                match vim::find(
+                    self.shown_lines(wraptext),
+                    pattern,
+                    cursor,
+                    action == Action::NextMatch,
+                ) {
+                    Some(line) => line,
+                    None => {
+                        let notice = format!("'{pattern}' not found");
+                        return self.notify(notice, Level::Info);
+                    }
+                }
+            }
+            Action::Visual => {
+                self.mode = match self.mode {
+                    Mode::Visual { .. } => Mode::Normal,
+                    _ => Mode::Visual { anchor: cursor },
+                };
+                cursor
+            }
+            Action::Cancel => {
+                self.mode = Mode::Normal;
+                cursor
+            }
+            Action::Yank => {
+                let anchor = match self.mode {
+                    Mode::Visual { anchor } => anchor,
+                    _ => cursor,
+                };
+                self.mode = Mode::Normal;
+                state.selection = Some(Selection::lines(anchor, cursor));
+                let text = self.selected_text(wraptext, state);
+                state.selection = None;
+                if let Some(text) = text {
+                    self.copy_selection(&text, false);
+                }
+                return;
+            }
+            Action::Insert => {
+                self.mode = Mode::Insert;
+                state.cursor = None;
+                state.selection = None;
+                return;
+            }
+            // Handled by the event loop since they open a prompt or send.
+            Action::Search | Action::Command | Action::SendAgain => return,
+        };
+        let cursor = moved.min(last);
+        state.cursor = Some(cursor);
+        state.selection = match self.mode {
+            Mode::Visual { anchor } => Some(Selection::lines(anchor, cursor)),
+            _ => None,
+        };
+        if action == Action::Bottom {
+            state.follow();
+        } else {
+            state.reveal(cursor);
+        }
+    }
+
+    /// The text selected in the output, from the lines as they're shown.
+    fn selected_text(&self, wraptext: &WrapText, state: &WrapTextState) -> Option<String> {
+        state.selected_text(self.shown_lines(wraptext))
+    }
+
+    /// Copies selected text to the clipboard, or to the primary selection where there is one.
+    fn copy_selection(&mut self, text: &str, primary: bool) {
+        let copied = if primary {
+            clipboard::copy_primary(text)
+        } else {
+            clipboard::copy(text)
+        };
+        match copied {
+            Ok(()) => self.notify(
+                format!("copied {} characters", text.chars().count()),
+                Level::Info,
+            ),
+            Err(e) => self.notify(format!("can't copy to the clipboard: {e}"), Level::Error),
+        }
+    }
+
+    /// Hides or shows the graph series at `idx`.
+    fn toggle_series(&mut self, idx: usize) {
+        let notice = match self
+            .grapher
+            .as_mut()
+            .and_then(|grapher| grapher.toggle_series(idx))
+        {
+            Some(series) => {
+                let name = match series.name.as_str() {
+                    "" => format!("series {}", idx + 1),
+                    name => name.to_string(),
+                };
+                format!("{name} {}", if series.visible { "shown" } else { "hidden" })
+            }
+            None => format!("there is no series {}", idx + 1),
+        };
+        self.notify(notice, Level::Info);
+    }
+
+    /// Writes the values the grapher has kept to an SVG image, or to a CSV
+    /// file if the file name ends with ".csv".
+    fn export_graph(&mut self, path: &Path) {
+        let Some(grapher) = &self.grapher else {
+            return self.notify("there is no graph to export", Level::Error);
+        };
+        let (width, height) = self.graph_export_size;
+        let exported = if path.extension().is_some_and(|ext| ext == "csv") {
+            csv::export(grapher, path, self.csv_utc)
+        } else {
+            svg::export(grapher, path, width, height)
+        };
+        match exported {
+            Ok(()) => self.notify(format!("graph saved to {}", path.display()), Level::Info),
+            Err(e) => self.notify(
+                format!("exporting the graph to {}: {e}", path.display()),
+                Level::Error,
+            ),
+        }
+    }
+
+    /// Makes the relative timestamps count from now.
+    fn zero_timestamps(&mut self, wraptext: &mut WrapText) {
+        let origin = Instant::now();
+        self.timestamp_origin = origin;
+        wraptext.origin = origin;
+    }
+
+    /// Shows `message` in the status bar for a moment, or until it's dismissed
+    /// if it's an error. Unlike a notice it doesn't end up in the output.
+    pub fn notify(&mut self, message: impl Into<String>, level: Level) {
+        if level == Level::Error {
+            self.errors += 1;
+        }
+        self.notifications.push(Notification {
+            message: message.into(),
+            level,
+            created: Instant::now(),
+        });
+        self.notifications_changed = true;
+    }
+
+    fn expire_notifications(&mut self) {
+        let shown = self.notifications.len();
+        self.notifications
+            .retain(|n| n.level == Level::Error || n.created.elapsed() < NOTIFICATION_DURATION);
+        self.notifications_changed |= self.notifications.len() != shown;
+    }
+
+    /// Whether an error is shown, which stays until it's dismissed.
+    fn error_shown(&self) -> bool {
+        self.notifications.iter().any(|n| n.level == Level::Error)
+    }
+
+    fn dismiss_notifications(&mut self) {
+        self.notifications_changed |= !self.notifications.is_empty();
+        self.notifications.clear();
+    }
+
+    /// Adds a line with information from rterm itself to the output.
+    fn push_notice(&mut self, wraptext: &mut WrapText, notice: &str) {
+        self.push_marker(
+            wraptext,
+            format!("[{notice}]"),
+            Style::default().fg(Color::Cyan),
+        );
+    }
+
+    /// Adds a line of its own to the output, ending the current one.
+    fn push_marker(&mut self, wraptext: &mut WrapText, text: String, style: Style) {
+        if !wraptext.lines.last().unwrap().text.is_empty() {
+            wraptext.lines.push(Line::default());
+        }
+        *wraptext.lines.last_mut().unwrap() = Line::new(text, style);
+        wraptext.lines.push(Line::default());
+        self.cur_line.clear();
+        self.cur_widths.clear();
+        self.prev_line = None;
+    }
+
+    fn handle_incoming(&mut self, incoming: Incoming, wraptext: &mut WrapText) -> io::Result<()> {
+        match incoming {
+            Incoming::Data(data) => {
+                self.rx_bytes += data.len();
+                let now = Instant::now();
+                if let (Some(idle), Some(last)) = (self.log_split_idle, self.last_log_rx) {
+                    if now - last >= idle {
+                        self.split_log();
+                        if let Some(routes) = &mut self.routes {
+                            routes.split();
+                        }
+                    }
+                }
+                self.last_log_rx = Some(now);
+                self.last_rx = Some(now);
+                self.idle_secs = None;
+                if let (Some(frames), Some(_)) = (&mut self.frames, &self.grapher) {
+                    let decoded = frames.push(&data);
+                    let skipped = frames.take_skipped();
+                    for frame in decoded {
+                        let values: Vec<Option<f64>> = frame.into_iter().map(Some).collect();
+                        self.graph_values(&values);
+                    }
+                    if skipped > 0 {
+                        self.push_data_loss(wraptext, "binary frame resync")?;
+                    }
+                }
+                for byte in &data {
+                    if let Some(screen) = &mut self.screen {
+                        screen.push(*byte);
+                    }
+                    self.parse_byte(*byte, wraptext)?;
+                }
+                Ok(())
+            }
+            Incoming::DataLoss(reason) => self.push_data_loss(wraptext, reason),
+        }
+    }
+
+    /// Adds a row to the statistics file, and stops writing to it if that fails.
+    fn write_stats(&mut self) {
+        let counters = Counters {
+            rx_bytes: self.rx_bytes,
+            tx_bytes: self.tx_written.load(Ordering::Relaxed),
+            lines: self.rx_lines,
+            reconnects: self.reconnects,
+            errors: self.errors,
+            stripped: self.stripped,
+            route_lines: self.routes.as_ref().map_or_else(Vec::new, |routes| {
+                routes.routes.iter().map(|route| route.lines).collect()
+            }),
+        };
+        let Some(stats) = &mut self.stats else {
+            return;
+        };
+        if let Err(e) = stats.write(&counters) {
+            self.stats = None;
+            self.notify(format!("writing the statistics file: {e}"), Level::Error);
+        }
+    }
+
+    /// Marks that data might be missing at this point, in both the output and the log.
+    fn push_data_loss(&mut self, wraptext: &mut WrapText, reason: &str) -> io::Result<()> {
+        let marker = format!("⚠ possible data loss ({reason})");
+        self.errors += 1;
+        self.log_marker(&marker)?;
+        self.push_marker(wraptext, marker, Style::default().fg(Color::LightRed));
+        Ok(())
+    }
+
+    /// Writes a line from rterm itself to the log, after what has been received so far.
+    fn log_marker(&mut self, marker: &str) -> io::Result<()> {
+        if self.dedup_log {
+            self.flush_held_log_line()?;
+        }
+        // The raw log only gets what was received.
+        if let (false, Some(outfile)) = (self.raw_log, &mut self.outfile) {
+            let partial = std::mem::take(&mut self.log_line);
+            if !partial.is_empty() || !self.cur_line.is_empty() {
+                writeln!(outfile, "{partial}")?;
+            }
+            writeln!(outfile, "{marker}")?;
+            outfile.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Updates for how long the device has been idle. Returns whether the
+    /// status bar needs to be redrawn.
+    fn check_idle(&mut self, connected: bool) -> io::Result<bool> {
+        let Some(timeout) = self.idle_timeout else {
+            return Ok(false);
+        };
+        if !connected {
+            self.last_rx = None;
+            return Ok(self.idle_secs.take().is_some());
+        }
+        let last_rx = *self.last_rx.get_or_insert_with(Instant::now);
+        let elapsed = last_rx.elapsed();
+        let idle_secs = (elapsed >= timeout).then_some(elapsed.as_secs());
+        if idle_secs.is_some() && self.idle_secs.is_none() && self.idle_log {
+            let since = timefmt::clock_time(timefmt::to_system_time(last_rx));
+            self.log_marker(&format!("⏸ idle since {since}"))?;
+        }
+        let changed = idle_secs != self.idle_secs;
+        self.idle_secs = idle_secs;
+        Ok(changed)
+    }
+
+    /// Marks where the data received after a reconnect starts.
+    fn push_reconnect_divider(&mut self, wraptext: &mut WrapText) {
+        if !wraptext.lines.last().unwrap().text.is_empty() {
+            wraptext.lines.push(Line::default());
+        }
+        self.reconnect_line = Some(wraptext.lines.len() - 1);
+        *wraptext.lines.last_mut().unwrap() = Line::new(
+            "──────── new since reconnect ────────".to_string(),
+            Style::default().fg(Color::DarkGray),
+        );
+        wraptext.lines.push(Line::default());
+        self.cur_line.clear();
+        self.cur_widths.clear();
+        self.prev_line = None;
+    }
+
+    /// Logs the line just completed unless it's a repeat of the previous one.
+    fn log_deduplicated_line(&mut self, repeated: bool) -> io::Result<()> {
+        let line = std::mem::take(&mut self.log_line);
+        match &mut self.held_log_line {
+            Some((_, count)) if repeated => *count += 1,
+            _ => {
+                self.flush_held_log_line()?;
+                self.held_log_line = Some((line, 1));
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the line held back by the deduplicated log.
+    fn flush_held_log_line(&mut self) -> io::Result<()> {
+        let (Some(outfile), Some((line, count))) = (&mut self.outfile, self.held_log_line.take())
+        else {
+            return Ok(());
+        };
+        if count > 1 {
+            writeln!(outfile, "{line} (x{count})")?;
+        } else {
+            writeln!(outfile, "{line}")?;
+        }
+        outfile.flush()
+    }
+
+    /// Writes what the deduplicated log still holds back.
+    fn finish_log(&mut self) -> io::Result<()> {
+        self.flush_held_log_line()?;
+        if let Some(outfile) = &mut self.outfile {
+            outfile.write_all(std::mem::take(&mut self.log_line).as_bytes())?;
+            outfile.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Adds a sample to the graph and to the statistics of the current row.
+    fn graph_values(&mut self, values: &[Option<f64>]) {
+        let Some(grapher) = &mut self.grapher else {
+            return;
+        };
+        if let Some(stats) = &mut self.stats {
+            stats.record(values);
+        }
+        if let Some(error) = grapher.add_values(values) {
+            self.notify(error, Level::Error);
+        }
+    }
+
+    /// Where the values the graph extracts from `text` are, if they're highlighted.
+    fn value_highlights(&self, text: &str) -> Vec<(Range<usize>, Style)> {
+        let (true, Some(grapher)) = (self.highlight_values, &self.grapher) else {
+            return Vec::new();
+        };
+        grapher
+            .value_ranges(text)
+            .into_iter()
+            .map(|(range, color)| {
+                // The ranges are in bytes but the line is drawn by characters.
+                let start = text[..range.start].chars().count();
+                let end = start + text[range].chars().count();
+                let style = Style::default()
+                    .fg(color)
+                    .add_modifier(Modifier::UNDERLINED);
+                (start..end, style)
+            })
+            .collect()
+    }
+
+    /// Colors a completed line green, yellow or red by how its values compare
+    /// to the thresholds of their extractors.
+    fn color_by_thresholds(&self, line: &mut Line) {
+        let Some(grapher) = &self.grapher else {
+            return;
+        };
+        let severities = grapher.severities(&line.text);
+        match self.threshold_color {
+            ThresholdColor::Line => {
+                if let Some(severity) = severities.iter().map(|(_, severity)| *severity).max() {
+                    line.style = line.style.fg(severity.color());
+                }
+            }
+            ThresholdColor::Value => {
+                for (range, severity) in severities {
+                    let start = line.text[..range.start].chars().count();
+                    let end = start + line.text[range].chars().count();
+                    line.highlights.push((
+                        start..end,
+                        Style::default()
+                            .fg(severity.color())
+                            .add_modifier(Modifier::BOLD),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Whether the log gets the output of the pipe command instead of the received lines.
+    fn logs_piped(&self) -> bool {
+        self.pipe.is_some() && self.pipe_log == Tap::Piped
+    }
+
+    /// Whether the graph gets the output of the pipe command instead of the received lines.
+    fn graphs_piped(&self) -> bool {
+        self.pipe.is_some() && self.pipe_graph == Tap::Piped
+    }
+
+    /// Shows what the pipe command has written. Returns whether anything changed.
+    fn handle_piped(&mut self, wraptext: &mut WrapText) -> io::Result<bool> {
+        let Some(pipe) = &mut self.pipe else {
+            return Ok(false);
+        };
+        let (lines, failure) = pipe.poll();
+        let changed = !lines.is_empty() || failure.is_some();
+        for text in lines {
+            if let (Tap::Piped, Some(outfile)) = (self.pipe_log, &mut self.outfile) {
+                writeln!(outfile, "{text}")?;
+                outfile.flush()?;
+            }
+            if let (Tap::Piped, Some(grapher)) = (self.pipe_graph, &self.grapher) {
+                self.graph_values(&grapher.extract(&text));
+            }
+            let style = if self.pipe_alongside {
+                Style::default().fg(Color::Green)
+            } else {
+                Style::default()
+            };
+            let mut line = Line::new(text, style);
+            line.time = Some(Instant::now());
+            line.highlights = self.value_highlights(&line.text);
+            self.color_by_thresholds(&mut line);
+            // The last line is the one still being received.
+            let idx = wraptext.lines.len() - 1;
+            wraptext.lines.insert(idx, line);
+            self.prev_line = None;
+        }
+        if let Some(reason) = failure {
+            self.stop_pipe(wraptext, &reason);
+        }
+        Ok(changed)
+    }
+
+    /// Stops using the pipe command since it doesn't work, going back to the received lines.
+    fn stop_pipe(&mut self, wraptext: &mut WrapText, reason: &str) {
+        self.pipe = None;
+        let warning = format!("⚠ {reason}, showing the received lines");
+        self.push_marker(wraptext, warning, Style::default().fg(Color::LightRed));
     }
 
     /// Parses a byte from the terminal device.
     pub fn parse_byte(&mut self, byte: u8, wraptext: &mut WrapText) -> std::io::Result<()> {
+        self.hex.push(byte);
+        if let (true, Some(outfile)) = (self.raw_log, &mut self.outfile) {
+            outfile.write_all(&[byte])?;
+            outfile.flush()?;
+        }
+        // A CR ends the line by itself, so the LF of a CRLF is dropped.
+        // Records with another delimiter can contain both.
+        let newlines = self.delimiter == b'\n';
+        let after_cr = std::mem::replace(&mut self.after_cr, byte == b'\r');
+        if newlines && byte == b'\n' && after_cr {
+            return Ok(());
+        }
         // let cursor_pos = wraptext.cursor();
         // wraptext.move_cursor(tui_textarea::CursorMove::Bottom);
         // wraptext.move_cursor(tui_textarea::CursorMove::End);
         // let jumped = cursor_pos != wraptext.cursor();
-        if byte == 10 {
+        if byte == self.delimiter || (newlines && byte == b'\r') {
             // new line
-            if let Some(outfile) = &mut self.outfile {
+            let repeated = self.dedup
+                && self
+                    .prev_line
+                    .as_ref()
+                    .is_some_and(|(prev, _)| *prev == self.cur_line);
+            // Logged like the sent lines, with the control characters as they are.
+            let jsonl_line = std::mem::take(&mut self.jsonl_line);
+            if let Some(jsonl) = &mut self.jsonl {
+                jsonl.write(JsonlDirection::Rx, SystemTime::now(), &jsonl_line)?;
+            }
+            self.rx_lines += 1;
+            for error in self
+                .triggers
+                .as_mut()
+                .map_or_else(Vec::new, |triggers| triggers.check(&self.cur_line))
+            {
+                self.notify(error, Level::Error);
+            }
+            if self
+                .alerts
+                .as_mut()
+                .is_some_and(|alerts| alerts.check(&self.cur_line))
+            {
+                self.notify(format!("🔔 {}", self.cur_line), Level::Error);
+            }
+            for error in self
+                .routes
+                .as_mut()
+                .map_or_else(Vec::new, |routes| routes.write(&self.cur_line))
+            {
+                self.notify(error, Level::Error);
+            }
+            if let Some(error) = self
+                .udp
+                .as_mut()
+                .and_then(|udp| udp.send(&self.cur_line, SystemTime::now()))
+            {
+                self.notify(error, Level::Error);
+            }
+            if self.dedup_log {
+                self.log_deduplicated_line(repeated)?;
+            } else if let (false, Some(outfile)) =
+                (self.raw_log || self.logs_piped(), &mut self.outfile)
+            {
                 outfile.write_all(&"\n".to_string().into_bytes())?;
                 outfile.flush()?;
             }
-            // wraptext.insert_newline();
-            wraptext.lines.push(String::new());
-            if let Some(grapher) = &mut self.grapher {
-                if let Some(captures) = grapher.value_pattern.captures(&self.cur_line) {
-                    if let Some(capture) = captures.get(0) {
-                        if let Ok(val) = capture.as_str().parse() {
-                            if grapher.data.len() as f64 + grapher.window_len as f64 / 10.0
-                                > grapher.window[1]
-                            {
-                                grapher.window[0] += 1.0;
-                                grapher.window[1] += 1.0;
-                            }
-                            grapher.data.push((grapher.data.len() as f64, val));
-                        }
-                    }
+            if let Some(pipe) = &mut self.pipe {
+                if let Err(reason) = pipe.send(&self.cur_line) {
+                    self.stop_pipe(wraptext, &reason);
                 }
             }
+            if self.pipe.is_some() && !self.pipe_alongside {
+                // The output of the pipe command is shown instead.
+                *wraptext.lines.last_mut().unwrap() = Line::default();
+                if let (Tap::Raw, Some(grapher)) = (self.pipe_graph, &self.grapher) {
+                    self.graph_values(&grapher.extract(&self.cur_line));
+                }
+                self.cur_line.clear();
+                self.cur_widths.clear();
+                return Ok(());
+            }
+            if repeated {
+                // Replaces the previous line with one that has the count.
+                wraptext.lines.pop();
+                let (prev, count) = self.prev_line.as_mut().unwrap();
+                *count += 1;
+                wraptext.lines.last_mut().unwrap().text = format!("{prev} (x{count})");
+            } else {
+                self.prev_line = Some((self.cur_line.clone(), 1));
+                // wraptext.insert_newline();
+                // Empty lines get the time of their newline.
+                let line = wraptext.lines.last_mut().unwrap();
+                line.time.get_or_insert_with(Instant::now);
+            }
+            if let Some(Some(false)) = self.nmea.as_mut().map(|stats| stats.record(&self.cur_line))
+            {
+                wraptext.lines.last_mut().unwrap().style = Style::default().fg(Color::Red);
+            }
+            let line = wraptext.lines.last_mut().unwrap();
+            line.highlights = self.value_highlights(&self.cur_line);
+            self.color_by_thresholds(line);
+            wraptext.lines.push(Line::default());
+            self.update_filtered(wraptext);
+            if let (false, Some(grapher)) = (self.graphs_piped(), &self.grapher) {
+                self.graph_values(&grapher.extract(&self.cur_line));
+            }
             self.cur_line.clear();
+            self.cur_widths.clear();
         } else {
-            let str = if let Ok(ch) = std::str::from_utf8(&[byte]) {
-                format!("{}", ch.chars().next().unwrap())
-            } else {
-                // If it's not a vaild char, display out its hex value.
-                format!("0x{byte:X}")
-            };
-            wraptext.lines.last_mut().unwrap().push_str(&str);
-            self.cur_line.push_str(&str);
-            if let Some(outfile) = &mut self.outfile {
-                outfile.write_all(&str.into_bytes())?;
+            let line = wraptext.lines.last_mut().unwrap();
+            match byte {
+                // Takes back what the byte before was displayed as, like a
+                // device editing its line does.
+                0x08 => {
+                    for _ in 0..self.cur_widths.pop().unwrap_or(0) {
+                        self.cur_line.pop();
+                        line.text.pop();
+                    }
+                }
+                0x07 => self.bell = true,
+                _ => match self.decoder.display_text(byte, &self.cur_line) {
+                    Some(str) => {
+                        line.time.get_or_insert_with(Instant::now);
+                        line.text.push_str(&str);
+                        self.cur_line.push_str(&str);
+                        self.cur_widths.push(str.chars().count());
+                    }
+                    None => self.stripped += 1,
+                },
+            }
+            if self.jsonl.is_some() {
+                self.jsonl_line.push_str(&self.decoder.log_text(byte));
+            }
+            if self.dedup_log {
+                self.log_line.push_str(&self.decoder.log_text(byte));
+            } else if let (false, Some(outfile)) =
+                (self.raw_log || self.logs_piped(), &mut self.outfile)
+            {
+                outfile.write_all(&self.decoder.log_text(byte).into_bytes())?;
                 outfile.flush()?;
             }
         }
@@ -311,23 +3020,97 @@ impl App {
 }
 
 impl UI {
-    fn new(f: &mut Frame<impl Backend>, graph: bool) -> Self {
-        let chunks = UI::generate_chunks(f.size(), graph);
-        let graph_chunk = if graph { Some(chunks[2]) } else { None };
-        let help_info_chunk = if graph { chunks[3] } else { chunks[2] };
+    fn new(
+        f: &mut Frame<impl Backend>,
+        graph: bool,
+        gauge: bool,
+        input_rows: u16,
+        split: f64,
+    ) -> Self {
+        UI::layout(f.size(), graph, gauge, input_rows, split)
+    }
+
+    /// The gauge goes on the right of the graph, or below the output without it.
+    fn layout(size: Rect, graph: bool, gauge: bool, input_rows: u16, split: f64) -> Self {
+        let bordered =
+            size.width >= MIN_BORDERED_WIDTH && size.height >= input_rows + MIN_BORDERED_HEIGHT;
+        // Both panes need room for their borders and some content.
+        let shown_graph = graph && bordered && UI::pane_rows(size, input_rows) >= 2 * MIN_PANE_ROWS;
+        let chunks = UI::generate_chunks(size, shown_graph, input_rows, split, bordered);
+        let mut graph_chunk = if shown_graph { Some(chunks[2]) } else { None };
+        let help_info_chunk = if shown_graph { chunks[3] } else { chunks[2] };
+        let mut ouput_chunk = chunks[1];
+        let mut gauge_chunk = None;
+        if gauge && bordered {
+            match graph_chunk {
+                Some(graph) if graph.width >= 2 * GAUGE_COLUMNS => {
+                    let split = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints([Constraint::Min(0), Constraint::Length(GAUGE_COLUMNS)])
+                        .split(graph);
+                    graph_chunk = Some(split[0]);
+                    gauge_chunk = Some(split[1]);
+                }
+                Some(_) => {}
+                None if ouput_chunk.height >= GAUGE_ROWS + MIN_PANE_ROWS => {
+                    let split = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Min(0), Constraint::Length(GAUGE_ROWS)])
+                        .split(ouput_chunk);
+                    ouput_chunk = split[0];
+                    gauge_chunk = Some(split[1]);
+                }
+                None => {}
+            }
+        }
         UI {
-            ouput_chunk: chunks[1],
+            ouput_chunk,
             input_chunk: chunks[0],
             graph_chunk,
+            gauge_chunk,
             help_info_chunk,
+            bordered,
+            too_small: size.width < MIN_WIDTH || size.height < MIN_HEIGHT,
+            size,
+            graph,
+            gauge,
+            input_rows,
+            split,
         }
     }
 
-    pub fn generate_chunks(rect: Rect, graph: bool) -> Vec<Rect> {
-        let mut constraints = vec![Constraint::Length(3)];
+    /// The chunks of the panes. With the graph, the output gets the `split`
+    /// share of the rows they have room for, but leaves the graph at least
+    /// `MIN_PANE_ROWS`. Without borders, the input and the status bar get
+    /// as few rows as possible.
+    pub fn generate_chunks(
+        rect: Rect,
+        graph: bool,
+        input_rows: u16,
+        split: f64,
+        bordered: bool,
+    ) -> Vec<Rect> {
+        if !bordered {
+            let input_rows = input_rows.min(rect.height.saturating_sub(2)).max(1);
+            return Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Length(input_rows),
+                    Constraint::Min(1),
+                    Constraint::Length(1),
+                ])
+                .split(rect);
+        }
+        let mut constraints = vec![Constraint::Length(input_rows + 2)];
         if graph {
-            constraints.push(Constraint::Percentage(50));
-            constraints.push(Constraint::Percentage(50));
+            let rows = UI::pane_rows(rect, input_rows);
+            let output_rows = if rows < 2 * MIN_PANE_ROWS {
+                rows / 2
+            } else {
+                ((rows as f64 * split).round() as u16).clamp(MIN_PANE_ROWS, rows - MIN_PANE_ROWS)
+            };
+            constraints.push(Constraint::Length(output_rows));
+            constraints.push(Constraint::Min(0));
         } else {
             constraints.push(Constraint::Min(4));
         }
@@ -338,16 +3121,61 @@ impl UI {
             .split(rect)
     }
 
-    fn update_size(&mut self, width: u16, height: u16, graph: bool) {
-        let chunks = UI::generate_chunks(Rect::new(0, 0, width, height), graph);
-        let graph_chunk = if graph { Some(chunks[2]) } else { None };
-        let help_info_chunk = if graph { chunks[3] } else { chunks[2] };
-        *self = UI {
-            ouput_chunk: chunks[1],
-            input_chunk: chunks[0],
-            graph_chunk,
-            help_info_chunk,
+    /// The rows shared by the output and the graph.
+    fn pane_rows(rect: Rect, input_rows: u16) -> u16 {
+        rect.height.saturating_sub(input_rows + 2 + 2)
+    }
+
+    /// Recomputes the chunks if the size or the panes that should be shown have changed.
+    fn update_layout(&mut self, size: Rect, graph: bool, gauge: bool, input_rows: u16, split: f64) {
+        if size == self.size
+            && graph == self.graph
+            && gauge == self.gauge
+            && input_rows == self.input_rows
+            && split == self.split
+        {
+            return;
         }
+        *self = UI::layout(size, graph, gauge, input_rows, split);
+    }
+
+    /// Where the text of the output is drawn, inside the borders if there are any.
+    fn output_text_area(&self) -> Rect {
+        if self.bordered {
+            Block::default()
+                .borders(Borders::ALL)
+                .inner(self.ouput_chunk)
+        } else {
+            self.ouput_chunk
+        }
+    }
+
+    /// Whether `row` is the border between the output and the graph, either
+    /// the bottom one of the output or the top one of the graph.
+    fn on_divider(&self, row: u16) -> bool {
+        self.graph_chunk
+            .is_some_and(|graph| row == graph.y || row + 1 == self.ouput_chunk.bottom())
+    }
+
+    /// The split that makes the output end at `row`.
+    fn split_at(&self, row: u16) -> f64 {
+        let rows = UI::pane_rows(self.size, self.input_rows).max(1);
+        let output_rows = (row + 1).saturating_sub(self.ouput_chunk.y);
+        (output_rows as f64 / rows as f64).clamp(0.0, 1.0)
+    }
+
+    /// The split that makes the output `rows` rows taller, or shorter if negative.
+    fn moved_split(&self, rows: i32) -> f64 {
+        let end = self.ouput_chunk.bottom() as i32 - 1 + rows;
+        self.split_at(end.max(0) as u16)
+    }
+
+    /// Whether the given position is on the text of the output pane, inside
+    /// its borders and left of the scrollbar.
+    fn in_output_text(&self, column: u16, row: u16) -> bool {
+        let area = self.output_text_area();
+        (area.x..area.right().saturating_sub(1)).contains(&column)
+            && (area.y..area.bottom()).contains(&row)
     }
 
     /// Renders all the widgets and their content.
@@ -357,70 +3185,323 @@ impl UI {
         textarea: &mut TextArea,
         wraptext: &mut WrapText,
         text_state: &mut WrapTextState,
-        grapher: &mut Option<Grapher>,
+        app: &App,
+        tx_pending: usize,
     ) {
-        let input_block = Block::default().borders(Borders::ALL);
-        let output_block = Block::default().borders(Borders::ALL);
+        if self.too_small {
+            let note = Paragraph::new("terminal too small").wrap(tui::widgets::Wrap { trim: true });
+            f.render_widget(note, f.size());
+            return;
+        }
+        let mut input_title = Vec::new();
+        match app.mode {
+            Mode::Normal => {
+                input_title.push(Span::styled(
+                    " NORMAL ",
+                    Style::default().fg(Color::Black).bg(Color::Cyan),
+                ));
+                input_title.push(Span::raw(
+                    " i types, / searches, v selects, y yanks, . resends, :q quits ",
+                ));
+            }
+            Mode::Visual { .. } => {
+                input_title.push(Span::styled(
+                    " VISUAL ",
+                    Style::default().fg(Color::Black).bg(Color::Magenta),
+                ));
+                input_title.push(Span::raw(" y yanks, Esc stops selecting "));
+            }
+            Mode::Insert if app.output_focused => {
+                input_title.push(Span::styled(
+                    " OUTPUT ",
+                    Style::default().fg(Color::Black).bg(Color::Cyan),
+                ));
+                input_title.push(Span::raw(
+                    " ↑↓ or j k scroll, ←→ scroll sideways, Esc goes back to the input ",
+                ));
+            }
+            Mode::Insert => {}
+        }
+        if app.input_locked {
+            let style = match app.lock_flash {
+                Some(_) => Style::default().fg(Color::Black).bg(Color::Yellow),
+                None => Style::default().fg(Color::Yellow),
+            };
+            input_title.push(Span::styled("🔒 locked, Ctrl+o unlocks", style));
+            input_title.push(Span::raw(" "));
+        }
+        match (&app.input_error, app.hex_input) {
+            (Some(error), _) => {
+                input_title.push(Span::styled(error.clone(), Style::default().fg(Color::Red)))
+            }
+            (None, true) => input_title.push(Span::raw("hex")),
+            (None, false) => {}
+        }
+        if app.char_mode {
+            if app.input_error.is_some() || app.hex_input {
+                input_title.push(Span::raw(", "));
+            }
+            input_title.push(Span::raw("char mode, keys are sent as they are typed"));
+        }
+        let input_block = Block::default()
+            .borders(Borders::ALL)
+            .title(Spans::from(input_title));
+        let mut output_block = Block::default().borders(Borders::ALL);
+        if app.output_focused {
+            output_block = output_block.border_style(Style::default().fg(Color::Cyan));
+        }
+        if app
+            .alerts
+            .as_ref()
+            .is_some_and(|alerts| alerts.flashed.is_some())
+        {
+            output_block = output_block.border_style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD | Modifier::REVERSED),
+            );
+        }
+        let filter = (!app.filters.is_empty()).then(|| app.filters.describe());
+        // The hex view and filters show the lines instead.
+        let screen = app.screen.as_ref().filter(|_| !app.view_active());
+        let title = match (app.hex_view, filter) {
+            (true, Some(filter)) => Some(format!("hex, filter: {filter}")),
+            (true, None) => Some("hex".to_string()),
+            (false, Some(filter)) => Some(format!("filter: {filter}")),
+            (false, None) => screen.map(|_| "vt100".to_string()),
+        };
+        if let Some(title) = title {
+            output_block = output_block.title(title);
+        }
 
-        textarea.set_block(input_block);
+        // The titles are left out with the borders.
+        let output_block = self.bordered.then_some(output_block);
+        if self.bordered {
+            textarea.set_block(input_block);
+        } else {
+            textarea.remove_block();
+        }
         f.render_widget(textarea.widget(), self.input_chunk);
 
-        wraptext.set_block(output_block);
-        f.render_stateful_widget(wraptext.widget(), self.ouput_chunk, text_state);
+        match screen {
+            Some(screen) => f.render_widget(screen.widget(output_block), self.ouput_chunk),
+            None => {
+                if let Some(block) = output_block {
+                    wraptext.set_block(block);
+                }
+                f.render_stateful_widget(
+                    wraptext
+                        .widget(app.filtered_lines())
+                        .search(app.search.as_deref()),
+                    self.ouput_chunk,
+                    text_state,
+                );
+            }
+        }
 
         if let Some(graph_chunk) = self.graph_chunk {
-            let graph_block = Block::default().borders(Borders::ALL);
-            let grapher = grapher.as_ref().unwrap();
-            let visible_data = &grapher.data
-                [0.max(grapher.data.len() as i64 - grapher.window_len as i64) as usize..];
-            let datasets = vec![Dataset::default()
-                .marker(symbols::Marker::Braille)
-                .style(Style::default().fg(Color::Yellow))
-                .graph_type(GraphType::Line)
-                .data(visible_data)];
-
-            let min = visible_data
-                .iter()
-                .min_by_key(|(_x, y)| OrderedFloat(*y))
-                .map(|x| x.1)
-                .unwrap_or(-1.0);
-            let max = visible_data
-                .iter()
-                .max_by_key(|(_x, y)| OrderedFloat(*y))
-                .map(|x| x.1)
-                .unwrap_or(1.0);
-            let size = max - min;
-            let min = min - 0.1 * size - 0.001 * max.abs().max(min.abs());
-            let max = max + 0.1 * size + 0.001 * max.abs().max(min.abs());
-            let mean = (max + min) / 2.0;
-
-            let chart = Chart::new(datasets)
-                .block(graph_block)
-                .x_axis(Axis::default().bounds(grapher.window).title("X axis"))
-                .y_axis(Axis::default().bounds([min, max]).labels(vec![
-                    Span::styled(
-                        format!("{min:.4}"),
-                        Style::default().add_modifier(Modifier::BOLD),
-                    ),
-                    Span::raw(format!("{mean:.4}")),
-                    Span::styled(
-                        format!("{max:.4}"),
-                        Style::default().add_modifier(Modifier::BOLD),
+            app.grapher.as_ref().unwrap().render(f, graph_chunk);
+            // Shows that the border can be dragged.
+            if (app.divider_hovered || app.dragging_divider) && self.ouput_chunk.height > 0 {
+                let output = self.ouput_chunk;
+                let border = Rect::new(output.x, output.bottom() - 1, output.width, 1);
+                f.render_widget(
+                    Restyle(
+                        Style::default()
+                            .fg(Color::Cyan)
+                            .add_modifier(Modifier::BOLD),
                     ),
-                ]));
-            f.render_widget(chart, graph_chunk);
-        }
-
-        let text = vec![
-            Spans::from(vec![
-                Span::styled("Exit - Esc       Goto bottom - Ctrl+d",Style::default().fg(Color::LightRed)),
-            ]),
-        ];
-        let par = Paragraph::new(text)
-            .block(Block::default().borders(Borders::LEFT.union(Borders::RIGHT).union(Borders::BOTTOM)))
-            .alignment(Alignment::Center);
+                    border,
+                );
+            }
+        }
+        if let Some(gauge_chunk) = self.gauge_chunk {
+            gauge::render(
+                f,
+                gauge_chunk,
+                app.grapher.as_ref().unwrap(),
+                &app.gauge_unit,
+            );
+        }
+
+        if let Some(picker) = &app.history_picker {
+            picker.render(f, self.ouput_chunk, &app.history_texts());
+        }
+
+        // The status comes before the key help so that it's visible even if
+        // the help doesn't fit.
+        let mut spans = vec![Span::styled(
+            app.device_name.as_str(),
+            Style::default().fg(Color::Yellow),
+        )];
+        if let Some(path) = &app.log_path {
+            spans.push(Span::raw("   "));
+            spans.push(Span::styled(
+                format!("log: {}", path.display()),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        if let Some(sparkline) = app
+            .grapher
+            .as_ref()
+            .filter(|_| app.graph_visible && app.graph_sparkline)
+            .and_then(|grapher| grapher.status_sparkline(STATUS_SPARKLINE_LEN))
+        {
+            spans.push(Span::raw("   "));
+            spans.push(sparkline);
+        }
+        if let Some(secs) = app.idle_secs {
+            spans.push(Span::raw("   "));
+            spans.push(Span::styled(
+                format!(" idle {secs}s "),
+                Style::default().fg(Color::Black).bg(Color::Yellow),
+            ));
+        }
+        match &app.modem_status {
+            Some(Ok(status)) => {
+                // Set lines are in upper case so that they stand out without colors too.
+                spans.push(Span::raw("  "));
+                for (name, set) in [
+                    ("cts", status.cts),
+                    ("dsr", status.dsr),
+                    ("dcd", status.dcd),
+                    ("ri", status.ri),
+                ] {
+                    spans.push(Span::raw(" "));
+                    spans.push(if set {
+                        Span::styled(
+                            name.to_uppercase(),
+                            Style::default()
+                                .fg(Color::Green)
+                                .add_modifier(Modifier::BOLD),
+                        )
+                    } else {
+                        Span::styled(name, Style::default().fg(Color::DarkGray))
+                    });
+                }
+            }
+            Some(Err(e)) => {
+                spans.push(Span::raw("   "));
+                spans.push(Span::styled(
+                    format!("modem lines: {e}"),
+                    Style::default().fg(Color::Red),
+                ));
+            }
+            None => {}
+        }
+        if let Some(stats) = app.nmea {
+            spans.push(Span::raw("   "));
+            spans.push(Span::styled(
+                format!("NMEA {} ok / {} bad", stats.good, stats.bad),
+                Style::default().fg(if stats.bad > 0 {
+                    Color::Red
+                } else {
+                    Color::Green
+                }),
+            ));
+        }
+        if let Some(triggers) = &app.triggers {
+            let counts: Vec<String> = triggers
+                .rules
+                .iter()
+                .filter(|rule| rule.matches > 0)
+                .map(|rule| format!("{} ×{}", rule.pattern, rule.matches))
+                .collect();
+            if !counts.is_empty() {
+                spans.push(Span::raw("   "));
+                spans.push(Span::styled(
+                    format!("matched {}", counts.join(", ")),
+                    Style::default().fg(Color::Magenta),
+                ));
+            }
+        }
+        if let Some(alerts) = &app.alerts {
+            spans.push(Span::raw("   "));
+            spans.push(Span::styled(
+                format!("alerts {}", alerts.describe()),
+                Style::default().fg(Color::Yellow),
+            ));
+        }
+        if let Some(udp) = app.udp.as_ref().filter(|udp| udp.failures > 0) {
+            spans.push(Span::raw("   "));
+            spans.push(Span::styled(
+                format!("UDP {} failed", udp.failures),
+                Style::default().fg(Color::Red),
+            ));
+        }
+        let tx_written = app.tx_written.load(Ordering::Relaxed);
+        if app.rx_bytes > 0 || tx_written > 0 {
+            spans.push(Span::raw("   "));
+            spans.push(Span::styled(
+                format!(
+                    "RX {}  TX {}",
+                    byte_count(app.rx_bytes),
+                    byte_count(tx_written)
+                ),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        if tx_pending > 0 {
+            spans.push(Span::raw("   "));
+            spans.push(Span::styled(
+                format!("TX {tx_pending} B pending"),
+                Style::default().fg(Color::Cyan),
+            ));
+        }
+        if app.stripped > 0 {
+            spans.push(Span::raw("   "));
+            spans.push(Span::styled(
+                format!("{} stripped", app.stripped),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        if app.keys_dropped > 0 {
+            spans.push(Span::raw("   "));
+            spans.push(Span::styled(
+                format!("{} keys dropped", app.keys_dropped),
+                Style::default().fg(Color::LightRed),
+            ));
+        }
+        spans.push(Span::raw("   "));
+        let exit_help = if app.vim {
+            "Normal mode - Esc       Quit - :q"
+        } else {
+            "Exit - Esc"
+        };
+        let completions = app.completion.as_ref().map(|completion| completion.hint());
+        match app.command_hint.as_ref().or(completions.as_ref()) {
+            Some(hint) => spans.push(Span::styled(hint.as_str(), Style::default().fg(Color::LightRed))),
+            None if !app.notifications.is_empty() => {
+                let latest = app.notifications.last().unwrap();
+                let style = match latest.level {
+                    Level::Info => Style::default().fg(Color::Cyan),
+                    Level::Error => Style::default().fg(Color::LightRed).add_modifier(Modifier::BOLD),
+                };
+                spans.push(Span::styled(latest.message.as_str(), style));
+                let older = app.notifications.len() - 1;
+                if older > 0 {
+                    spans.push(Span::styled(format!("   (+{older} more)"), Style::default().fg(Color::DarkGray)));
+                }
+                if app.error_shown() {
+                    spans.push(Span::styled("   Dismiss - Alt+d", Style::default().fg(Color::DarkGray)));
+                }
+            }
+            None => spans.push(Span::styled(format!("{exit_help}       Goto bottom - Ctrl+d       Graph - Ctrl+g       Gauge - Alt+g       Reconnect - Alt+j       Timestamps - Ctrl+t       Zero timestamps - Alt+z       Reset min/max - Alt+l       Reapply serial settings - Alt+r       Refit graph - Alt+a       Resize graph - Alt+Up/Down or drag       Toggle series - Alt+1..9       Export graph - Alt+e       Copy lines or selection - Alt+c       Send again - Alt+.       History - Alt+h       Hex input - Alt+x       Char mode - Alt+k       Cancel sending - Ctrl+x       Lock input - Ctrl+o       Scroll with the keys - Alt+o       Bookmark - Alt+m, Alt+n, Alt+p"),Style::default().fg(Color::LightRed))),
+        }
+        let text = vec![Spans::from(spans)];
+        let mut par = Paragraph::new(text).alignment(Alignment::Center);
+        if self.bordered {
+            par = par.block(
+                Block::default()
+                    .borders(Borders::LEFT.union(Borders::RIGHT).union(Borders::BOTTOM)),
+            );
+        }
             //.wrap(Wrap { trim: true });
         f.render_widget(par, self.help_info_chunk);
+
+        if !app.color {
+            f.render_widget(Monochrome, f.size());
+        }
     }
 
 }
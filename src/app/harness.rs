@@ -0,0 +1,894 @@
+//! Feeds bytes to an [`App`] as if they were received and renders the result
+//! into an off-screen buffer, so that tests need neither a terminal nor a device.
+//! Tests of what's sent connect it to a pseudoterminal instead.
+
+use std::{
+    os::unix::io::RawFd,
+    path::{Path, PathBuf},
+};
+
+use nix::{
+    poll::{poll, PollFd, PollFlags},
+    pty::openpty,
+    sys::termios::BaudRate,
+    unistd::{close, read, ttyname, write},
+};
+use tui::{backend::TestBackend, buffer::Buffer, Terminal};
+use tui_textarea::TextArea;
+
+use super::{App, Connection, UI};
+use crate::{
+    termdev::{Mode, OpenFlags, SerialConfig, TerminalDevice},
+    wraptext::{WrapText, WrapTextState},
+};
+
+pub struct Harness {
+    pub app: App,
+    pub wraptext: WrapText<'static>,
+    pub text_state: WrapTextState,
+    pub view_state: WrapTextState,
+}
+
+impl Harness {
+    /// An app with the default settings that isn't connected to anything.
+    pub fn new() -> Self {
+        let serial_config = SerialConfig {
+            baud_rate: BaudRate::B115200,
+            // Sent newlines would otherwise become CRLF on a pseudoterminal.
+            mode: Some(Mode::Raw),
+            open_flags: OpenFlags::default(),
+        };
+        Harness {
+            app: App::new(None, serial_config),
+            wraptext: WrapText::new(),
+            text_state: WrapTextState::new(),
+            view_state: WrapTextState::new(),
+        }
+    }
+
+    /// Connects to the device at `path` like the event loop does.
+    fn connect(&self, path: &Path) -> Connection {
+        let td = TerminalDevice::open(path, &self.app.serial_config).unwrap();
+        Connection::start(td, self.app.tx_settings, self.app.tx_written.clone(), None)
+    }
+
+    /// Parses `bytes` like the event loop does with the received ones.
+    pub fn feed(&mut self, bytes: &[u8]) -> &mut Self {
+        for &byte in bytes {
+            self.app.parse_byte(byte, &mut self.wraptext).unwrap();
+        }
+        self
+    }
+
+    /// Renders only the output, without borders, into `width` x `height` cells.
+    pub fn render_output(&mut self, width: u16, height: u16) -> Buffer {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal
+            .draw(|f| {
+                let area = f.size();
+                self.app.update_filtered(&self.wraptext);
+                let mut view = self.app.take_view(&self.wraptext, area.width);
+                let app = &self.app;
+                let output = view.as_mut().unwrap_or(&mut self.wraptext);
+                let state = app.output_state(&mut self.text_state, &mut self.view_state);
+                let widget = output
+                    .widget(app.filtered_lines())
+                    .search(app.search.as_deref());
+                f.render_stateful_widget(widget, area, state);
+                self.app.put_back_view(view);
+            })
+            .unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    /// Renders the whole UI like the event loop does into a terminal of
+    /// `width` x `height` cells.
+    pub fn render(&mut self, width: u16, height: u16) -> Buffer {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal
+            .draw(|f| {
+                self.app.update_filtered(&self.wraptext);
+                let app = &self.app;
+                let mut ui = UI::new(f, app.graph_shown(), app.gauge_shown(), 1, app.graph_split);
+                let mut view = self.app.take_view(&self.wraptext, ui.ouput_chunk.width);
+                let app = &self.app;
+                let output = view.as_mut().unwrap_or(&mut self.wraptext);
+                let state = app.output_state(&mut self.text_state, &mut self.view_state);
+                ui.render(f, &mut TextArea::default(), output, state, app, 0);
+                self.app.put_back_view(view);
+            })
+            .unwrap();
+        terminal.backend().buffer().clone()
+    }
+}
+
+/// A pseudoterminal for the app to connect to, whose other end is read by the test.
+pub struct Pty {
+    master: RawFd,
+    /// Kept open so that the other end isn't hung up between connections.
+    slave: RawFd,
+    pub path: PathBuf,
+}
+
+impl Pty {
+    pub fn open() -> Self {
+        let pty = openpty(None, None).unwrap();
+        Pty {
+            master: pty.master,
+            slave: pty.slave,
+            path: ttyname(pty.slave).unwrap(),
+        }
+    }
+
+    /// Sends `bytes` to the app as if the device did.
+    pub fn write(&self, bytes: &[u8]) {
+        assert_eq!(write(self.master, bytes).unwrap(), bytes.len());
+    }
+
+    /// Reads what the app has sent until there are `len` bytes, failing the
+    /// test if they don't arrive within a few seconds.
+    pub fn read(&self, len: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        while bytes.len() < len {
+            let mut fds = [PollFd::new(self.master, PollFlags::POLLIN)];
+            assert_eq!(poll(&mut fds, 5000).unwrap(), 1, "only got {bytes:?}");
+            let mut buf = [0; 256];
+            let n = read(self.master, &mut buf).unwrap();
+            bytes.extend_from_slice(&buf[..n]);
+        }
+        bytes
+    }
+}
+
+impl Drop for Pty {
+    fn drop(&mut self) {
+        let _ = close(self.master);
+        let _ = close(self.slave);
+    }
+}
+
+/// The rows of `buffer` as text, without the spaces they end with.
+pub fn rows(buffer: &Buffer) -> Vec<String> {
+    let width = buffer.area.width as usize;
+    buffer
+        .content
+        .chunks(width.max(1))
+        .map(|row| {
+            let text: String = row.iter().map(|cell| cell.symbol.as_str()).collect();
+            text.trim_end().to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use nix::sys::termios::{tcgetattr, tcsetattr, LocalFlags, SetArg};
+
+    use std::{
+        fs,
+        sync::atomic::Ordering,
+        thread,
+        time::{Duration, Instant},
+    };
+    use tui::style::{Color, Style};
+
+    use super::*;
+    use crate::{
+        alert::Alerts,
+        app::{HistoryEntry, Incoming, Level},
+        command::Command,
+        decoder::{ControlMode, TxEncoding},
+        discovery::Candidate,
+        gauge::GAUGE_COLUMNS,
+        graph::{Extractor, Grapher},
+        jsonl::JsonlLog,
+        wraptext::Wrap,
+    };
+
+    fn texts(harness: &Harness) -> Vec<&str> {
+        harness
+            .wraptext
+            .lines
+            .iter()
+            .map(|line| line.text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn cr_lf_and_crlf_each_end_one_line() {
+        let mut harness = Harness::new();
+        harness.feed(b"one\r\ntwo\rthree\n\nfour");
+        assert_eq!(texts(&harness), ["one", "two", "three", "", "four"]);
+    }
+
+    #[test]
+    fn lines_are_numbered() {
+        let mut harness = Harness::new();
+        harness.feed(b"one\r\ntwo\nthree");
+        let shown = rows(&harness.render_output(12, 4));
+        assert_eq!(
+            shown,
+            [
+                " 00 one    █",
+                " 01 two    █",
+                " 02 three  █",
+                "           █"
+            ]
+        );
+
+        harness.wraptext.line_number_start = 1;
+        let shown = rows(&harness.render_output(12, 4));
+        assert_eq!(shown[0], " 01 one    █");
+        harness.wraptext.line_number_width = Some(0);
+        let shown = rows(&harness.render_output(12, 4));
+        assert_eq!(shown[0], "one        █");
+    }
+
+    #[test]
+    fn long_lines_wrap() {
+        let mut harness = Harness::new();
+        harness.wraptext.line_number_width = Some(0);
+        harness.feed(b"abcdefghijklmnop qrs tuv\n");
+        let shown = rows(&harness.render_output(10, 4));
+        assert_eq!(
+            shown,
+            ["abcdefghi█", "jklmnop q█", "rs tuv   █", "         █"]
+        );
+
+        harness.wraptext.wrapping.wrap = Wrap::Word;
+        let shown = rows(&harness.render_output(10, 4));
+        assert_eq!(
+            shown,
+            ["abcdefghi█", "jklmnop  █", "qrs tuv  █", "         █"]
+        );
+    }
+
+    #[test]
+    fn hex_view_shows_the_received_bytes() {
+        let mut harness = Harness::new();
+        harness.app.hex_view = true;
+        harness.feed(b"AB\r\n");
+        let shown = rows(&harness.render(60, 10));
+        assert!(shown[3].starts_with("┌hex"), "{shown:#?}");
+        assert!(
+            shown[4].starts_with("│ 00 00000000  41 42 0D 0A "),
+            "{shown:#?}"
+        );
+        assert!(shown[4].contains("|AB..|"), "{shown:#?}");
+        // The lines are still parsed while their bytes are shown.
+        assert_eq!(texts(&harness), ["AB", ""]);
+    }
+
+    #[test]
+    fn filters_show_the_matching_lines() {
+        let mut harness = Harness::new();
+        harness.feed(b"ERR one\nok\nERR two\nERR th");
+        let filter = |harness: &mut Harness, command| {
+            harness.app.run_command(
+                command,
+                &mut harness.wraptext,
+                &mut None,
+                &mut harness.text_state,
+                &mut harness.view_state,
+            );
+        };
+        filter(&mut harness, Command::AddFilter("ERR".to_string()));
+        // The line being received is shown once it matches.
+        let shown = rows(&harness.render_output(14, 4));
+        assert_eq!(
+            shown,
+            [
+                " 00 ERR one  █",
+                " 01 ERR two  █",
+                " 02 ERR th   █",
+                "             █"
+            ]
+        );
+        harness.feed(b"ree\nok\nERR four\n");
+        let shown = rows(&harness.render_output(14, 4));
+        assert_eq!(
+            shown,
+            [
+                " 00 ERR one  █",
+                " 01 ERR two  █",
+                " 02 ERR three█",
+                " 03 ERR four █"
+            ]
+        );
+        filter(&mut harness, Command::AddFilter("t".to_string()));
+        let shown = rows(&harness.render_output(14, 4));
+        assert_eq!(
+            shown,
+            [
+                " 00 ERR two  █",
+                " 01 ERR three█",
+                "             █",
+                "             █"
+            ]
+        );
+        // Without filters every line is shown again, following the end.
+        filter(&mut harness, Command::Filter(None));
+        let shown = rows(&harness.render_output(14, 4));
+        assert_eq!(
+            shown,
+            [
+                " 03 ERR three│",
+                " 04 ok       █",
+                " 05 ERR four █",
+                " 06          █"
+            ]
+        );
+    }
+
+    #[test]
+    fn received_lines_are_logged_as_json_with_their_controls() {
+        let path = std::env::temp_dir().join(format!("rterm-jsonl-{}.jsonl", std::process::id()));
+        let mut harness = Harness::new();
+        harness.app.jsonl = Some(JsonlLog::create(&path).unwrap());
+        harness.feed(b"a\x1bb\tc\x00d\x08\r\n");
+        assert_eq!(texts(&harness), ["a^[b    c", ""]);
+        let logged = fs::read_to_string(&path).unwrap();
+        assert!(
+            logged
+                .trim_end()
+                .ends_with(r#""dir":"rx","line":"a\u001bb\tc\u0000d\u0008"}"#),
+            "{logged}"
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn alerts_are_shown_as_errors() {
+        let mut harness = Harness::new();
+        let pattern = regex::Regex::new("FAIL").unwrap();
+        harness.app.alerts = Some(Alerts::new(vec![pattern]));
+        harness.feed(b"ok\nFAIL 3\n");
+        let notification = &harness.app.notifications[0];
+        assert_eq!(notification.message, "🔔 FAIL 3");
+        assert_eq!(notification.level, Level::Error);
+        assert_eq!(harness.app.notifications.len(), 1);
+        assert!(harness.app.alerts.as_ref().unwrap().flashed.is_some());
+    }
+
+    #[test]
+    fn control_characters_are_never_displayed_as_they_are() {
+        let mut harness = Harness::new();
+        harness.feed(b"\0\0a\tb\x1b[1m\x7f\0\n");
+        harness.feed(b"12\x08\x083 ok\x07\n");
+        assert_eq!(texts(&harness), ["a       b^[[1m^?", "3 ok", ""]);
+        assert!(harness.app.bell);
+        assert_eq!(harness.app.stripped, 3);
+
+        harness.app.decoder.nul = ControlMode::Glyph;
+        harness.app.decoder.controls = ControlMode::Hex;
+        harness.feed(b"\0\x1b\n");
+        assert_eq!(texts(&harness)[2], "␀0x1B");
+        // A backspace takes back everything a byte was displayed as.
+        harness.feed(b"a\tb\x08\x08c\x1b\x08!\n");
+        assert_eq!(texts(&harness)[3], "ac!");
+    }
+
+    #[test]
+    fn scrolling() {
+        let mut harness = Harness::new();
+        for i in 0..10 {
+            harness.feed(format!("line {i}\n").as_bytes());
+        }
+        // The latest lines are followed.
+        let shown = rows(&harness.render_output(12, 3));
+        assert_eq!(shown, [" 08 line 8 │", " 09 line 9 │", " 10        █"]);
+
+        harness.text_state.jump_to(0);
+        let shown = rows(&harness.render_output(12, 3));
+        assert_eq!(shown, [" 00 line 0 █", " 01 line 1 │", " 02 line 2 │"]);
+        // Stays put as more lines are received.
+        harness.feed(b"line 10\n");
+        assert_eq!(rows(&harness.render_output(12, 3))[0], " 00 line 0 █");
+
+        harness.text_state.scroll_down_by(1);
+        assert_eq!(rows(&harness.render_output(12, 3))[0], " 01 line 1 █");
+        harness.text_state.follow();
+        assert!(rows(&harness.render_output(12, 3))[1].starts_with(" 10 line 10"));
+    }
+
+    #[test]
+    fn the_graph_waits_for_data() {
+        let mut harness = Harness::new();
+        harness.app.grapher = Some(Grapher::new(vec![Extractor::first_number()], 60));
+        harness.app.graph_visible = true;
+        let waiting = |harness: &mut Harness| {
+            rows(&harness.render(60, 30))
+                .iter()
+                .any(|row| row.contains("waiting for data…"))
+        };
+        harness.feed(b"no number\n");
+        assert!(waiting(&mut harness));
+        harness.feed(b"t=5\n");
+        assert!(!waiting(&mut harness));
+    }
+
+    #[test]
+    fn the_grapher_is_created_when_the_graph_is_first_shown() {
+        let mut harness = Harness::new();
+        harness.feed(b"t=1\n");
+        assert!(harness.app.grapher.is_none());
+        harness.app.toggle_graph();
+        assert!(harness.app.graph_shown());
+        harness.feed(b"t=2\n");
+        let grapher = harness.app.grapher.as_ref().unwrap();
+        assert_eq!(grapher.kept_samples().end, 1);
+        // Hiding it keeps the values.
+        harness.app.toggle_graph();
+        assert!(!harness.app.graph_shown());
+        harness.app.toggle_graph();
+        assert_eq!(harness.app.grapher.as_ref().unwrap().kept_samples().end, 1);
+    }
+
+    #[test]
+    fn the_gauge_shows_the_latest_value() {
+        let mut harness = Harness::new();
+        harness.app.grapher = Some(Grapher::new(vec![Extractor::first_number()], 60));
+        harness.app.gauge_visible = true;
+        harness.app.gauge_unit = "V".to_string();
+        for line in ["v=1\n", "v=1\n", "v=7\n", "v=8\n"] {
+            harness.feed(line.as_bytes());
+        }
+        // Below the output, above the status bar.
+        let shown = rows(&harness.render(60, 30));
+        let gauge: Vec<&str> = shown[22..27]
+            .iter()
+            .map(|row| row.trim_matches(|c| c == ' ' || c == '│'))
+            .collect();
+        assert_eq!(
+            gauge,
+            [
+                "███   ███ ███ ███",
+                "█ █   █ █ █ █ █ █",
+                "███   █ █ █ █ █ █ ↑",
+                "█ █   █ █ █ █ █ █",
+                "███ █ ███ ███ ███ V",
+            ]
+        );
+        // On the right of the graph when it's shown.
+        harness.app.graph_visible = true;
+        let shown = rows(&harness.render(80, 30));
+        let arrow = shown.iter().find(|row| row.contains('↑')).unwrap();
+        let column = arrow.chars().position(|c| c == '█').unwrap();
+        assert!(column > 80 - GAUGE_COLUMNS as usize, "{arrow}");
+    }
+
+    #[test]
+    fn scrolling_to_the_bottom_follows_again() {
+        let mut harness = Harness::new();
+        for i in 0..5 {
+            harness.feed(format!("line {i}\n").as_bytes());
+        }
+        harness.text_state.jump_to(0);
+        // Three rows are shown, the last one is the empty line being received.
+        for top in 1..=3 {
+            harness.text_state.scroll_down_by(1);
+            harness.text_state.follow_at_bottom();
+            let expected = format!(" 0{top} line {top}");
+            assert!(rows(&harness.render_output(12, 3))[0].starts_with(&expected));
+        }
+        harness.feed(b"line 5\n");
+        assert!(rows(&harness.render_output(12, 3))[0].starts_with(" 04 line 4"));
+        // Not when the view is scrolled up, the first row only stops following.
+        harness.text_state.scroll_up_by(2);
+        harness.text_state.follow_at_bottom();
+        harness.render_output(12, 3);
+        harness.feed(b"line 6\n");
+        assert!(rows(&harness.render_output(12, 3))[0].starts_with(" 03 line 3"));
+    }
+
+    #[test]
+    fn scrolling_several_rows_is_like_scrolling_one_at_a_time() {
+        let mut harness = Harness::new();
+        for i in 0..6 {
+            // Wrapped over a number of rows.
+            harness.feed(format!("line {i} {}\n", "ab ".repeat(i)).as_bytes());
+        }
+        for n in 0..30 {
+            for down in [false, true] {
+                let mut shown = Vec::new();
+                for batched in [false, true] {
+                    harness.text_state.follow();
+                    harness.render_output(12, 4);
+                    let steps = if batched {
+                        vec![n]
+                    } else {
+                        vec![1; n as usize]
+                    };
+                    for step in steps {
+                        match down {
+                            true => harness.text_state.scroll_down_by(step),
+                            false => harness.text_state.scroll_up_by(step),
+                        }
+                        // Every step is applied to its own render.
+                        if !batched {
+                            harness.render_output(12, 4);
+                        }
+                    }
+                    shown.push(rows(&harness.render_output(12, 4)));
+                }
+                assert_eq!(shown[0], shown[1], "{n} rows, down: {down}");
+            }
+        }
+        harness.text_state.jump_to(0);
+        harness.text_state.scroll_up_by(5);
+        assert_eq!(rows(&harness.render_output(12, 4))[0], " 00 line 0 █");
+
+        // Nothing to scroll through when no line matches the filters.
+        harness.app.filters.patterns.push("nothing".to_string());
+        for down in [false, true] {
+            match down {
+                true => harness.view_state.scroll_down_by(1),
+                false => harness.view_state.scroll_up_by(1),
+            }
+            assert_eq!(rows(&harness.render_output(12, 4))[0], "");
+        }
+    }
+
+    #[test]
+    fn the_scrollbar_is_only_clicked_where_it_is_drawn() {
+        let mut harness = Harness::new();
+        for i in 0..8 {
+            harness.feed(format!("line {i}\n").as_bytes());
+        }
+        let shown = rows(&harness.render_output(12, 4));
+        assert!(shown.iter().all(|row| row.ends_with(['█', '│'])));
+        let state = &harness.text_state;
+        assert_eq!(state.scrollbar_line(11, 0, 9), Some(0));
+        assert_eq!(state.scrollbar_line(11, 3, 9), Some(6));
+        assert_eq!(state.scrollbar_line(10, 0, 9), None);
+        assert_eq!(state.scrollbar_line(11, 4, 9), None);
+        // The line numbers make way for the text before the scrollbar does.
+        harness.render_output(5, 4);
+        assert_eq!(harness.text_state.scrollbar_line(4, 0, 9), Some(0));
+        harness.render_output(1, 4);
+        assert_eq!(harness.text_state.scrollbar_line(0, 0, 9), None);
+    }
+
+    #[test]
+    fn the_scrollbar_marks_bookmarks_matches_and_highlights() {
+        let mut harness = Harness::new();
+        for i in 0..20 {
+            harness.feed(format!("line {i}\n").as_bytes());
+        }
+        harness.wraptext.lines[0].bookmarked = true;
+        harness.wraptext.lines[12].highlights = vec![(0..4, Style::default())];
+        harness.app.search = Some("7".to_string());
+        // 21 lines on 10 rows, with the last 10 lines shown.
+        let buffer = harness.render_output(12, 10);
+        let bar: Vec<(&str, Color)> = (0..10)
+            .map(|row| {
+                let cell = buffer.get(11, row);
+                (cell.symbol.as_str(), cell.fg)
+            })
+            .collect();
+        assert_eq!(bar[0], ("•", Color::Yellow));
+        assert_eq!(bar[1], ("│", Color::DarkGray));
+        assert_eq!(bar[3], ("•", Color::LightMagenta));
+        assert_eq!(bar[5], ("█", Color::Cyan));
+        assert_eq!(bar[6], ("█", Color::Gray));
+        assert_eq!(bar[8], ("█", Color::LightMagenta));
+    }
+
+    #[test]
+    fn tiny_terminals() {
+        let mut harness = Harness::new();
+        harness.feed(b"some text that is a lot wider than the terminal\nshort");
+        for height in 1..12 {
+            for width in 1..24 {
+                harness.render(width, height);
+            }
+        }
+        assert_eq!(rows(&harness.render(5, 3)), ["termi", "nal", "too"]);
+        assert_eq!(rows(&harness.render(20, 2)), ["terminal too small", ""]);
+        // Without borders.
+        let shown = rows(&harness.render(12, 3));
+        assert_eq!(shown[1], " 01 short  █");
+        assert!(shown[2].contains("Exit"), "{shown:#?}");
+    }
+
+    #[test]
+    fn status_bar_fits_with_the_borders() {
+        let mut harness = Harness::new();
+        let shown = rows(&harness.render(40, 9));
+        assert!(shown[0].starts_with('┌'), "{shown:#?}");
+        assert!(shown[7].contains("Exit"), "{shown:#?}");
+    }
+
+    #[test]
+    fn non_ascii_text_is_sent_byte_exact() {
+        let typed = "µs é";
+        let encodings: [(TxEncoding, &[u8]); 2] = [
+            (TxEncoding::Utf8, "µs é\n".as_bytes()),
+            (TxEncoding::Latin1, b"\xB5s \xE9\n"),
+        ];
+        for (encoding, expected) in encodings {
+            let pty = Pty::open();
+            let mut harness = Harness::new();
+            harness.app.tx_encoding = encoding;
+            harness.app.local_echo = true;
+            let connection = Some(harness.connect(&pty.path));
+            let entry = HistoryEntry {
+                text: typed.to_string(),
+                hex: false,
+            };
+            harness
+                .app
+                .send_entry(connection.as_ref(), &entry, &mut harness.wraptext)
+                .unwrap();
+            assert_eq!(pty.read(expected.len()), expected, "{encoding:?}");
+            // Char mode.
+            harness.app.send_key(&connection, 'é');
+            let key = encoding.encode("é").unwrap();
+            assert_eq!(pty.read(key.len()), key, "{encoding:?}");
+            // The echo shows what was typed.
+            assert_eq!(texts(&harness), [typed, ""]);
+        }
+    }
+
+    #[test]
+    fn devices_that_dont_read_stay_connected() {
+        let pty = Pty::open();
+        let harness = Harness::new();
+        let connection = harness.connect(&pty.path);
+        // A lot more than the pseudoterminal buffers.
+        let data = vec![b'x'; 256 * 1024];
+        connection.send(data.clone());
+        thread::sleep(Duration::from_millis(300));
+        assert!(!connection.handle.is_finished());
+        assert!(connection.tx_pending.load(Ordering::Relaxed) > 0);
+
+        // Everything is written once it's read.
+        assert_eq!(pty.read(data.len()), data);
+        for _ in 0..100 {
+            if connection.tx_pending.load(Ordering::Relaxed) == 0 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(connection.tx_pending.load(Ordering::Relaxed), 0);
+        assert_eq!(harness.app.tx_written.load(Ordering::Relaxed), data.len());
+        connection.join().unwrap();
+    }
+
+    #[test]
+    fn devices_that_never_send_show_what_is_sent() {
+        let pty = Pty::open();
+        let mut harness = Harness::new();
+        harness.app.local_echo = true;
+        let connection = harness.connect(&pty.path);
+        let entry = HistoryEntry {
+            text: "on".to_string(),
+            hex: false,
+        };
+        harness
+            .app
+            .send_entry(Some(&connection), &entry, &mut harness.wraptext)
+            .unwrap();
+        assert_eq!(pty.read(3), b"on\n");
+        for _ in 0..100 {
+            if connection.tx_pending.load(Ordering::Relaxed) == 0 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(texts(&harness), ["on", ""]);
+        let shown = rows(&harness.render(80, 9));
+        assert!(shown[7].contains("RX 0 B  TX 3 B"), "{shown:#?}");
+        assert!(connection.read_rx.try_recv().is_err());
+
+        // Stopping doesn't wait for something to be received.
+        let start = Instant::now();
+        connection.join().unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn on_connect_lines_are_sent_again_after_a_reconnect_if_asked_to() {
+        let pty = Pty::open();
+        let mut harness = Harness::new();
+        harness.app.on_connect = vec!["init".to_string()];
+        let candidate = Candidate {
+            path: pty.path.clone(),
+            by_id: None,
+        };
+        let connect = |harness: &mut Harness, connected_before| {
+            let td = TerminalDevice::open(&pty.path, &harness.app.serial_config).unwrap();
+            let connection =
+                harness
+                    .app
+                    .connect_found(&candidate, td, connected_before, &mut harness.wraptext);
+            connection.unwrap()
+        };
+        let connection = connect(&mut harness, false);
+        assert_eq!(pty.read(5), b"init\n");
+        connection.join().unwrap();
+        // Only the first connection sets the device up by default.
+        connect(&mut harness, true).join().unwrap();
+        assert_eq!(harness.app.tx_written.load(Ordering::Relaxed), 5);
+
+        harness.app.rerun_on_reconnect = true;
+        let connection = connect(&mut harness, true);
+        assert_eq!(pty.read(5), b"init\n");
+        connection.join().unwrap();
+        assert_eq!(harness.app.reconnects, 2);
+    }
+
+    #[test]
+    fn escape_sequences_are_sent_as_they_are_and_echoed_in_caret_notation() {
+        let pty = Pty::open();
+        let mut harness = Harness::new();
+        harness.app.local_echo = true;
+        let connection = harness.connect(&pty.path);
+        let entry = HistoryEntry {
+            text: "1B 5B 32 4A".to_string(),
+            hex: true,
+        };
+        harness
+            .app
+            .send_entry(Some(&connection), &entry, &mut harness.wraptext)
+            .unwrap();
+        assert_eq!(pty.read(4), b"\x1b[2J");
+        assert_eq!(texts(&harness), ["^[[2J", ""]);
+        connection.join().unwrap();
+    }
+
+    #[test]
+    fn enter_sends_nothing_while_the_input_is_empty() {
+        let pty = Pty::open();
+        let mut harness = Harness::new();
+        let connection = harness.connect(&pty.path);
+        let mut textarea = TextArea::default();
+        textarea.insert_newline();
+        harness
+            .app
+            .send_input(Some(&connection), &mut textarea, &mut harness.wraptext);
+        assert!(harness.app.history.is_empty());
+
+        harness.app.allow_empty_send = true;
+        harness
+            .app
+            .send_input(Some(&connection), &mut textarea, &mut harness.wraptext);
+        // Only the two lines of the input, nothing from the first Enter.
+        assert_eq!(pty.read(2), b"\n\n");
+        assert_eq!(harness.app.history.len(), 1);
+        textarea.insert_str(" ");
+        harness
+            .app
+            .send_input(Some(&connection), &mut textarea, &mut harness.wraptext);
+        assert_eq!(pty.read(2), b" \n");
+        connection.join().unwrap();
+    }
+
+    #[test]
+    fn serial_settings_can_be_applied_again() {
+        let pty = Pty::open();
+        let mut harness = Harness::new();
+        let connection = harness.connect(&pty.path);
+        // Like a driver resetting the line discipline behind rterm's back.
+        let mut termios = tcgetattr(pty.slave).unwrap();
+        termios.local_flags |= LocalFlags::ICANON | LocalFlags::ECHO;
+        tcsetattr(pty.slave, SetArg::TCSANOW, &termios).unwrap();
+
+        harness.app.reapply_config(Some(&connection));
+        let termios = tcgetattr(pty.slave).unwrap();
+        assert!(!termios
+            .local_flags
+            .intersects(LocalFlags::ICANON | LocalFlags::ECHO));
+        assert_eq!(
+            harness.app.notifications[0].message,
+            "serial settings applied again"
+        );
+        connection.join().unwrap();
+    }
+
+    #[test]
+    fn cr_and_crlf_end_lines_across_reads() {
+        let mut harness = Harness::new();
+        for chunk in [&b"one\r"[..], b"\ntwo\r", b"three\r", b"\n\nfour"] {
+            let incoming = Incoming::Data(chunk.to_vec());
+            harness
+                .app
+                .handle_incoming(incoming, &mut harness.wraptext)
+                .unwrap();
+        }
+        assert_eq!(texts(&harness), ["one", "two", "three", "", "four"]);
+    }
+
+    #[test]
+    fn carriage_returns_reach_the_app_in_every_mode() {
+        for mode in [None, Some(Mode::Arduino), Some(Mode::Raw)] {
+            let pty = Pty::open();
+            let mut harness = Harness::new();
+            harness.app.serial_config.mode = mode;
+            let connection = harness.connect(&pty.path);
+            pty.write(b"one\rtwo\r\nthree\n");
+            while texts(&harness).len() < 4 {
+                let incoming = connection.read_rx.recv_timeout(Duration::from_secs(5));
+                harness
+                    .app
+                    .handle_incoming(incoming.unwrap(), &mut harness.wraptext)
+                    .unwrap();
+            }
+            assert_eq!(texts(&harness), ["one", "two", "three", ""], "{mode:?}");
+            connection.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn the_log_is_split_at_gaps() {
+        let dir = std::env::temp_dir().join(format!("rterm-split-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let out = dir.join("capture.txt").to_string_lossy().to_string();
+        let mut harness = Harness::new();
+        let app = &mut harness.app;
+        app.log_split_idle = Some(Duration::from_millis(50));
+        app.switch_log(Some(&out));
+        for burst in [&b"one\n"[..], b"two\n", b"three\n"] {
+            for chunk in burst.chunks(2) {
+                let incoming = Incoming::Data(chunk.to_vec());
+                app.handle_incoming(incoming, &mut harness.wraptext)
+                    .unwrap();
+            }
+            thread::sleep(Duration::from_millis(60));
+        }
+        app.finish_log().unwrap();
+        let mut files: Vec<PathBuf> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        // Named by the time, and numbered within the same second.
+        files.sort_by_key(|path| fs::metadata(path).unwrap().modified().unwrap());
+        let contents: Vec<String> = files
+            .iter()
+            .map(|path| fs::read_to_string(path).unwrap())
+            .collect();
+        assert_eq!(contents, ["one\n", "two\n", "three\n"]);
+        let name = files[0].file_name().unwrap().to_string_lossy().to_string();
+        assert!(
+            name.starts_with("capture-2") && name.ends_with(".txt"),
+            "{name}"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn the_last_entry_is_sent_again_with_the_current_settings() {
+        let pty = Pty::open();
+        let mut harness = Harness::new();
+        let connection = harness.connect(&pty.path);
+        let (app, wraptext) = (&mut harness.app, &mut harness.wraptext);
+        app.send_again(Some(&connection), wraptext);
+        assert_eq!(app.notifications[0].message, "nothing to send again");
+
+        app.history.push(HistoryEntry {
+            text: "41 42".to_string(),
+            hex: true,
+        });
+        app.send_again(Some(&connection), wraptext);
+        assert_eq!(pty.read(2), b"AB");
+        app.hex_newline = true;
+        app.send_again(Some(&connection), wraptext);
+        assert_eq!(pty.read(3), b"AB\n");
+        connection.join().unwrap();
+    }
+
+    #[test]
+    fn latin1_refuses_what_it_cant_encode() {
+        let mut harness = Harness::new();
+        harness.app.tx_encoding = TxEncoding::Latin1;
+        let entry = HistoryEntry {
+            text: "5 €".to_string(),
+            hex: false,
+        };
+        let error = harness
+            .app
+            .send_entry(None, &entry, &mut harness.wraptext)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "'€' can't be sent in latin1");
+    }
+}
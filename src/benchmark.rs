@@ -0,0 +1,375 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// How many bytes after a mismatch have to match the pattern for the
+/// stream to count as back in step.
+const RESYNC_LEN: usize = 8;
+/// The most errors that are listed in the report, the rest are only counted.
+const MAX_LISTED: usize = 20;
+
+/// The pseudorandom bytes that are sent, from a xorshift generator with a
+/// fixed seed so that runs are comparable.
+pub struct Pattern {
+    state: u32,
+}
+
+impl Pattern {
+    pub fn new() -> Self {
+        Pattern { state: 0x2545_F491 }
+    }
+
+    pub fn take(&mut self, len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                self.state ^= self.state << 13;
+                self.state ^= self.state >> 17;
+                self.state ^= self.state << 5;
+                (self.state >> 24) as u8
+            })
+            .collect()
+    }
+}
+
+/// Something that went wrong at an offset into the sent bytes.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    Corrupted {
+        at: usize,
+        expected: u8,
+        got: u8,
+    },
+    Dropped {
+        at: usize,
+        len: usize,
+    },
+    /// A byte was received that was never sent.
+    Unexpected {
+        at: usize,
+        got: u8,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Corrupted { at, expected, got } => {
+                write!(f, "byte {at}: expected 0x{expected:02X}, got 0x{got:02X}")
+            }
+            Error::Dropped { at, len: 1 } => write!(f, "byte {at}: dropped"),
+            Error::Dropped { at, len } => write!(f, "bytes {at}..{}: dropped", at + len),
+            Error::Unexpected { at, got } => write!(f, "before byte {at}: unexpected 0x{got:02X}"),
+        }
+    }
+}
+
+/// Compares the echoed bytes with the sent ones. Any number of bytes can be
+/// in flight, and after a mismatch the received bytes are matched up with the
+/// sent ones again, so that a dropped byte isn't reported as every following
+/// one being corrupted.
+pub struct Verifier {
+    /// Sent bytes whose echo hasn't been verified yet.
+    sent: VecDeque<u8>,
+    /// The offset of the first byte of `sent`.
+    offset: usize,
+    /// Received bytes that wait for more to decide what happened to them.
+    received: VecDeque<u8>,
+    /// When the chunks were sent, by the offset after their last byte.
+    chunks: VecDeque<(usize, Instant)>,
+    round_trips: Vec<Duration>,
+    intact: usize,
+    corrupted: usize,
+    dropped: usize,
+    unexpected: usize,
+    errors: Vec<Error>,
+    unlisted: usize,
+}
+
+impl Verifier {
+    pub fn new() -> Self {
+        Verifier {
+            sent: VecDeque::new(),
+            offset: 0,
+            received: VecDeque::new(),
+            chunks: VecDeque::new(),
+            round_trips: Vec::new(),
+            intact: 0,
+            corrupted: 0,
+            dropped: 0,
+            unexpected: 0,
+            errors: Vec::new(),
+            unlisted: 0,
+        }
+    }
+
+    pub fn sent(&mut self, bytes: &[u8], at: Instant) {
+        self.sent.extend(bytes);
+        self.chunks.push_back((self.offset + self.sent.len(), at));
+    }
+
+    pub fn received(&mut self, bytes: &[u8], at: Instant) {
+        self.received.extend(bytes);
+        self.verify(at, false);
+    }
+
+    /// The number of sent bytes whose echo is still expected.
+    pub fn in_flight(&self) -> usize {
+        self.sent.len()
+    }
+
+    /// Gives up on what's in flight, e.g. when the echo has stopped. What's
+    /// been received is verified as far as possible and the sent bytes
+    /// without an echo count as dropped.
+    pub fn flush(&mut self, at: Instant) {
+        self.verify(at, true);
+        if !self.sent.is_empty() {
+            let len = self.sent.len();
+            self.drop_sent(len);
+        }
+    }
+
+    fn verify(&mut self, at: Instant, flush: bool) {
+        while let Some(&got) = self.received.front() {
+            let Some(&expected) = self.sent.front() else {
+                self.received.pop_front();
+                self.error(Error::Unexpected {
+                    at: self.offset,
+                    got,
+                });
+                continue;
+            };
+            if got == expected {
+                self.received.pop_front();
+                self.advance(1);
+                self.intact += 1;
+                if let Some(&(end, sent_at)) = self.chunks.front() {
+                    if end == self.offset {
+                        self.round_trips.push(at - sent_at);
+                    }
+                }
+                continue;
+            }
+            if self.received.len() <= RESYNC_LEN {
+                if !flush {
+                    // Waits for the bytes that tell what happened.
+                    return;
+                }
+                self.corrupt(expected, got);
+                continue;
+            }
+            let matches = |sent_start: usize, received_start: usize| {
+                (0..RESYNC_LEN)
+                    .all(|i| self.sent.get(sent_start + i) == self.received.get(received_start + i))
+            };
+            if matches(1, 1) {
+                self.corrupt(expected, got);
+            } else if let Some(len) = (1..self.sent.len()).find(|&len| matches(len, 0)) {
+                self.drop_sent(len);
+            } else if matches(0, 1) {
+                self.received.pop_front();
+                self.error(Error::Unexpected {
+                    at: self.offset,
+                    got,
+                });
+            } else {
+                self.corrupt(expected, got);
+            }
+        }
+    }
+
+    fn corrupt(&mut self, expected: u8, got: u8) {
+        self.received.pop_front();
+        self.error(Error::Corrupted {
+            at: self.offset,
+            expected,
+            got,
+        });
+        self.advance(1);
+    }
+
+    fn drop_sent(&mut self, len: usize) {
+        self.error(Error::Dropped {
+            at: self.offset,
+            len,
+        });
+        self.advance(len);
+    }
+
+    /// Moves past `len` sent bytes.
+    fn advance(&mut self, len: usize) {
+        self.sent.drain(..len);
+        self.offset += len;
+        while self
+            .chunks
+            .front()
+            .is_some_and(|&(end, _)| end < self.offset)
+        {
+            self.chunks.pop_front();
+        }
+    }
+
+    fn error(&mut self, error: Error) {
+        match error {
+            Error::Corrupted { .. } => self.corrupted += 1,
+            Error::Dropped { len, .. } => self.dropped += len,
+            Error::Unexpected { .. } => self.unexpected += 1,
+        }
+        if self.errors.len() < MAX_LISTED {
+            self.errors.push(error);
+        } else {
+            self.unlisted += 1;
+        }
+    }
+
+    /// The results of a run that took `elapsed`.
+    pub fn report(mut self, elapsed: Duration) -> Report {
+        self.round_trips.sort();
+        Report {
+            sent: self.offset + self.sent.len(),
+            intact: self.intact,
+            corrupted: self.corrupted,
+            dropped: self.dropped,
+            unexpected: self.unexpected,
+            errors: self.errors,
+            unlisted: self.unlisted,
+            round_trips: self.round_trips,
+            elapsed,
+        }
+    }
+}
+
+pub struct Report {
+    pub sent: usize,
+    pub intact: usize,
+    pub corrupted: usize,
+    pub dropped: usize,
+    pub unexpected: usize,
+    /// The first errors, in the order they happened in.
+    pub errors: Vec<Error>,
+    /// The number of errors after those.
+    pub unlisted: usize,
+    /// From sending a chunk to receiving the echo of its last byte, sorted.
+    pub round_trips: Vec<Duration>,
+    pub elapsed: Duration,
+}
+
+impl Report {
+    /// Whether every byte came back as it was sent.
+    pub fn clean(&self) -> bool {
+        self.corrupted == 0 && self.dropped == 0 && self.unexpected == 0
+    }
+
+    fn percentile(&self, p: usize) -> Option<Duration> {
+        let idx = (self.round_trips.len() * p / 100).min(self.round_trips.len().checked_sub(1)?);
+        Some(self.round_trips[idx])
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.elapsed.as_secs_f64().max(f64::EPSILON);
+        writeln!(
+            f,
+            "Sent {} bytes in {:.1} s, {:.0} bytes/s",
+            self.sent,
+            secs,
+            self.sent as f64 / secs
+        )?;
+        writeln!(
+            f,
+            "Echoed {} bytes intact, {:.0} bytes/s",
+            self.intact,
+            self.intact as f64 / secs
+        )?;
+        if let (Some(p50), Some(p90), Some(p99)) = (
+            self.percentile(50),
+            self.percentile(90),
+            self.percentile(99),
+        ) {
+            let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+            writeln!(
+                f,
+                "Round trip: p50 {:.1} ms, p90 {:.1} ms, p99 {:.1} ms, max {:.1} ms",
+                ms(p50),
+                ms(p90),
+                ms(p99),
+                ms(*self.round_trips.last().unwrap())
+            )?;
+        }
+        if self.clean() {
+            return writeln!(f, "No corrupted or dropped bytes");
+        }
+        writeln!(
+            f,
+            "{} corrupted, {} dropped and {} unexpected bytes:",
+            self.corrupted, self.dropped, self.unexpected
+        )?;
+        for error in &self.errors {
+            writeln!(f, "  {error}")?;
+        }
+        if self.unlisted > 0 {
+            writeln!(f, "  and {} more", self.unlisted)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verify(sent: &[u8], echoed: &[u8]) -> Report {
+        let now = Instant::now();
+        let mut verifier = Verifier::new();
+        // In small pieces, like slow devices echo.
+        for chunk in sent.chunks(16) {
+            verifier.sent(chunk, now);
+        }
+        for chunk in echoed.chunks(3) {
+            verifier.received(chunk, now);
+        }
+        verifier.flush(now);
+        verifier.report(Duration::from_secs(1))
+    }
+
+    #[test]
+    fn intact_echo() {
+        let sent = Pattern::new().take(100);
+        let report = verify(&sent, &sent);
+        assert!(report.clean());
+        assert_eq!((report.sent, report.intact), (100, 100));
+        // One per chunk.
+        assert_eq!(report.round_trips.len(), 7);
+    }
+
+    #[test]
+    fn errors_are_found_where_they_happen() {
+        let sent = Pattern::new().take(100);
+        let mut echoed = sent.clone();
+        echoed[10] ^= 0x01;
+        echoed.drain(40..43);
+        echoed.insert(60, !sent[63]);
+        echoed.truncate(95);
+        let report = verify(&sent, &echoed);
+        assert_eq!(
+            report.errors,
+            [
+                Error::Corrupted {
+                    at: 10,
+                    expected: sent[10],
+                    got: sent[10] ^ 0x01
+                },
+                Error::Dropped { at: 40, len: 3 },
+                Error::Unexpected {
+                    at: 63,
+                    got: !sent[63]
+                },
+                Error::Dropped { at: 97, len: 3 },
+            ]
+        );
+        assert_eq!(report.intact, 93);
+        assert!(!report.clean());
+    }
+}
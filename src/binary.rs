@@ -0,0 +1,119 @@
+use clap::ArgEnum;
+
+/// How the samples in a binary stream are encoded, all little-endian.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum)]
+pub enum SampleFormat {
+    #[clap(name = "i16le")]
+    I16,
+    #[clap(name = "u16le")]
+    U16,
+    #[clap(name = "i32le")]
+    I32,
+    #[clap(name = "f32le")]
+    F32,
+}
+
+impl SampleFormat {
+    /// The number of bytes of a sample.
+    fn size(self) -> usize {
+        match self {
+            SampleFormat::I16 | SampleFormat::U16 => 2,
+            SampleFormat::I32 | SampleFormat::F32 => 4,
+        }
+    }
+
+    /// Decodes a sample of `self.size()` bytes.
+    fn decode(self, bytes: &[u8]) -> f64 {
+        match self {
+            SampleFormat::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            SampleFormat::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            SampleFormat::I32 => {
+                i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64
+            }
+            SampleFormat::F32 => {
+                f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64
+            }
+        }
+    }
+}
+
+/// Splits a binary stream into frames of one sample for every channel,
+/// each preceded by the sync pattern if there is one.
+///
+/// With a sync pattern, bytes are skipped until the pattern is found again
+/// whenever a frame doesn't start with it, e.g. after dropped bytes. Without
+/// one the frames are only aligned to where the stream started, so a dropped
+/// byte shifts all samples after it.
+pub struct FrameDecoder {
+    format: SampleFormat,
+    channels: usize,
+    sync: Vec<u8>,
+    /// Received bytes that aren't part of a complete frame yet.
+    buf: Vec<u8>,
+    /// A frame has been decoded, so skipped bytes are lost data rather than
+    /// the end of a frame the stream started in.
+    synced: bool,
+    skipped: usize,
+}
+
+impl FrameDecoder {
+    pub fn new(format: SampleFormat, channels: usize, sync: Vec<u8>) -> Self {
+        FrameDecoder {
+            format,
+            channels: channels.max(1),
+            sync,
+            buf: Vec::new(),
+            synced: false,
+            skipped: 0,
+        }
+    }
+
+    /// Adds received bytes and returns the values of every frame they completed.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<Vec<f64>> {
+        self.buf.extend_from_slice(bytes);
+        let size = self.format.size();
+        let frame_len = self.sync.len() + size * self.channels;
+        let mut frames = Vec::new();
+        loop {
+            if !self.sync.is_empty() {
+                let start = self
+                    .buf
+                    .windows(self.sync.len())
+                    .position(|window| window == self.sync);
+                match start {
+                    Some(start) => self.skip(start),
+                    None => {
+                        // The end might be the start of the next sync pattern.
+                        let keep = self.sync.len() - 1;
+                        self.skip(self.buf.len().saturating_sub(keep));
+                        break;
+                    }
+                }
+            }
+            if self.buf.len() < frame_len {
+                break;
+            }
+            let values = self.buf[self.sync.len()..frame_len]
+                .chunks(size)
+                .map(|sample| self.format.decode(sample))
+                .collect();
+            frames.push(values);
+            self.buf.drain(..frame_len);
+            self.synced = true;
+        }
+        frames
+    }
+
+    fn skip(&mut self, n: usize) {
+        self.buf.drain(..n);
+        if self.synced {
+            self.skipped += n;
+        }
+    }
+
+    /// The number of bytes skipped to find the sync pattern again since this
+    /// was last called.
+    pub fn take_skipped(&mut self) -> usize {
+        std::mem::take(&mut self.skipped)
+    }
+}
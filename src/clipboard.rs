@@ -0,0 +1,88 @@
+use std::{
+    env,
+    io::{self, Write},
+    process::{Command, Stdio},
+};
+
+use anyhow::anyhow;
+
+/// A program that copies its input to the clipboard, and the environment
+/// variable that has to be set for it to be usable, if any.
+struct Program {
+    name: &'static str,
+    args: &'static [&'static str],
+    /// The arguments that copy to the primary selection instead, if there is one.
+    primary_args: Option<&'static [&'static str]>,
+    needs_env: Option<&'static str>,
+}
+
+const PROGRAMS: &[Program] = &[
+    Program {
+        name: "wl-copy",
+        args: &[],
+        primary_args: Some(&["--primary"]),
+        needs_env: Some("WAYLAND_DISPLAY"),
+    },
+    Program {
+        name: "xclip",
+        args: &["-selection", "clipboard"],
+        primary_args: Some(&["-selection", "primary"]),
+        needs_env: Some("DISPLAY"),
+    },
+    Program {
+        name: "xsel",
+        args: &["--clipboard", "--input"],
+        primary_args: Some(&["--primary", "--input"]),
+        needs_env: Some("DISPLAY"),
+    },
+    Program {
+        name: "pbcopy",
+        args: &[],
+        primary_args: None,
+        needs_env: None,
+    },
+];
+
+/// Copies `text` to the clipboard with the first clipboard program that's available.
+pub fn copy(text: &str) -> anyhow::Result<()> {
+    run(text, false)
+}
+
+/// Copies `text` to the primary selection, or to the clipboard where there is none.
+pub fn copy_primary(text: &str) -> anyhow::Result<()> {
+    run(text, true)
+}
+
+fn run(text: &str, primary: bool) -> anyhow::Result<()> {
+    for program in PROGRAMS {
+        if program
+            .needs_env
+            .is_some_and(|var| env::var_os(var).is_none())
+        {
+            continue;
+        }
+        let args = match program.primary_args {
+            Some(args) if primary => args,
+            _ => program.args,
+        };
+        let child = Command::new(program.name)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(anyhow!("running {}: {e}", program.name)),
+        };
+        // Closing stdin tells the program that all of the text has been written.
+        child.stdin.take().unwrap().write_all(text.as_bytes())?;
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(anyhow!("{} failed with {status}", program.name));
+        }
+        return Ok(());
+    }
+    Err(anyhow!("no clipboard program found"))
+}
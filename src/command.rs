@@ -0,0 +1,204 @@
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use clap::ArgEnum;
+use nix::sys::termios::BaudRate;
+
+use crate::{filter::Combine, wraptext::Wrap};
+
+/// A command entered in the command palette, e.g. `:baud 57600`. A plain
+/// number, e.g. `:1234`, jumps to that line. `:filter` replaces all filters
+/// by one, while `:addfilter` and `:rmfilter` narrow or widen the ones set.
+pub enum Command {
+    AddFilter(String),
+    /// Turn an alert, counting from 1, on or off.
+    Alert {
+        number: usize,
+        enabled: bool,
+    },
+    Baud {
+        rate: u32,
+        baud_rate: BaudRate,
+    },
+    Clear,
+    Copy(Option<usize>),
+    Export(Option<PathBuf>),
+    Filter(Option<String>),
+    FilterMode(Combine),
+    Gauge,
+    Goto(usize),
+    Graph,
+    Hex,
+    Highlight,
+    /// Log to another file, or stop logging without one.
+    Log(Option<String>),
+    LogY,
+    Quit,
+    /// Set the serial settings of the device again.
+    Reconfigure,
+    RemoveFilter(Option<String>),
+    Save(PathBuf),
+    Timestamps,
+    Wrap(Wrap),
+}
+
+/// The names of all commands and a description of their arguments.
+pub const COMMANDS: &[(&str, &str)] = &[
+    ("addfilter", "<text>"),
+    ("alert", "<number> <on|off>"),
+    ("baud", "<rate>"),
+    ("clear", ""),
+    ("copy", "[lines]"),
+    ("export", "[file]"),
+    ("filter", "[text]"),
+    ("filtermode", "<all|any>"),
+    ("gauge", ""),
+    ("goto", "<line>"),
+    ("graph", ""),
+    ("hex", ""),
+    ("highlight", ""),
+    ("log", "[file]"),
+    ("logy", ""),
+    ("quit", ""),
+    ("reconfigure", ""),
+    ("rmfilter", "[text]"),
+    ("save", "<file>"),
+    ("timestamps", ""),
+    ("wrap", "<char|word|off>"),
+];
+
+impl Command {
+    /// Parses the input of the command palette, without the leading ':'.
+    pub fn parse(input: &str) -> anyhow::Result<Command> {
+        let input = input.trim();
+        let (name, arg) = match input.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (input, ""),
+        };
+        let command =
+            match name {
+                "addfilter" if arg.is_empty() => return Err(anyhow!("addfilter needs a text")),
+                "addfilter" => Command::AddFilter(arg.to_string()),
+                "alert" => parse_alert(arg)?,
+                "baud" => {
+                    let invalid = || anyhow!("invalid baud rate '{arg}'");
+                    let rate = arg.parse().map_err(|_| invalid())?;
+                    let baud_rate = crate::string_to_baudrate(arg).ok_or_else(invalid)?;
+                    Command::Baud { rate, baud_rate }
+                }
+                "clear" => Command::Clear,
+                "copy" if arg.is_empty() => Command::Copy(None),
+                "copy" => Command::Copy(Some(
+                    arg.parse()
+                        .map_err(|_| anyhow!("invalid number of lines '{arg}'"))?,
+                )),
+                "export" if arg.is_empty() => Command::Export(None),
+                "export" => Command::Export(Some(PathBuf::from(arg))),
+                "filter" if arg.is_empty() => Command::Filter(None),
+                "filter" => Command::Filter(Some(arg.to_string())),
+                "filtermode" => Command::FilterMode(
+                    Combine::from_str(arg, true)
+                        .map_err(|_| anyhow!("invalid filter mode '{arg}', expected all or any"))?,
+                ),
+                "gauge" => Command::Gauge,
+                "goto" => Command::Goto(parse_line_number(arg)?),
+                "graph" => Command::Graph,
+                "hex" => Command::Hex,
+                "highlight" => Command::Highlight,
+                "log" if arg.is_empty() => Command::Log(None),
+                "log" => Command::Log(Some(arg.to_string())),
+                "logy" => Command::LogY,
+                "quit" | "q" => Command::Quit,
+                "reconfigure" => Command::Reconfigure,
+                "rmfilter" if arg.is_empty() => Command::RemoveFilter(None),
+                "rmfilter" => Command::RemoveFilter(Some(arg.to_string())),
+                "save" if arg.is_empty() => return Err(anyhow!("save needs a file name")),
+                "save" => Command::Save(PathBuf::from(arg)),
+                "timestamps" => Command::Timestamps,
+                "wrap" => Command::Wrap(Wrap::from_str(arg, true).map_err(|_| {
+                    anyhow!("invalid wrap mode '{arg}', expected char, word or off")
+                })?),
+                "" => return Err(anyhow!("no command given")),
+                _ if name.starts_with(|c: char| c.is_ascii_digit()) && arg.is_empty() => {
+                    Command::Goto(parse_line_number(name)?)
+                }
+                _ => return Err(anyhow!("unknown command '{name}'")),
+            };
+        if !arg.is_empty()
+            && matches!(
+                command,
+                Command::Clear
+                    | Command::Gauge
+                    | Command::Graph
+                    | Command::Hex
+                    | Command::Highlight
+                    | Command::LogY
+                    | Command::Quit
+                    | Command::Reconfigure
+                    | Command::Timestamps
+            )
+        {
+            return Err(anyhow!("{name} takes no arguments"));
+        }
+        Ok(command)
+    }
+}
+
+/// Parses the arguments of `:alert`, e.g. "2 off".
+fn parse_alert(arg: &str) -> anyhow::Result<Command> {
+    let invalid = || anyhow!("invalid alert '{arg}', expected a number and on or off");
+    let (number, state) = arg.split_once(char::is_whitespace).ok_or_else(invalid)?;
+    let enabled = match state.trim() {
+        "on" => true,
+        "off" => false,
+        _ => return Err(invalid()),
+    };
+    Ok(Command::Alert {
+        number: number.parse().map_err(|_| invalid())?,
+        enabled,
+    })
+}
+
+fn parse_line_number(arg: &str) -> anyhow::Result<usize> {
+    arg.parse()
+        .map_err(|_| anyhow!("invalid line number '{arg}'"))
+}
+
+/// The commands whose names start with the word being typed. Once the name
+/// is followed by a space only that command matches.
+pub fn matching(input: &str) -> Vec<(&'static str, &'static str)> {
+    let input = input.trim_start();
+    match input.split_once(char::is_whitespace) {
+        Some((name, _)) => COMMANDS
+            .iter()
+            .filter(|(n, _)| *n == name)
+            .copied()
+            .collect(),
+        None => COMMANDS
+            .iter()
+            .filter(|(n, _)| n.starts_with(input))
+            .copied()
+            .collect(),
+    }
+}
+
+/// Completes the command name in `input` as far as it's unambiguous.
+pub fn complete(input: &str) -> Option<String> {
+    if input.trim_start().contains(char::is_whitespace) {
+        return None;
+    }
+    let candidates = matching(input);
+    let (first, _) = candidates.first()?;
+    if candidates.len() == 1 {
+        return Some(format!("{first} "));
+    }
+    let common = candidates.iter().fold(first.len(), |len, (name, _)| {
+        first
+            .bytes()
+            .zip(name.bytes())
+            .take(len)
+            .take_while(|(a, b)| a == b)
+            .count()
+    });
+    Some(first[..common].to_string())
+}
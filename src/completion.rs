@@ -0,0 +1,95 @@
+use std::path::Path;
+
+/// Completes the word being typed from a list of words, like the commands
+/// a device understands.
+pub struct Completer {
+    words: Vec<String>,
+}
+
+/// Cycling through the words the one being typed could be completed to.
+pub struct Completion {
+    candidates: Vec<String>,
+    current: usize,
+    /// The column the completed word starts at.
+    pub start: usize,
+}
+
+impl Completer {
+    /// Loads a file with one word per line. Empty lines and lines starting
+    /// with '#' are skipped.
+    pub fn load(path: &Path) -> anyhow::Result<Completer> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(Completer::new(content.lines()))
+    }
+
+    pub fn new<'a>(lines: impl Iterator<Item = &'a str>) -> Completer {
+        let mut words: Vec<String> = lines
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from)
+            .collect();
+        words.sort();
+        words.dedup();
+        Completer { words }
+    }
+
+    /// Starts completing the word that ends at column `col` of `line`.
+    pub fn complete(&self, line: &str, col: usize) -> Option<Completion> {
+        let before: Vec<char> = line.chars().take(col).collect();
+        let start = before
+            .iter()
+            .rposition(|c| c.is_whitespace())
+            .map_or(0, |idx| idx + 1);
+        let prefix: String = before[start..].iter().collect();
+        if prefix.is_empty() {
+            return None;
+        }
+        let candidates: Vec<String> = self
+            .words
+            .iter()
+            .filter(|word| word.starts_with(&prefix))
+            .cloned()
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        Some(Completion {
+            candidates,
+            current: 0,
+            start,
+        })
+    }
+}
+
+impl Completion {
+    /// The word the one being typed is completed to.
+    pub fn current(&self) -> &str {
+        &self.candidates[self.current]
+    }
+
+    /// Moves on to the next candidate, going back to the first after the last.
+    pub fn advance(&mut self) {
+        self.current = (self.current + 1) % self.candidates.len();
+    }
+
+    /// Whether there is more than one candidate to cycle through.
+    pub fn ambiguous(&self) -> bool {
+        self.candidates.len() > 1
+    }
+
+    /// All the candidates, with the current one in brackets.
+    pub fn hint(&self) -> String {
+        self.candidates
+            .iter()
+            .enumerate()
+            .map(|(i, word)| {
+                if i == self.current {
+                    format!("[{word}]")
+                } else {
+                    word.clone()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+}
@@ -0,0 +1,187 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+use clap::ArgEnum;
+use regex::Regex;
+use tui::style::Color;
+
+use crate::graph::{Extractor, Radix, Thresholds};
+
+/// Settings read from a config file.
+///
+/// The file consists of sections with `key = value` lines, e.g.
+///
+/// ```text
+/// # Plots the temperature in red.
+/// [extractor temperature]
+/// regex = temp=(-?\d+\.?\d*)
+/// capture = 1
+/// color = red
+/// # Shown in yellow above 80 and red above 95.
+/// warn = 80
+/// critical = 95
+///
+/// # A register printed as e.g. "status=0x1F".
+/// [extractor status]
+/// regex = status=(0x[0-9a-fA-F]+)
+/// capture = 1
+/// radix = hex
+/// ```
+#[derive(Default)]
+pub struct Config {
+    pub extractors: Vec<Extractor>,
+}
+
+/// A `[kind name]` section and its `key = value` pairs.
+struct Section {
+    kind: String,
+    name: String,
+    line_number: usize,
+    entries: Vec<(String, String)>,
+}
+
+impl Section {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+fn parse_sections(content: &str) -> anyhow::Result<Vec<Section>> {
+    let mut sections: Vec<Section> = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let mut parts = header.split_whitespace();
+            let kind = parts.next().unwrap_or_default().to_string();
+            let name = parts.collect::<Vec<_>>().join(" ");
+            sections.push(Section {
+                kind,
+                name,
+                line_number,
+                entries: Vec::new(),
+            });
+        } else if let Some((key, value)) = line.split_once('=') {
+            let section = sections
+                .last_mut()
+                .ok_or_else(|| anyhow!("line {line_number}: setting outside of a section"))?;
+            section
+                .entries
+                .push((key.trim().to_string(), value.trim().to_string()));
+        } else {
+            return Err(anyhow!("line {line_number}: expected `key = value`"));
+        }
+    }
+    Ok(sections)
+}
+
+pub fn parse_color(s: &str) -> Option<Color> {
+    Some(match s.to_lowercase().as_str() {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => return None,
+    })
+}
+
+fn parse_extractor(section: &Section) -> anyhow::Result<Extractor> {
+    let name = &section.name;
+    if name.is_empty() {
+        return Err(anyhow!(
+            "line {}: extractor has no name",
+            section.line_number
+        ));
+    }
+    let regex = section
+        .get("regex")
+        .ok_or_else(|| anyhow!("extractor '{name}' has no regex"))?;
+    let pattern = Regex::new(regex).context(format!("invalid regex for extractor '{name}'"))?;
+    let capture = match section.get("capture") {
+        Some(capture) => capture
+            .parse()
+            .map_err(|_| anyhow!("extractor '{name}': invalid capture index '{capture}'"))?,
+        None => 0,
+    };
+    if capture >= pattern.captures_len() {
+        return Err(anyhow!(
+            "extractor '{name}': the regex has no capture group {capture}"
+        ));
+    }
+    let color = match section.get("color") {
+        Some(color) => parse_color(color)
+            .ok_or_else(|| anyhow!("extractor '{name}': unknown color '{color}'"))?,
+        None => Color::Yellow,
+    };
+    let radix = section
+        .get("radix")
+        .map(|radix| {
+            Radix::from_str(radix, true).map_err(|_| {
+                anyhow!("extractor '{name}': invalid radix '{radix}', expected decimal or hex")
+            })
+        })
+        .transpose()?;
+    let threshold = |key: &str| {
+        section
+            .get(key)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| anyhow!("extractor '{name}': invalid {key} threshold '{value}'"))
+            })
+            .transpose()
+    };
+    Ok(Extractor {
+        name: name.clone(),
+        pattern,
+        capture,
+        color,
+        digital: false,
+        radix,
+        thresholds: Thresholds {
+            warn: threshold("warn")?,
+            critical: threshold("critical")?,
+        },
+    })
+}
+
+impl Config {
+    pub fn load(path: &Path) -> anyhow::Result<Config> {
+        let content = std::fs::read_to_string(path)?;
+        Config::parse(&content)
+    }
+
+    pub fn parse(content: &str) -> anyhow::Result<Config> {
+        let mut config = Config::default();
+        for section in parse_sections(content)? {
+            match section.kind.as_str() {
+                "extractor" => config.extractors.push(parse_extractor(&section)?),
+                kind => {
+                    return Err(anyhow!(
+                        "line {}: unknown section '{kind}'",
+                        section.line_number
+                    ))
+                }
+            }
+        }
+        Ok(config)
+    }
+}
@@ -0,0 +1,76 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{graph::Grapher, timefmt};
+
+/// Writes the graphed values to a CSV file as they are extracted, one row per sample.
+pub struct CsvStream {
+    file: BufWriter<File>,
+    /// Write the times in UTC instead of the local time zone.
+    utc: bool,
+}
+
+impl CsvStream {
+    /// Creates the file and writes the header for the series of `grapher`.
+    pub fn create(path: &Path, grapher: &Grapher, utc: bool) -> io::Result<CsvStream> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "{}", header(grapher))?;
+        file.flush()?;
+        Ok(CsvStream { file, utc })
+    }
+
+    pub fn write_sample(&mut self, grapher: &Grapher, sample: usize) -> io::Result<()> {
+        writeln!(self.file, "{}", row(grapher, sample, self.utc))?;
+        self.file.flush()
+    }
+}
+
+/// Writes the values of the samples the grapher has kept to a CSV file.
+pub fn export(grapher: &Grapher, path: &Path, utc: bool) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    writeln!(file, "{}", header(grapher))?;
+    for sample in grapher.kept_samples() {
+        writeln!(file, "{}", row(grapher, sample, utc))?;
+    }
+    file.flush()
+}
+
+/// The time, the sample number and a column for every series.
+fn header(grapher: &Grapher) -> String {
+    let mut columns = vec!["time".to_string(), "sample".to_string()];
+    for (i, series) in grapher.series.iter().enumerate() {
+        columns.push(match series.name.as_str() {
+            "" => format!("series {i}"),
+            name => field(name),
+        });
+    }
+    columns.join(",")
+}
+
+/// The columns of `sample`, left empty for the series that had no value in its line.
+fn row(grapher: &Grapher, sample: usize, utc: bool) -> String {
+    let mut columns = vec![
+        timefmt::iso8601(grapher.sample_time(sample), utc),
+        sample.to_string(),
+    ];
+    for series in &grapher.series {
+        columns.push(
+            grapher
+                .value(series, sample)
+                .map_or(String::new(), |v| v.to_string()),
+        );
+    }
+    columns.join(",")
+}
+
+/// Quotes `text` if it would otherwise be split into several fields.
+pub fn field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,132 @@
+use anyhow::anyhow;
+use clap::ArgEnum;
+
+/// How received NUL bytes and other control characters are displayed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum)]
+pub enum ControlMode {
+    /// Don't display them at all.
+    Strip,
+    /// Display them as a single placeholder glyph, e.g. "␀" or "␛".
+    Glyph,
+    /// Display their hex value.
+    Hex,
+    /// Display them in caret notation, e.g. ESC as "^[".
+    Caret,
+}
+
+impl ControlMode {
+    fn text(self, byte: u8) -> Option<String> {
+        match self {
+            ControlMode::Strip => None,
+            ControlMode::Glyph if byte == 0x7F => Some("␡".to_string()),
+            // The control pictures are in the order of the characters.
+            ControlMode::Glyph => char::from_u32(0x2400 + byte as u32).map(String::from),
+            ControlMode::Hex => Some(format!("0x{byte:X}")),
+            ControlMode::Caret => Some(format!("^{}", (byte ^ 0x40) as char)),
+        }
+    }
+}
+
+/// How typed text is encoded when it's sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum)]
+pub enum TxEncoding {
+    Utf8,
+    /// ISO 8859-1, a byte for each of the first 256 characters of Unicode.
+    Latin1,
+}
+
+impl TxEncoding {
+    /// The bytes `text` is sent as, or an error if it has characters that
+    /// can't be encoded.
+    pub fn encode(self, text: &str) -> anyhow::Result<Vec<u8>> {
+        match self {
+            TxEncoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            TxEncoding::Latin1 => text
+                .chars()
+                .map(|c| u8::try_from(c).map_err(|_| anyhow!("'{c}' can't be sent in latin1")))
+                .collect(),
+        }
+    }
+}
+
+/// Converts received bytes, and sent ones when they're echoed, to the text
+/// that is displayed and logged.
+pub struct Decoder {
+    pub nul: ControlMode,
+    /// How the control characters other than NUL, tab and the line endings
+    /// are displayed. They would be interpreted by the terminal as they are.
+    pub controls: ControlMode,
+}
+
+/// Tabs are expanded to the next multiple of this many columns.
+const TAB_WIDTH: usize = 8;
+
+impl Decoder {
+    pub fn new() -> Self {
+        Decoder {
+            nul: ControlMode::Strip,
+            controls: ControlMode::Caret,
+        }
+    }
+
+    /// The text written to the log file for `byte`.
+    pub fn log_text(&self, byte: u8) -> String {
+        if let Ok(ch) = std::str::from_utf8(&[byte]) {
+            format!("{}", ch.chars().next().unwrap())
+        } else {
+            // If it's not a vaild char, display out its hex value.
+            format!("0x{byte:X}")
+        }
+    }
+
+    /// The text displayed for `byte` when it comes after `before` on its
+    /// line, or `None` if it shouldn't be displayed.
+    pub fn display_text(&self, byte: u8, before: &str) -> Option<String> {
+        if byte == 0 {
+            return self.nul.text(byte);
+        }
+        // Only part of a line when it ends with another delimiter.
+        if byte == b'\n' || byte == b'\r' {
+            return Some(format!("0x{byte:X}"));
+        }
+        if byte == b'\t' {
+            return Some(" ".repeat(TAB_WIDTH - before.chars().count() % TAB_WIDTH));
+        }
+        if byte < 0x20 || byte == 0x7F {
+            return self.controls.text(byte);
+        }
+        Some(self.log_text(byte))
+    }
+
+    /// The text displayed for sent bytes that are echoed, one line of them
+    /// without its newline.
+    pub fn echo_text(&self, bytes: &[u8]) -> String {
+        self.sent_text(bytes, |b, text| self.display_text(b, text))
+    }
+
+    /// The text logged for a line of sent bytes.
+    pub fn sent_log_text(&self, bytes: &[u8]) -> String {
+        self.sent_text(bytes, |b, _| Some(self.log_text(b)))
+    }
+
+    /// The characters encoded as UTF-8 in `bytes` as they are, and the text
+    /// of `byte_text` for the other bytes, given the text before them.
+    fn sent_text(&self, bytes: &[u8], byte_text: impl Fn(u8, &str) -> Option<String>) -> String {
+        let mut text = String::new();
+        for chunk in bytes.utf8_chunks() {
+            for c in chunk.valid().chars() {
+                if c.is_ascii() {
+                    let byte_text = byte_text(c as u8, &text);
+                    text.extend(byte_text);
+                } else {
+                    text.push(c);
+                }
+            }
+            for &b in chunk.invalid() {
+                let byte_text = byte_text(b, &text);
+                text.extend(byte_text);
+            }
+        }
+        text
+    }
+}
@@ -0,0 +1,266 @@
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+
+const BY_ID_DIR: &str = "/dev/serial/by-id";
+const RESCAN_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A serial device that could be opened.
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    /// The real device node, e.g. /dev/ttyUSB0.
+    pub path: PathBuf,
+    /// The name of the symlink in /dev/serial/by-id pointing to `path`, if any.
+    pub by_id: Option<OsString>,
+}
+
+impl Candidate {
+    /// The name shown to the user. The by-id name is stable between boots
+    /// so it's preferred over the kernel name.
+    pub fn display_name(&self) -> String {
+        match &self.by_id {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => self.path.to_string_lossy().into_owned(),
+        }
+    }
+}
+
+/// Lists the devices in /dev/serial/by-id together with the nodes they point to.
+fn by_id_candidates() -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    let entries = match std::fs::read_dir(BY_ID_DIR) {
+        Ok(entries) => entries,
+        Err(_) => return candidates,
+    };
+    for dir_entry in entries.flatten() {
+        if let Ok(path) = std::fs::canonicalize(dir_entry.path()) {
+            candidates.push(Candidate {
+                path,
+                by_id: Some(dir_entry.file_name()),
+            });
+        }
+    }
+    candidates.sort_by(|a, b| a.by_id.cmp(&b.by_id));
+    candidates
+}
+
+/// Whether the name is that of a USB serial device, e.g. ttyUSB0 or ttyACM1.
+/// Names that aren't valid UTF-8 are matched by their bytes.
+fn is_arduino_like(file_name: &OsStr) -> bool {
+    file_name
+        .as_bytes()
+        .strip_prefix(b"tty")
+        .is_some_and(|rest| rest.starts_with(b"USB") || rest.starts_with(b"ACM"))
+}
+
+/// Whether `name` contains `pattern`, comparing bytes so that names that
+/// aren't valid UTF-8 can be matched too.
+fn contains(name: &OsStr, pattern: &str) -> bool {
+    let pattern = pattern.as_bytes();
+    pattern.is_empty() || name.as_bytes().windows(pattern.len()).any(|w| w == pattern)
+}
+
+/// Scans /dev for ttyUSB* and ttyACM* devices.
+fn dev_candidates() -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    let entries = match std::fs::read_dir("/dev/") {
+        Ok(entries) => entries,
+        Err(_) => return candidates,
+    };
+    for dir_entry in entries.flatten() {
+        let file_name = dir_entry.file_name();
+        if is_arduino_like(&file_name) {
+            candidates.push(Candidate {
+                path: Path::new("/dev").join(file_name),
+                by_id: None,
+            });
+        }
+    }
+    candidates.sort_by(|a, b| a.path.cmp(&b.path));
+    candidates
+}
+
+/// The time the device node was created, which is when the device was plugged in.
+fn plug_time(path: &Path) -> Option<(i64, i64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.ctime(), metadata.ctime_nsec()))
+}
+
+/// Finds all devices that look like an arduino, preferring the stable names in
+/// /dev/serial/by-id and falling back to scanning /dev when it doesn't exist.
+pub fn find_candidates() -> Vec<Candidate> {
+    let candidates = by_id_candidates();
+    if !candidates.is_empty() {
+        return candidates;
+    }
+    dev_candidates()
+}
+
+/// Picks a device to connect to. Unless `first` is set, the most recently
+/// plugged in device is chosen since that's usually the one that is wanted.
+pub fn find_possible_arduino_dev(first: bool) -> Option<Candidate> {
+    let mut candidates = find_candidates();
+    if !first {
+        candidates.sort_by_key(|c| std::cmp::Reverse(plug_time(&c.path)));
+    }
+    candidates.into_iter().next()
+}
+
+/// Resolves the device given on the command line. Accepts a path to a device,
+/// a by-id name or a unique substring of a by-id name.
+pub fn resolve_device(arg: &str) -> anyhow::Result<Candidate> {
+    let by_id = by_id_candidates();
+    let path = Path::new(arg);
+    if path.exists() {
+        let real_path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let name = by_id
+            .into_iter()
+            .find(|c| c.path == real_path)
+            .and_then(|c| c.by_id);
+        return Ok(Candidate {
+            path: real_path,
+            by_id: name,
+        });
+    }
+
+    let matches: Vec<Candidate> = by_id
+        .into_iter()
+        .filter(|c| c.by_id.as_ref().is_some_and(|name| contains(name, arg)))
+        .collect();
+    match matches.len() {
+        0 => Err(anyhow!("no such device '{arg}'")),
+        1 => Ok(matches.into_iter().next().unwrap()),
+        _ => {
+            let names: Vec<String> = matches.iter().map(|c| c.display_name()).collect();
+            Err(anyhow!(
+                "'{arg}' matches several devices:\n  {}",
+                names.join("\n  ")
+            ))
+        }
+    }
+}
+
+/// Watches /dev for devices that are plugged in.
+pub struct Watcher {
+    target: Option<String>,
+    first: bool,
+    inotify: Option<Inotify>,
+    next_scan: Option<Instant>,
+}
+
+impl Watcher {
+    /// Creates a watcher looking for `target` as given to `resolve_device`, or
+    /// for any arduino like device if there is no target.
+    pub fn new(target: Option<String>, first: bool) -> Self {
+        let flags = AddWatchFlags::IN_CREATE | AddWatchFlags::IN_ATTRIB;
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)
+            .ok()
+            .and_then(|inotify| {
+                inotify.add_watch("/dev", flags).ok()?;
+                // An explicitly given device might live somewhere else.
+                let target_dir = target.as_ref().and_then(|t| Path::new(t).parent());
+                if let Some(dir) = target_dir.filter(|dir| dir.is_dir()) {
+                    let _ = inotify.add_watch(dir, flags);
+                }
+                Some(inotify)
+            });
+        Watcher {
+            target,
+            first,
+            inotify,
+            next_scan: Some(Instant::now()),
+        }
+    }
+
+    /// Makes the next call to `poll` scan for devices after a short while even
+    /// if nothing has happened in /dev.
+    pub fn rescan_soon(&mut self) {
+        self.next_scan = Some(Instant::now() + RESCAN_INTERVAL);
+    }
+
+    /// Blocks until there is a device to connect to, or until `timeout` has passed.
+    pub fn wait(&mut self, timeout: Option<Duration>) -> Option<Candidate> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            if let Some(candidate) = self.poll() {
+                return Some(candidate);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return None;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    /// Returns a device to connect to if one has appeared since the last call.
+    pub fn poll(&mut self) -> Option<Candidate> {
+        let mut scan = false;
+        match &self.inotify {
+            Some(inotify) => {
+                if let Ok(events) = inotify.read_events() {
+                    scan = self.target.is_some()
+                        || events.iter().any(|event| {
+                            event
+                                .name
+                                .as_ref()
+                                .is_some_and(|name| name.as_bytes().starts_with(b"tty"))
+                        });
+                }
+            }
+            // Without inotify we have to fall back to scanning periodically.
+            None => {
+                if self.next_scan.is_none() {
+                    self.rescan_soon();
+                }
+            }
+        }
+        if let Some(next_scan) = self.next_scan {
+            if Instant::now() >= next_scan {
+                self.next_scan = None;
+                scan = true;
+            }
+        }
+        if !scan {
+            return None;
+        }
+        match &self.target {
+            Some(target) => resolve_device(target).ok(),
+            None => find_possible_arduino_dev(self.first),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unusual_device_names() {
+        let name = |bytes: &[u8]| OsStr::from_bytes(bytes).to_os_string();
+        for (file_name, expected) in [
+            (&b"ttyUSB0"[..], true),
+            (b"ttyACM12", true),
+            (b"ttyUSB", true),
+            (b"ttyUSB\xff\xfe", true),
+            (b"ttyS0", false),
+            (b"tty", false),
+            (b"tt", false),
+            (b"", false),
+            (b"myttyUSB0", false),
+            (b"\xfftyUSB0", false),
+        ] {
+            assert_eq!(is_arduino_like(&name(file_name)), expected, "{file_name:?}");
+        }
+
+        let by_id = name(b"usb-\xe9tude_board-if00");
+        assert!(contains(&by_id, "tude_b"));
+        assert!(contains(&by_id, ""));
+        assert!(!contains(&by_id, "étude"));
+        assert!(!contains(&name(b"ab"), "abc"));
+    }
+}
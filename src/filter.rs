@@ -0,0 +1,58 @@
+use clap::ArgEnum;
+
+/// How the patterns of a filter set are combined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum)]
+pub enum Combine {
+    /// Lines have to contain every pattern.
+    All,
+    /// Lines have to contain at least one of the patterns.
+    Any,
+}
+
+/// The patterns the output is filtered by. Without any patterns every line is shown.
+pub struct FilterSet {
+    pub patterns: Vec<String>,
+    pub combine: Combine,
+}
+
+impl FilterSet {
+    pub fn new() -> Self {
+        FilterSet {
+            patterns: Vec::new(),
+            combine: Combine::All,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        match self.combine {
+            Combine::All => self.patterns.iter().all(|p| text.contains(p.as_str())),
+            Combine::Any => self.patterns.iter().any(|p| text.contains(p.as_str())),
+        }
+    }
+
+    /// Removes `pattern`, or the last one added if it's `None`. Returns
+    /// whether there was anything to remove.
+    pub fn remove(&mut self, pattern: Option<&str>) -> bool {
+        let idx = match pattern {
+            Some(pattern) => self.patterns.iter().rposition(|p| p == pattern),
+            None => self.patterns.len().checked_sub(1),
+        };
+        idx.map(|idx| self.patterns.remove(idx)).is_some()
+    }
+
+    /// The patterns joined by the way they are combined, e.g. "ERR | WARN".
+    pub fn describe(&self) -> String {
+        let separator = match self.combine {
+            Combine::All => " & ",
+            Combine::Any => " | ",
+        };
+        self.patterns.join(separator)
+    }
+}
@@ -0,0 +1,221 @@
+use tui::{
+    backend::Backend,
+    layout::{Alignment, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+
+use crate::graph::Grapher;
+
+/// The rows of a big character.
+pub const BIG_ROWS: usize = 5;
+/// The rows of the gauge pane, the big characters and the borders.
+pub const GAUGE_ROWS: u16 = BIG_ROWS as u16 + 2;
+/// The columns of the gauge pane when it's next to the graph.
+pub const GAUGE_COLUMNS: u16 = 36;
+/// The number of values in each of the means that are compared for the trend.
+const TREND_SAMPLES: usize = 5;
+/// Changes smaller than this share of the range of the window are steady.
+const TREND_STEADY: f64 = 0.05;
+
+/// Which way the values are heading.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trend {
+    Rising,
+    Falling,
+    Steady,
+}
+
+impl Trend {
+    /// Compares the mean of the latest `TREND_SAMPLES` values with that of
+    /// the ones before them, relative to the range of all of `values`.
+    pub fn of(values: &[f64]) -> Trend {
+        let n = TREND_SAMPLES.min(values.len() / 2);
+        if n == 0 {
+            return Trend::Steady;
+        }
+        let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+        let latest = mean(&values[values.len() - n..]);
+        let before = mean(&values[values.len() - 2 * n..values.len() - n]);
+        let (min, max) = values
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+                (min.min(*v), max.max(*v))
+            });
+        let steady = TREND_STEADY * (max - min);
+        if latest - before > steady {
+            Trend::Rising
+        } else if before - latest > steady {
+            Trend::Falling
+        } else {
+            Trend::Steady
+        }
+    }
+
+    pub fn arrow(self) -> char {
+        match self {
+            Trend::Rising => '↑',
+            Trend::Falling => '↓',
+            Trend::Steady => '→',
+        }
+    }
+}
+
+/// The rows of a digit, sign or point drawn in blocks, or None for other characters.
+fn glyph(c: char) -> Option<[&'static str; BIG_ROWS]> {
+    Some(match c {
+        '0' => ["███", "█ █", "█ █", "█ █", "███"],
+        '1' => [" █ ", "██ ", " █ ", " █ ", "███"],
+        '2' => ["███", "  █", "███", "█  ", "███"],
+        '3' => ["███", "  █", "███", "  █", "███"],
+        '4' => ["█ █", "█ █", "███", "  █", "  █"],
+        '5' => ["███", "█  ", "███", "  █", "███"],
+        '6' => ["███", "█  ", "███", "█ █", "███"],
+        '7' => ["███", "  █", "  █", "  █", "  █"],
+        '8' => ["███", "█ █", "███", "█ █", "███"],
+        '9' => ["███", "█ █", "███", "  █", "███"],
+        '-' => ["   ", "   ", "███", "   ", "   "],
+        '+' => ["   ", " █ ", "███", " █ ", "   "],
+        '.' => [" ", " ", " ", " ", "█"],
+        _ => return None,
+    })
+}
+
+/// `text` in big characters, one space apart. Other characters than digits,
+/// signs and points are left as they are on the bottom row, like the SI
+/// prefix of "1.52k".
+pub fn big_text(text: &str) -> [String; BIG_ROWS] {
+    let mut rows: [String; BIG_ROWS] = Default::default();
+    for (i, c) in text.chars().enumerate() {
+        for row in &mut rows {
+            if i > 0 {
+                row.push(' ');
+            }
+        }
+        match glyph(c) {
+            Some(glyph) => {
+                for (row, part) in rows.iter_mut().zip(glyph) {
+                    row.push_str(part);
+                }
+            }
+            None => {
+                for row in &mut rows[..BIG_ROWS - 1] {
+                    row.push(' ');
+                }
+                rows[BIG_ROWS - 1].push(c);
+            }
+        }
+    }
+    rows
+}
+
+/// Draws the latest value of the first shown series in big characters, with
+/// `unit` and an arrow of the trend next to it. It's colored by the thresholds
+/// of the series if it has any. A single row is used when there isn't room.
+pub fn render<B: Backend>(f: &mut Frame<B>, area: Rect, grapher: &Grapher, unit: &str) {
+    let shown = grapher
+        .series
+        .iter()
+        .enumerate()
+        .find(|(_, series)| series.visible);
+    let mut block = Block::default().borders(Borders::ALL);
+    if let Some((_, series)) = shown.filter(|(_, series)| !series.name.is_empty()) {
+        block = block.title(Span::styled(
+            series.name.clone(),
+            Style::default().fg(series.color),
+        ));
+    }
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let values: Vec<f64> = match shown {
+        Some((_, series)) => grapher
+            .visible_data(series)
+            .iter()
+            .map(|(_, y)| *y)
+            .collect(),
+        None => Vec::new(),
+    };
+    let (Some((idx, series)), Some(&latest)) = (shown, values.last()) else {
+        let note = Paragraph::new(Span::styled(
+            "waiting for data…",
+            Style::default().fg(Color::DarkGray),
+        ))
+        .alignment(Alignment::Center);
+        let row = Rect::new(
+            inner.x,
+            inner.y + inner.height / 2,
+            inner.width,
+            inner.height.min(1),
+        );
+        return f.render_widget(note, row);
+    };
+    let color = grapher
+        .extractors
+        .get(idx)
+        .and_then(|extractor| extractor.thresholds.severity(latest))
+        .map_or(series.color, |severity| severity.color());
+    let style = Style::default().fg(color).add_modifier(Modifier::BOLD);
+    let value = grapher.number_format.format(latest, [latest, latest]);
+    let value = value.trim();
+    let arrow = Trend::of(&values).arrow();
+
+    let big = big_text(value);
+    let side = unit.chars().count().max(1);
+    let width = big[0].chars().count() + 1 + side;
+    let lines: Vec<Spans> = if inner.height as usize >= BIG_ROWS && inner.width as usize >= width {
+        let top = (inner.height as usize - BIG_ROWS) / 2;
+        let mut lines = vec![Spans::default(); top];
+        lines.extend(big.into_iter().enumerate().map(|(i, row)| {
+            let beside = match i {
+                2 => arrow.to_string(),
+                i if i == BIG_ROWS - 1 => unit.to_string(),
+                _ => String::new(),
+            };
+            Spans::from(Span::styled(format!("{row} {beside:side$}"), style))
+        }));
+        lines
+    } else {
+        let mut text = value.to_string();
+        if !unit.is_empty() {
+            text.push(' ');
+            text.push_str(unit);
+        }
+        let top = inner.height.saturating_sub(1) as usize / 2;
+        let mut lines = vec![Spans::default(); top];
+        lines.push(Spans::from(Span::styled(format!("{text} {arrow}"), style)));
+        lines
+    };
+    f.render_widget(Paragraph::new(lines).alignment(Alignment::Center), inner);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn big_text_keeps_prefixes_on_the_bottom_row() {
+        assert_eq!(
+            big_text("-1.5k"),
+            [
+                "     █    ███  ",
+                "    ██    █    ",
+                "███  █    ███  ",
+                "     █      █  ",
+                "    ███ █ ███ k",
+            ]
+        );
+    }
+
+    #[test]
+    fn trend_of_the_latest_values() {
+        assert_eq!(Trend::of(&[]), Trend::Steady);
+        assert_eq!(Trend::of(&[5.0]), Trend::Steady);
+        assert_eq!(Trend::of(&[1.0, 2.0]), Trend::Rising);
+        assert_eq!(Trend::of(&[3.0, 2.0, 1.0, 0.0]), Trend::Falling);
+        // Noise that doesn't move the mean is steady.
+        let noisy = [1.0, 9.0, 5.0, 3.0, 7.0, 9.0, 1.0, 7.0, 3.0, 5.0];
+        assert_eq!(Trend::of(&noisy), Trend::Steady);
+    }
+}
@@ -0,0 +1,1161 @@
+use std::ops::Range;
+use std::time::{Duration, Instant, SystemTime};
+
+use clap::ArgEnum;
+use ordered_float::OrderedFloat;
+use regex::Regex;
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    symbols,
+    text::{Span, Spans},
+    widgets::{Axis, Block, Borders, Chart, Dataset, GraphType, Paragraph, Sparkline},
+    Frame,
+};
+
+use crate::{csv::CsvStream, numfmt::NumberFormat, timefmt};
+
+/// Graphs lower than this are drawn as a sparkline by `GraphStyle::Auto`.
+const SPARKLINE_BELOW: u16 = 8;
+/// The colors of the series from `--series` and of the channels of a binary stream, in turn.
+const SERIES_COLORS: [Color; 6] = [
+    Color::Yellow,
+    Color::Cyan,
+    Color::Magenta,
+    Color::Green,
+    Color::Red,
+    Color::Blue,
+];
+/// The characters of a sparkline in the status bar, from the lowest value to the highest.
+const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// How much every new interval between samples weighs in the estimated rate.
+const RATE_SMOOTHING: f64 = 0.1;
+/// The shortest and longest intervals between samples are shown for this long back.
+const GAP_WINDOW: Duration = Duration::from_secs(5);
+/// The samples are considered stalled after this many of the expected intervals without one.
+const STALLED_AFTER: f64 = 3.0;
+/// The number of the latest samples that are kept for exporting them, or
+/// the window if it's longer. Older ones are dropped once there are twice
+/// as many.
+pub const KEPT_SAMPLES: usize = 10_000;
+
+/// How the graph is drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum)]
+pub enum GraphStyle {
+    /// A chart, or a sparkline when there isn't room for the axes and labels.
+    Auto,
+    Chart,
+    /// Only the shape of the first series and its latest value.
+    Sparkline,
+}
+
+/// How extracted numbers are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum)]
+pub enum Radix {
+    Decimal,
+    /// Hexadecimal integers, with or without "0x", e.g. ADC readings like "0x03FF".
+    Hex,
+}
+
+/// Extracts a value from received lines.
+#[derive(Clone)]
+pub struct Extractor {
+    pub name: String,
+    pub pattern: Regex,
+    /// Index of the capture group containing the value.
+    pub capture: usize,
+    pub color: Color,
+    /// The value is a digital level, 0 or 1, which may also be given as e.g. "high" or "off".
+    pub digital: bool,
+    /// Decimal if not set.
+    pub radix: Option<Radix>,
+    pub thresholds: Thresholds,
+}
+
+/// The values above which an extracted value is a warning or critical.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Thresholds {
+    pub warn: Option<f64>,
+    pub critical: Option<f64>,
+}
+
+/// How a value compares to the thresholds of its extractor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Normal,
+    Warn,
+    Critical,
+}
+
+impl Thresholds {
+    /// The severity of `value`, if there are any thresholds.
+    pub fn severity(&self, value: f64) -> Option<Severity> {
+        if self.warn.is_none() && self.critical.is_none() {
+            return None;
+        }
+        Some(if self.critical.is_some_and(|critical| value > critical) {
+            Severity::Critical
+        } else if self.warn.is_some_and(|warn| value > warn) {
+            Severity::Warn
+        } else {
+            Severity::Normal
+        })
+    }
+}
+
+impl Severity {
+    pub fn color(self) -> Color {
+        match self {
+            Severity::Normal => Color::Green,
+            Severity::Warn => Color::Yellow,
+            Severity::Critical => Color::Red,
+        }
+    }
+}
+
+/// The lowest or highest value of a series and when it was received.
+#[derive(Clone, Copy)]
+pub struct Peak {
+    pub value: f64,
+    pub time: Instant,
+}
+
+/// The bounds of the y axis, which only shrink once the values have fit in
+/// narrower ones for a while.
+struct Fit {
+    bounds: [f64; 2],
+    /// The sample since which the values would have fit in narrower bounds.
+    tighter_since: Option<usize>,
+}
+
+/// The values extracted by one extractor.
+pub struct Series {
+    pub name: String,
+    pub color: Color,
+    pub data: Vec<(f64, f64)>,
+    /// The lowest value since the start or the last reset, not only within the window.
+    pub global_min: Option<Peak>,
+    pub global_max: Option<Peak>,
+    /// Whether the series is drawn. Values are still recorded while it's hidden.
+    pub visible: bool,
+}
+
+pub struct Grapher {
+    pub extractors: Vec<Extractor>,
+    /// One series for every extractor.
+    pub series: Vec<Series>,
+    pub window_len: usize,
+    pub window: [f64; 2],
+    /// Draw the values as steps that hold until the next sample instead of
+    /// interpolating between them.
+    pub steps: bool,
+    /// Plot the values on a logarithmic y axis. Values that aren't positive are left out.
+    pub log_y: bool,
+    /// Plot the rate of change of the first series below the graph.
+    pub derivative: bool,
+    /// The number of rates of change the plotted one is the average of, to
+    /// smooth out noise. 1 plots them as they are.
+    pub derivative_smoothing: usize,
+    /// The index of the series plotted against a y axis of its own on the right.
+    pub y2: Option<usize>,
+    /// The bounds of the right y axis, found from the values if not set.
+    pub y2_bounds: Option<[f64; 2]>,
+    pub style: GraphStyle,
+    /// Draw every series as a square wave between 0 and 1 in a lane of its
+    /// own, like a logic analyzer.
+    pub digital: bool,
+    /// The number of samples the values have to fit in narrower bounds
+    /// before the y axis shrinks to them. It grows right away.
+    pub shrink_after: usize,
+    fit: Option<Fit>,
+    /// Fit the y axes to this percentile of the visible values, and the one
+    /// as far from the bottom, instead of to all of them, so that outliers
+    /// don't flatten the rest. Between 50 and 100.
+    pub percentile: Option<f64>,
+    /// Fit the y axes to bounds as far below zero as above it, for signals
+    /// that swing both ways. Left alone on a logarithmic axis.
+    pub symmetric: bool,
+    /// How the labels of the axes and the values in the titles are formatted.
+    pub number_format: NumberFormat,
+    /// Every sample is written to this as well.
+    pub out: Option<CsvStream>,
+    /// The number of lines a value has been extracted from.
+    samples: usize,
+    /// The first sample that hasn't been dropped.
+    first_kept: usize,
+    /// When the grapher was created, for the time of every sample.
+    start: Instant,
+    start_time: SystemTime,
+    /// When every kept sample was taken, after `start`.
+    sample_offsets: Vec<Duration>,
+    /// The exponentially smoothed interval between samples, in seconds.
+    interval: Option<f64>,
+}
+
+impl Extractor {
+    /// Extracts the first number in the line.
+    pub fn first_number() -> Self {
+        Extractor {
+            name: String::new(),
+            pattern: Regex::new("(\\-?\\d+\\.?[\\d]*)").unwrap(),
+            capture: 0,
+            color: Color::Yellow,
+            digital: false,
+            radix: None,
+            thresholds: Thresholds::default(),
+        }
+    }
+
+    /// Extracts the first hexadecimal number in the line. Without "0x" it
+    /// needs a decimal digit so that words like "add" aren't taken for one.
+    pub fn first_hex_number() -> Self {
+        Extractor {
+            pattern: Regex::new(r"(?i)-?\b(0x[0-9a-f]+|[0-9a-f]*\d[0-9a-f]*)\b").unwrap(),
+            radix: Some(Radix::Hex),
+            ..Extractor::first_number()
+        }
+    }
+
+    /// Extracts the first capture group of `pattern` into the `index`th of several series,
+    /// which gets a color of its own.
+    pub fn series(name: String, pattern: Regex, index: usize) -> Self {
+        Extractor {
+            name,
+            pattern,
+            capture: 1,
+            color: SERIES_COLORS[index % SERIES_COLORS.len()],
+            digital: false,
+            radix: None,
+            thresholds: Thresholds::default(),
+        }
+    }
+
+    /// Extracts the first digital level in the line, a 0 or 1 or a word like "high".
+    pub fn first_level() -> Self {
+        Extractor {
+            name: String::new(),
+            pattern: Regex::new("(?i)\\b(0|1|high|low|on|off|true|false)\\b").unwrap(),
+            capture: 0,
+            color: Color::Yellow,
+            digital: true,
+            radix: None,
+            thresholds: Thresholds::default(),
+        }
+    }
+
+    pub fn extract(&self, line: &str) -> Option<f64> {
+        self.parse(&line[self.value_range(line)?])
+    }
+
+    /// The byte range of the value in the line, if there is one.
+    pub fn value_range(&self, line: &str) -> Option<Range<usize>> {
+        let value = self.pattern.captures(line)?.get(self.capture)?;
+        self.parse(value.as_str())?;
+        Some(value.range())
+    }
+
+    /// Digital levels are 1 for any number other than 0.
+    fn parse(&self, text: &str) -> Option<f64> {
+        if !self.digital {
+            return self.parse_number(text);
+        }
+        match text.to_lowercase().as_str() {
+            "high" | "h" | "on" | "true" => Some(1.0),
+            "low" | "l" | "off" | "false" => Some(0.0),
+            number => self
+                .parse_number(number)
+                .map(|v| if v != 0.0 { 1.0 } else { 0.0 }),
+        }
+    }
+
+    fn parse_number(&self, text: &str) -> Option<f64> {
+        if self.radix != Some(Radix::Hex) {
+            return text.parse().ok();
+        }
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text),
+        };
+        let digits = digits
+            .strip_prefix("0x")
+            .or_else(|| digits.strip_prefix("0X"))
+            .unwrap_or(digits);
+        if digits.starts_with('+') {
+            return None;
+        }
+        let value = u64::from_str_radix(digits, 16).ok()? as f64;
+        Some(if negative { -value } else { value })
+    }
+}
+
+impl Grapher {
+    pub fn new(extractors: Vec<Extractor>, window_len: usize) -> Self {
+        let series = extractors
+            .iter()
+            .map(|e| Series {
+                name: e.name.clone(),
+                color: e.color,
+                data: Vec::new(),
+                global_min: None,
+                global_max: None,
+                visible: true,
+            })
+            .collect();
+        Grapher {
+            extractors,
+            series,
+            window_len,
+            window: [0.0, window_len as f64],
+            steps: false,
+            log_y: false,
+            derivative: false,
+            derivative_smoothing: 1,
+            y2: None,
+            y2_bounds: None,
+            style: GraphStyle::Auto,
+            digital: false,
+            shrink_after: 30,
+            fit: None,
+            percentile: None,
+            symmetric: false,
+            number_format: NumberFormat::Auto,
+            out: None,
+            samples: 0,
+            start: Instant::now(),
+            start_time: SystemTime::now(),
+            sample_offsets: Vec::new(),
+            first_kept: 0,
+            interval: None,
+        }
+    }
+
+    /// A grapher without extractors, for values decoded from a binary stream
+    /// with `add_values`. The channels are named if there are several.
+    pub fn with_channels(channels: usize, window_len: usize) -> Self {
+        let mut grapher = Grapher::new(Vec::new(), window_len);
+        grapher.series = (0..channels)
+            .map(|i| Series {
+                name: match channels {
+                    1 => String::new(),
+                    _ => format!("ch {i}"),
+                },
+                color: SERIES_COLORS[i % SERIES_COLORS.len()],
+                data: Vec::new(),
+                global_min: None,
+                global_max: None,
+                visible: true,
+            })
+            .collect();
+        grapher
+    }
+
+    /// The value every extractor finds in a completed line, if any.
+    pub fn extract(&self, line: &str) -> Vec<Option<f64>> {
+        self.extractors.iter().map(|e| e.extract(line)).collect()
+    }
+
+    /// Records a sample with a value for every series that has one. Returns
+    /// why the CSV file stopped being written, which only happens once.
+    pub fn add_values(&mut self, values: &[Option<f64>]) -> Option<String> {
+        let x = self.samples as f64;
+        let now = Instant::now();
+        let mut found = false;
+        for (value, series) in values.iter().zip(&mut self.series) {
+            if let Some(val) = *value {
+                series.data.push((x, val));
+                let peak = Peak {
+                    value: val,
+                    time: now,
+                };
+                if series.global_min.is_none_or(|min| val < min.value) {
+                    series.global_min = Some(peak);
+                }
+                if series.global_max.is_none_or(|max| val > max.value) {
+                    series.global_max = Some(peak);
+                }
+                found = true;
+            }
+        }
+        if !found {
+            return None;
+        }
+        if x + self.window_len as f64 / 10.0 > self.window[1] {
+            self.window[0] += 1.0;
+            self.window[1] += 1.0;
+        }
+        let offset = now - self.start;
+        if let Some(last) = self.sample_offsets.last() {
+            let interval = (offset - *last).as_secs_f64();
+            self.interval = Some(match self.interval {
+                Some(smoothed) => smoothed + RATE_SMOOTHING * (interval - smoothed),
+                None => interval,
+            });
+        }
+        self.sample_offsets.push(offset);
+        self.samples += 1;
+        self.drop_old_samples();
+        self.update_fit();
+        let mut out = self.out.take()?;
+        match out.write_sample(self, self.samples - 1) {
+            Ok(()) => {
+                self.out = Some(out);
+                None
+            }
+            Err(e) => Some(format!("writing the CSV file: {e}")),
+        }
+    }
+
+    /// The samples that haven't been dropped yet.
+    pub fn kept_samples(&self) -> Range<usize> {
+        self.first_kept..self.samples
+    }
+
+    /// Drops the values of the samples before the latest `KEPT_SAMPLES`, or
+    /// the window if it's longer, once there are twice as many.
+    fn drop_old_samples(&mut self) {
+        let kept = self.window_len.max(KEPT_SAMPLES);
+        if self.samples - self.first_kept < 2 * kept {
+            return;
+        }
+        let first = self.samples - kept;
+        for series in &mut self.series {
+            let idx = series.data.partition_point(|(x, _)| *x < first as f64);
+            series.data.drain(..idx);
+        }
+        self.sample_offsets.drain(..first - self.first_kept);
+        self.first_kept = first;
+    }
+
+    /// The wall-clock time `sample`, which has been kept, was taken at.
+    pub fn sample_time(&self, sample: usize) -> SystemTime {
+        self.start_time + self.sample_offsets[sample - self.first_kept]
+    }
+
+    /// The value `series` got from the line of `sample`, if it had one.
+    pub fn value(&self, series: &Series, sample: usize) -> Option<f64> {
+        let x = sample as f64;
+        let idx = series.data.partition_point(|(sx, _)| *sx < x);
+        series
+            .data
+            .get(idx)
+            .filter(|(sx, _)| *sx == x)
+            .map(|(_, y)| *y)
+    }
+
+    /// Where the values graphed from `line` are and the color of their series.
+    pub fn value_ranges(&self, line: &str) -> Vec<(Range<usize>, Color)> {
+        self.extractors
+            .iter()
+            .filter_map(|e| Some((e.value_range(line)?, e.color)))
+            .collect()
+    }
+
+    /// Where the values with thresholds are in `line` and how they compare to them.
+    pub fn severities(&self, line: &str) -> Vec<(Range<usize>, Severity)> {
+        self.extractors
+            .iter()
+            .filter_map(|e| {
+                let range = e.value_range(line)?;
+                let severity = e.thresholds.severity(e.parse(&line[range.clone()])?)?;
+                Some((range, severity))
+            })
+            .collect()
+    }
+
+    /// The series with the given name or else index.
+    pub fn series_index(&self, name_or_index: &str) -> Option<usize> {
+        self.series
+            .iter()
+            .position(|s| s.name == name_or_index)
+            .or_else(|| {
+                name_or_index
+                    .parse()
+                    .ok()
+                    .filter(|i| *i < self.series.len())
+            })
+    }
+
+    /// Hides the series at `idx` if it's shown and shows it if it's hidden.
+    /// Returns the series, or `None` if there is none at `idx`.
+    pub fn toggle_series(&mut self, idx: usize) -> Option<&Series> {
+        let series = self.series.get_mut(idx)?;
+        series.visible = !series.visible;
+        // The axis only fits the shown series.
+        self.refit();
+        self.series.get(idx)
+    }
+
+    /// Fits the y axis to the values within the window right away.
+    pub fn refit(&mut self) {
+        self.fit = None;
+        self.update_fit();
+    }
+
+    /// Grows the y axis to the latest values, or shrinks it if they have
+    /// fit in narrower bounds for `shrink_after` samples.
+    fn update_fit(&mut self) {
+        let plotted: Vec<(f64, f64)> = self
+            .series
+            .iter()
+            .enumerate()
+            .filter(|(i, series)| Some(*i) != self.y2 && series.visible)
+            .flat_map(|(_, series)| self.plotted_data(series))
+            .collect();
+        if plotted.is_empty() {
+            self.fit = None;
+            return;
+        }
+        let target = round_bounds(self.auto_bounds(plotted.iter().map(|(_x, y)| *y).collect()));
+        self.fit = Some(match self.fit.take() {
+            Some(Fit {
+                bounds: [min, max], ..
+            }) if target[0] < min || target[1] > max => Fit {
+                bounds: [min.min(target[0]), max.max(target[1])],
+                tighter_since: None,
+            },
+            Some(Fit { bounds, .. }) if bounds == target => Fit {
+                bounds,
+                tighter_since: None,
+            },
+            Some(Fit {
+                bounds,
+                tighter_since,
+            }) => {
+                let since = tighter_since.unwrap_or(self.samples);
+                if self.samples - since >= self.shrink_after {
+                    Fit {
+                        bounds: target,
+                        tighter_since: None,
+                    }
+                } else {
+                    Fit {
+                        bounds,
+                        tighter_since: Some(since),
+                    }
+                }
+            }
+            None => Fit {
+                bounds: target,
+                tighter_since: None,
+            },
+        });
+    }
+
+    /// The bounds of a y axis showing `values`, or the percentile of them if set.
+    fn auto_bounds(&self, mut values: Vec<f64>) -> [f64; 2] {
+        let [min, max] = match self.percentile.filter(|_| !values.is_empty()) {
+            None => y_bounds(values.into_iter()),
+            Some(percentile) => {
+                // Only the visible values are sorted, not the whole history.
+                values.sort_by(f64::total_cmp);
+                let at = |p: f64| values[((values.len() - 1) as f64 * p / 100.0).round() as usize];
+                y_bounds([at(100.0 - percentile), at(percentile)].into_iter())
+            }
+        };
+        if self.symmetric && !self.log_y {
+            let max = min.abs().max(max.abs());
+            return [-max, max];
+        }
+        [min, max]
+    }
+
+    /// Forgets the lowest and highest values seen so far.
+    pub fn reset_peaks(&mut self) {
+        for series in &mut self.series {
+            series.global_min = None;
+            series.global_max = None;
+        }
+    }
+
+    /// No sample has been taken for several of the intervals they have been
+    /// coming in at.
+    pub fn stalled(&self) -> bool {
+        let (Some(interval), Some(last)) = (self.interval, self.sample_offsets.last()) else {
+            return false;
+        };
+        let since = self.start.elapsed().saturating_sub(*last);
+        since.as_secs_f64() > STALLED_AFTER * interval
+    }
+
+    /// The estimated rate of samples and the shortest and longest intervals
+    /// between them lately, e.g. "~48.2 Hz, gaps 18.1-25.3ms", or "stalled".
+    fn rate_span(&self) -> Option<Span<'static>> {
+        let interval = self.interval?;
+        if self.stalled() {
+            return Some(Span::styled(
+                "stalled",
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            ));
+        }
+        let last = *self.sample_offsets.last()?;
+        let recent = self
+            .sample_offsets
+            .iter()
+            .rev()
+            .take_while(|offset| last - **offset <= GAP_WINDOW)
+            .collect::<Vec<_>>();
+        let (shortest, longest) = recent
+            .windows(2)
+            .map(|w| *w[0] - *w[1])
+            .fold((Duration::MAX, Duration::ZERO), |(min, max), gap| {
+                (min.min(gap), max.max(gap))
+            });
+        let mut text = format!("~{:.1} Hz", 1.0 / interval);
+        if shortest <= longest {
+            text.push_str(&format!(", gaps {}-{}", gap(shortest), gap(longest)));
+        }
+        Some(Span::raw(text))
+    }
+
+    /// The title of the graph, showing the rate of samples and the lowest and
+    /// highest values of every series.
+    fn peaks_title(&self) -> Spans<'_> {
+        let mut spans = Vec::new();
+        if let Some(rate) = self.rate_span() {
+            spans.push(rate);
+            spans.push(Span::raw("   "));
+        }
+        for series in &self.series {
+            // Formatted alike so that the lowest and highest can be told apart.
+            let range = match (series.global_min, series.global_max) {
+                (Some(min), Some(max)) => [min.value, max.value],
+                _ => [0.0, 0.0],
+            };
+            let format_peak = |label: &str, peak: Option<Peak>| match peak {
+                Some(peak) => format!(
+                    "{label} {} at {}",
+                    self.number_format.format(peak.value, range),
+                    timefmt::clock_time(timefmt::to_system_time(peak.time))
+                ),
+                None => format!("{label} -"),
+            };
+            let mut text = String::new();
+            if !series.name.is_empty() {
+                text.push_str(&format!("{}: ", series.name));
+            }
+            if !series.visible {
+                text.push_str("hidden");
+                spans.push(Span::styled(text, Style::default().fg(Color::DarkGray)));
+                spans.push(Span::raw("   "));
+                continue;
+            }
+            text.push_str(&format_peak("min", series.global_min));
+            text.push_str("  ");
+            text.push_str(&format_peak("max", series.global_max));
+            spans.push(Span::styled(text, Style::default().fg(series.color)));
+            spans.push(Span::raw("   "));
+        }
+        spans.pop();
+        Spans::from(spans)
+    }
+
+    /// The data of `series` that is within the last `window_len` samples.
+    pub fn visible_data<'a>(&self, series: &'a Series) -> &'a [(f64, f64)] {
+        let start = self.samples as f64 - self.window_len as f64;
+        let idx = series.data.partition_point(|(x, _)| *x < start);
+        &series.data[idx..]
+    }
+
+    /// The visible data of `series` as it's plotted.
+    fn plotted_data(&self, series: &Series) -> Vec<(f64, f64)> {
+        let data = self.visible_data(series);
+        let data = if self.log_y {
+            data.iter()
+                .filter(|(_, y)| *y > 0.0)
+                .map(|(x, y)| (*x, y.log10()))
+                .collect()
+        } else {
+            data.to_vec()
+        };
+        if self.steps {
+            steps(&data)
+        } else {
+            data
+        }
+    }
+
+    /// The rate of change of the first series within the window, averaged
+    /// over `derivative_smoothing` samples.
+    fn derivative_data(&self) -> Vec<(f64, f64)> {
+        let Some(series) = self.series.first() else {
+            return Vec::new();
+        };
+        let rates: Vec<(f64, f64)> = self
+            .visible_data(series)
+            .windows(2)
+            .map(|w| (w[1].0, (w[1].1 - w[0].1) / (w[1].0 - w[0].0)))
+            .collect();
+        let n = self.derivative_smoothing.max(1);
+        (0..rates.len())
+            .map(|i| {
+                let averaged = &rates[(i + 1).saturating_sub(n)..=i];
+                let sum: f64 = averaged.iter().map(|(_, rate)| rate).sum();
+                (rates[i].0, sum / averaged.len() as f64)
+            })
+            .collect()
+    }
+
+    /// Plots the rate of change of the first series with its own y axis.
+    fn render_derivative<B: Backend>(&self, f: &mut Frame<B>, area: Rect) {
+        let data = self.derivative_data();
+        let mut title = "rate of change".to_string();
+        if let Some(name) = self
+            .series
+            .first()
+            .map(|s| &s.name)
+            .filter(|n| !n.is_empty())
+        {
+            title.push_str(&format!(" of {name}"));
+        }
+        if self.derivative_smoothing > 1 {
+            title.push_str(&format!(", averaged over {}", self.derivative_smoothing));
+        }
+        let dataset = Dataset::default()
+            .marker(symbols::Marker::Braille)
+            .style(Style::default().fg(Color::LightMagenta))
+            .graph_type(GraphType::Line)
+            .data(&data);
+        let bounds = y_bounds(data.iter().map(|(_, y)| *y));
+        let chart = Chart::new(vec![dataset])
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .x_axis(Axis::default().bounds(self.window))
+            .y_axis(y_axis(bounds, |y, bounds| {
+                self.number_format.format(y, bounds)
+            }));
+        f.render_widget(chart, area);
+    }
+
+    /// The first shown series as a line of block characters for the status bar,
+    /// one for each of the latest `width` values in the window, and the latest value.
+    pub fn status_sparkline(&self, width: usize) -> Option<Span<'static>> {
+        let series = self.series.iter().find(|series| series.visible)?;
+        let data = self.visible_data(series);
+        let data = &data[data.len().saturating_sub(width)..];
+        let (_, latest) = data.last()?;
+        let values: Vec<f64> = data.iter().map(|(_, y)| *y).collect();
+        let mut text = series.name.clone();
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(&blocks(&values));
+        text.push(' ');
+        text.push_str(&self.number_format.format(*latest, [*latest, *latest]));
+        Some(Span::styled(text, Style::default().fg(series.color)))
+    }
+
+    /// Draws the first shown series as a sparkline, the columns of which are the
+    /// latest values, with the latest one in the title.
+    fn render_sparkline<B: Backend>(&self, f: &mut Frame<B>, area: Rect) {
+        let Some(series) = self.series.iter().find(|series| series.visible) else {
+            return;
+        };
+        let block = Block::default().borders(Borders::ALL);
+        let width = block.inner(area).width as usize;
+        let data = self.visible_data(series);
+        let data = &data[data.len().saturating_sub(width)..];
+        let mut title = series.name.clone();
+        if let Some((_, latest)) = data.last() {
+            if !title.is_empty() {
+                title.push_str(": ");
+            }
+            title.push_str(&self.number_format.format(*latest, [*latest, *latest]));
+        }
+        // Sparklines start at 0, so the lowest value gets the smallest bar
+        // that is still visible.
+        let bounds = y_bounds(data.iter().map(|(_, y)| *y));
+        let heights: Vec<u64> = data
+            .iter()
+            .map(|(_, y)| ((y - bounds[0]) / (bounds[1] - bounds[0]) * 1000.0) as u64 + 1)
+            .collect();
+        let sparkline = Sparkline::default()
+            .block(block.title(Span::styled(title, Style::default().fg(series.color))))
+            .style(Style::default().fg(series.color))
+            .max(1001)
+            .data(&heights);
+        f.render_widget(sparkline, area);
+    }
+
+    /// Draws every series as steps between fixed 0 and 1 bounds, stacked
+    /// on top of each other with the name of the channel on the left.
+    fn render_digital<B: Backend>(&self, f: &mut Frame<B>, area: Rect) {
+        let mut title = vec![Span::raw("digital")];
+        if let Some(rate) = self.rate_span() {
+            title.push(Span::raw("   "));
+            title.push(rate);
+        }
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(Spans::from(title));
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        let shown: Vec<&Series> = self.series.iter().filter(|series| series.visible).collect();
+        let names: Vec<String> = self
+            .series
+            .iter()
+            .enumerate()
+            .filter(|(_, series)| series.visible)
+            .map(|(i, series)| match series.name.as_str() {
+                "" => format!("ch {i}"),
+                name => name.to_string(),
+            })
+            .collect();
+        // The labels of every lane are as wide so that their charts line up.
+        let width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+        let lanes = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Ratio(1, shown.len() as u32); shown.len()])
+            .split(inner);
+        for ((series, name), lane) in shown.into_iter().zip(&names).zip(lanes) {
+            let data = steps(self.visible_data(series));
+            let dataset = Dataset::default()
+                .marker(symbols::Marker::Braille)
+                .style(Style::default().fg(series.color))
+                .graph_type(GraphType::Line)
+                .data(&data);
+            let y_axis = Axis::default().bounds([-0.25, 1.25]).labels(vec![
+                Span::raw(format!("{:>width$}", 0)),
+                Span::styled(format!("{name:>width$}"), Style::default().fg(series.color)),
+                Span::raw(format!("{:>width$}", 1)),
+            ]);
+            let chart = Chart::new(vec![dataset])
+                .x_axis(Axis::default().bounds(self.window))
+                .y_axis(y_axis);
+            f.render_widget(chart, lane);
+        }
+    }
+
+    pub fn render<B: Backend>(&self, f: &mut Frame<B>, area: Rect) {
+        if self.samples == 0 {
+            return self.render_waiting(f, area);
+        }
+        if self.digital {
+            return self.render_digital(f, area);
+        }
+        let sparkline = match self.style {
+            GraphStyle::Auto => area.height < SPARKLINE_BELOW,
+            GraphStyle::Chart => false,
+            GraphStyle::Sparkline => true,
+        };
+        if sparkline {
+            return self.render_sparkline(f, area);
+        }
+        let area = if self.derivative {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Percentage(65), Constraint::Percentage(35)])
+                .split(area);
+            self.render_derivative(f, chunks[1]);
+            chunks[0]
+        } else {
+            area
+        };
+        let graph_block = Block::default()
+            .borders(Borders::ALL)
+            .title(self.peaks_title());
+        let mut plotted: Vec<Vec<(f64, f64)>> = self
+            .series
+            .iter()
+            .map(|series| match series.visible {
+                true => self.plotted_data(series),
+                false => Vec::new(),
+            })
+            .collect();
+
+        let label = |y: f64, bounds: [f64; 2]| {
+            if self.log_y {
+                // The labels are decades apart, so each gets its own precision.
+                let value = 10f64.powf(y);
+                self.number_format.format(value, [value, value])
+            } else {
+                self.number_format.format(y, bounds)
+            }
+        };
+        let bounds = match &self.fit {
+            Some(fit) => fit.bounds,
+            None => y_bounds(std::iter::empty()),
+        };
+        let y2 = self.y2.filter(|idx| self.series[*idx].visible);
+        let y2_bounds = y2.map(|idx| match self.y2_bounds {
+            Some([min, max]) if self.log_y => [min.log10(), max.log10()],
+            Some(fixed) => fixed,
+            None => self.auto_bounds(plotted[idx].iter().map(|(_x, y)| *y).collect()),
+        });
+
+        let inner = graph_block.inner(area);
+        f.render_widget(graph_block, area);
+        let chart_area = match (y2, y2_bounds) {
+            (Some(idx), Some(y2_bounds)) => {
+                // The series is drawn against the left axis, scaled so that
+                // its bounds line up with the left ones.
+                let [min2, max2] = y2_bounds;
+                let scale = (bounds[1] - bounds[0]) / (max2 - min2);
+                for point in &mut plotted[idx] {
+                    point.1 = bounds[0] + (point.1 - min2) * scale;
+                }
+                self.render_y2_labels(f, inner, idx, y2_bounds, label)
+            }
+            _ => inner,
+        };
+        // Lines to points outside of the bounds aren't drawn at all, so the
+        // points left out by the percentile are drawn at the edge instead.
+        for point in plotted.iter_mut().flatten() {
+            point.1 = point.1.clamp(bounds[0], bounds[1]);
+        }
+
+        let datasets = self
+            .series
+            .iter()
+            .enumerate()
+            .zip(&plotted)
+            .map(|((i, series), data)| {
+                let name = if series.name.is_empty() {
+                    String::new()
+                } else if !series.visible {
+                    format!("{} (hidden)", series.name)
+                } else if Some(i) == self.y2 {
+                    format!("{} (right)", series.name)
+                } else {
+                    series.name.clone()
+                };
+                let color = if series.visible {
+                    series.color
+                } else {
+                    Color::DarkGray
+                };
+                Dataset::default()
+                    .name(name)
+                    .marker(symbols::Marker::Braille)
+                    .style(Style::default().fg(color))
+                    .graph_type(GraphType::Line)
+                    .data(data)
+            })
+            .collect();
+
+        let mut y_axis = y_axis(bounds, label);
+        if self.log_y {
+            y_axis = y_axis.title("log scale");
+        }
+
+        let chart = Chart::new(datasets)
+            .hidden_legend_constraints((Constraint::Ratio(1, 2), Constraint::Ratio(3, 4)))
+            .x_axis(Axis::default().bounds(self.window).title("X axis"))
+            .y_axis(y_axis);
+        f.render_widget(chart, chart_area);
+    }
+
+    /// Draws a note instead of axes before there's a sample, since an empty
+    /// chart looks like a flat line at some arbitrary scale.
+    fn render_waiting<B: Backend>(&self, f: &mut Frame<B>, area: Rect) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(self.peaks_title());
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        let note = Paragraph::new(Span::styled(
+            "waiting for data…",
+            Style::default().fg(Color::DarkGray),
+        ))
+        .alignment(tui::layout::Alignment::Center);
+        let row = Rect::new(
+            inner.x,
+            inner.y + inner.height / 2,
+            inner.width,
+            inner.height.min(1),
+        );
+        f.render_widget(note, row);
+    }
+
+    /// Draws the labels of the right y axis along the right edge of `area`,
+    /// in the color of the series, and returns what's left for the chart.
+    fn render_y2_labels<B: Backend>(
+        &self,
+        f: &mut Frame<B>,
+        area: Rect,
+        idx: usize,
+        bounds: [f64; 2],
+        label: impl Fn(f64, [f64; 2]) -> String,
+    ) -> Rect {
+        let [min, max] = bounds;
+        let labels = [
+            label(max, bounds),
+            label((min + max) / 2.0, bounds),
+            label(min, bounds),
+        ];
+        let width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0) as u16 + 1;
+        if area.width <= width || area.height < 3 {
+            return area;
+        }
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(0), Constraint::Length(width)])
+            .split(area);
+        // The right axis spans the whole height like the left one, since the
+        // x axis has no labels.
+        let style = Style::default().fg(self.series[idx].color);
+        let mut rows = vec![Spans::default(); area.height as usize];
+        let last = rows.len() - 1;
+        let [top, middle, bottom] = labels;
+        rows[0] = Spans::from(Span::styled(top, style.add_modifier(Modifier::BOLD)));
+        rows[last / 2] = Spans::from(Span::styled(middle, style));
+        rows[last] = Spans::from(Span::styled(bottom, style.add_modifier(Modifier::BOLD)));
+        f.render_widget(
+            Paragraph::new(rows).alignment(tui::layout::Alignment::Right),
+            chunks[1],
+        );
+        chunks[0]
+    }
+}
+
+/// Formats an interval between samples, e.g. "18.1ms" or "2.50s".
+fn gap(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+    } else {
+        format!("{:.2}s", duration.as_secs_f64())
+    }
+}
+
+/// The bounds of a y axis showing `values`, with some room around them.
+fn y_bounds(values: impl Iterator<Item = f64> + Clone) -> [f64; 2] {
+    let min = values
+        .clone()
+        .min_by_key(|y| OrderedFloat(*y))
+        .unwrap_or(-1.0);
+    let max = values.max_by_key(|y| OrderedFloat(*y)).unwrap_or(1.0);
+    let size = max - min;
+    let min = min - 0.1 * size - 0.001 * max.abs().max(min.abs());
+    let max = max + 0.1 * size + 0.001 * max.abs().max(min.abs());
+    [min, max]
+}
+
+/// A block for each value, as high as the value is between the lowest and the
+/// highest of them.
+fn blocks(values: &[f64]) -> String {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    values
+        .iter()
+        .map(|y| match max > min {
+            true => BLOCKS[((y - min) / (max - min) * 7.0).round() as usize],
+            false => BLOCKS[0],
+        })
+        .collect()
+}
+
+/// Widens `bounds` to multiples of a round step, 1, 2 or 5 times a power of
+/// ten, so that the labels are round numbers too.
+fn round_bounds(bounds: [f64; 2]) -> [f64; 2] {
+    let [min, max] = bounds;
+    if max <= min || !(max - min).is_finite() {
+        return bounds;
+    }
+    let rough = (max - min) / 10.0;
+    let magnitude = 10f64.powf(rough.log10().floor());
+    let step = match rough / magnitude {
+        r if r <= 1.0 => 1.0,
+        r if r <= 2.0 => 2.0,
+        r if r <= 5.0 => 5.0,
+        _ => 10.0,
+    } * magnitude;
+    [(min / step).floor() * step, (max / step).ceil() * step]
+}
+
+/// A y axis labeled at the bounds and in the middle.
+fn y_axis<'a>(bounds: [f64; 2], label: impl Fn(f64, [f64; 2]) -> String) -> Axis<'a> {
+    let [min, max] = bounds;
+    let mean = (max + min) / 2.0;
+    Axis::default().bounds(bounds).labels(vec![
+        Span::styled(
+            label(min, bounds),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(label(mean, bounds)),
+        Span::styled(
+            label(max, bounds),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+    ])
+}
+
+/// Expands `data` so that every value is held until the next sample.
+pub fn steps(data: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut stepped = Vec::with_capacity(data.len() * 2);
+    for (i, &(x, y)) in data.iter().enumerate() {
+        if i > 0 {
+            stepped.push((x, data[i - 1].1));
+        }
+        stepped.push((x, y));
+    }
+    stepped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_values() {
+        let extractor = Extractor::first_hex_number();
+        let values: Vec<Option<f64>> = ["adc 0x03FF", "add 03ff", "reg=-0X1f ok", "ff", "none"]
+            .iter()
+            .map(|line| extractor.extract(line))
+            .collect();
+        assert_eq!(
+            values,
+            [Some(1023.0), Some(1023.0), Some(-31.0), None, None]
+        );
+
+        let mut digital = Extractor::first_hex_number();
+        digital.digital = true;
+        assert_eq!(digital.extract("pin 0x0"), Some(0.0));
+        assert_eq!(digital.extract("pin 0x10"), Some(1.0));
+        // Decimal is the default, which doesn't take hex.
+        let decimal = Extractor::series(String::new(), Regex::new("v=(\\S+)").unwrap(), 0);
+        assert_eq!(decimal.extract("v=0x10"), None);
+        assert_eq!(decimal.extract("v=10"), Some(10.0));
+    }
+
+    #[test]
+    fn symmetric_bounds_are_centered_on_zero() {
+        let mut grapher = Grapher::new(vec![Extractor::first_number()], 60);
+        let [min, max] = grapher.auto_bounds(vec![2.0, 10.0]);
+        assert!(min > 0.0 && max > 10.0);
+        grapher.symmetric = true;
+        assert_eq!(grapher.auto_bounds(vec![2.0, 10.0]), [-max, max]);
+        let [min, _] = y_bounds([-20.0, 5.0].into_iter());
+        assert_eq!(grapher.auto_bounds(vec![-20.0, 5.0]), [min, -min]);
+        // A logarithmic axis can't show zero, so it isn't centered.
+        grapher.log_y = true;
+        assert!(grapher.auto_bounds(vec![2.0, 10.0])[0] > 0.0);
+    }
+
+    #[test]
+    fn old_samples_are_dropped() {
+        let mut grapher = Grapher::new(vec![Extractor::first_number()], 60);
+        for i in 0..2 * KEPT_SAMPLES {
+            grapher.add_values(&grapher.extract(&i.to_string()));
+        }
+        let series = &grapher.series[0];
+        assert_eq!(series.data.len(), KEPT_SAMPLES);
+        assert_eq!(series.data[0], (KEPT_SAMPLES as f64, KEPT_SAMPLES as f64));
+        assert_eq!(grapher.kept_samples(), KEPT_SAMPLES..2 * KEPT_SAMPLES);
+        assert_eq!(grapher.visible_data(series).len(), 60);
+        let last = grapher.kept_samples().end - 1;
+        assert!(grapher.sample_time(last) >= grapher.sample_time(KEPT_SAMPLES));
+        assert_eq!(grapher.value(series, last), Some(last as f64));
+    }
+
+    #[test]
+    fn status_sparkline_of_the_latest_values() {
+        assert_eq!(blocks(&[0.0, 1.0, 3.5, 7.0, 6.0]), "▁▂▅█▇");
+        assert_eq!(blocks(&[2.0, 2.0]), "▁▁");
+        let mut grapher = Grapher::new(vec![Extractor::first_number()], 60);
+        assert_eq!(grapher.status_sparkline(3), None);
+        for line in ["1", "2", "10", "4"] {
+            grapher.add_values(&grapher.extract(line));
+        }
+        let sparkline = grapher.status_sparkline(3).unwrap();
+        assert_eq!(sparkline.content, "▁█▃ 4.000");
+    }
+}
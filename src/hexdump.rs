@@ -0,0 +1,216 @@
+use tui::style::Style;
+
+use crate::{filter::FilterSet, wraptext::Line};
+
+/// The number of received bytes kept for the hex view. The oldest quarter is
+/// dropped when there are more.
+pub const KEPT_BYTES: usize = 256 * 1024;
+
+/// The number of bytes shown on every row unless the pane is too narrow.
+pub const WIDE_ROW: usize = 16;
+/// The number of bytes per row in narrow panes.
+pub const NARROW_ROW: usize = 8;
+
+/// The number of characters a row with `bytes_per_row` bytes takes up.
+fn row_width(bytes_per_row: usize) -> usize {
+    // Offset, hex bytes with an extra space between the halves, and the ASCII rendering.
+    8 + 2 + bytes_per_row * 3 + 1 + 1 + bytes_per_row + 2
+}
+
+/// The number of bytes per row that fit in `width` columns.
+pub fn bytes_per_row(width: usize) -> usize {
+    if width >= row_width(WIDE_ROW) {
+        WIDE_ROW
+    } else {
+        NARROW_ROW
+    }
+}
+
+/// Formats the bytes in `chunk`, the first of which is at `offset`, as a row
+/// of offset, hex bytes and the printable ASCII characters, e.g.
+///
+/// ```text
+/// 00000000  68 65 6C 6C 6F 0A 00 01  02 03 41 42 43 44 45 46  |hello.....ABCDEF|
+/// ```
+fn row(offset: usize, chunk: &[u8], bytes_per_row: usize) -> String {
+    let mut row = format!("{offset:08X} ");
+    for j in 0..bytes_per_row {
+        if j % 8 == 0 {
+            row.push(' ');
+        }
+        match chunk.get(j) {
+            Some(byte) => row.push_str(&format!("{byte:02X} ")),
+            None => row.push_str("   "),
+        }
+    }
+    row.push(' ');
+    row.push('|');
+    row.extend(chunk.iter().map(|&byte| ascii(byte)));
+    row.push('|');
+    row
+}
+
+/// The latest received bytes and their rows. The rows are only formatted
+/// for the bytes that arrived since they were last updated.
+pub struct Dump {
+    bytes: Vec<u8>,
+    /// The offset of the first byte that's kept.
+    start: usize,
+    bytes_per_row: usize,
+    pub rows: Vec<Line>,
+    /// The indices of the rows that match the filters.
+    pub filtered: Vec<usize>,
+}
+
+impl Dump {
+    pub fn new() -> Self {
+        Dump {
+            bytes: Vec::new(),
+            start: 0,
+            bytes_per_row: WIDE_ROW,
+            rows: Vec::new(),
+            filtered: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, byte: u8) {
+        self.bytes.push(byte);
+        if self.bytes.len() <= KEPT_BYTES {
+            return;
+        }
+        // A whole number of rows of either width, so the rows left still
+        // start where they did.
+        let dropped = KEPT_BYTES / 4;
+        self.bytes.drain(..dropped);
+        self.start += dropped;
+        let rows = (dropped / self.bytes_per_row).min(self.rows.len());
+        self.rows.drain(..rows);
+        self.filtered.retain(|&idx| idx >= rows);
+        for idx in &mut self.filtered {
+            *idx -= rows;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Dump::new();
+    }
+
+    /// Formats the rows again the next time they're updated, like after
+    /// the filters changed.
+    pub fn reformat(&mut self) {
+        self.rows.clear();
+        self.filtered.clear();
+    }
+
+    /// Adds the rows of the bytes received since the last update, and
+    /// formats the last row again since it might have been partial. All
+    /// of them are formatted again if `bytes_per_row` changed.
+    pub fn update(&mut self, bytes_per_row: usize, filters: &FilterSet) {
+        if bytes_per_row != self.bytes_per_row {
+            self.bytes_per_row = bytes_per_row;
+            self.reformat();
+        }
+        let from = self.rows.len().saturating_sub(1);
+        self.rows.truncate(from);
+        if self.filtered.last().is_some_and(|&idx| idx >= from) {
+            self.filtered.pop();
+        }
+        let chunks = self.bytes[from * bytes_per_row..].chunks(bytes_per_row);
+        for (idx, chunk) in (from..).zip(chunks) {
+            let row = row(self.start + idx * bytes_per_row, chunk, bytes_per_row);
+            if !filters.is_empty() && filters.matches(&row) {
+                self.filtered.push(idx);
+            }
+            self.rows.push(Line::new(row, Style::default()));
+        }
+    }
+}
+
+/// The character shown for `byte` in the ASCII column.
+fn ascii(byte: u8) -> char {
+    if byte.is_ascii_graphic() || byte == b' ' {
+        byte as char
+    } else {
+        '.'
+    }
+}
+
+/// Whether `c` may be typed when entering bytes as hex, like "DE AD,BE:EF".
+pub fn is_input_char(c: char) -> bool {
+    c.is_ascii_hexdigit() || c.is_whitespace() || matches!(c, ',' | ':' | '-')
+}
+
+/// Parses bytes entered as hex. Everything but the digits is a separator, and
+/// the digits pair up into bytes regardless of the separators.
+pub fn parse(text: &str) -> anyhow::Result<Vec<u8>> {
+    let digits: Vec<u8> = text
+        .chars()
+        .filter_map(|c| c.to_digit(16))
+        .map(|d| d as u8)
+        .collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err(anyhow::anyhow!(
+            "odd number of hex digits ({})",
+            digits.len()
+        ));
+    }
+    Ok(digits
+        .chunks(2)
+        .map(|pair| pair[0] << 4 | pair[1])
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(dump: &Dump) -> Vec<&str> {
+        dump.rows.iter().map(|row| row.text.as_str()).collect()
+    }
+
+    #[test]
+    fn rows_are_added_as_bytes_arrive() {
+        let mut dump = Dump::new();
+        let filters = FilterSet::new();
+        b"hello".iter().for_each(|&byte| dump.push(byte));
+        dump.update(NARROW_ROW, &filters);
+        assert_eq!(texts(&dump), ["00000000  68 65 6C 6C 6F           |hello|"]);
+        b"ABCDEF".iter().for_each(|&byte| dump.push(byte));
+        dump.update(NARROW_ROW, &filters);
+        assert_eq!(
+            texts(&dump),
+            [
+                "00000000  68 65 6C 6C 6F 41 42 43  |helloABC|",
+                "00000008  44 45 46                 |DEF|",
+            ]
+        );
+        // A wider pane formats them all again.
+        dump.update(WIDE_ROW, &filters);
+        assert_eq!(dump.rows.len(), 1);
+        assert!(texts(&dump)[0].ends_with("|helloABCDEF|"));
+    }
+
+    #[test]
+    fn filtered_rows_and_dropped_bytes() {
+        let mut dump = Dump::new();
+        let mut filters = FilterSet::new();
+        filters.patterns.push("|AAAA".to_string());
+        let bytes = (0..KEPT_BYTES).map(|i| if i % 32 < 16 { b'A' } else { b'B' });
+        bytes.for_each(|byte| dump.push(byte));
+        dump.update(WIDE_ROW, &filters);
+        assert_eq!(dump.rows.len(), KEPT_BYTES / WIDE_ROW);
+        assert_eq!(dump.filtered[..3], [0, 2, 4]);
+
+        // The oldest quarter is dropped and the rows left keep their offsets.
+        b"BBBB".iter().for_each(|&byte| dump.push(byte));
+        dump.update(WIDE_ROW, &filters);
+        assert_eq!(dump.rows.len(), KEPT_BYTES * 3 / 4 / WIDE_ROW + 1);
+        assert!(texts(&dump)[0].starts_with("00010000  41 41 "));
+        assert!(texts(&dump)
+            .last()
+            .unwrap()
+            .starts_with("00040000  42 42 42 42 "));
+        assert_eq!(dump.filtered[..3], [0, 2, 4]);
+        assert_eq!(dump.filtered.len(), KEPT_BYTES * 3 / 4 / WIDE_ROW / 2);
+    }
+}
@@ -0,0 +1,67 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Which way a logged line went.
+#[derive(Clone, Copy)]
+pub enum Direction {
+    Rx,
+    Tx,
+}
+
+/// Logs sent and received lines as one JSON object per line, e.g.
+/// `{"t":1718000000.123,"dir":"rx","line":"OK"}`, for other tools to read.
+pub struct JsonlLog {
+    file: BufWriter<File>,
+}
+
+impl JsonlLog {
+    pub fn create(path: &Path) -> io::Result<JsonlLog> {
+        Ok(JsonlLog {
+            file: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    pub fn write(&mut self, dir: Direction, time: SystemTime, line: &str) -> io::Result<()> {
+        writeln!(self.file, "{}", object(dir, time, line))?;
+        self.file.flush()
+    }
+}
+
+/// The JSON object of a line, without a newline.
+pub fn object(dir: Direction, time: SystemTime, line: &str) -> String {
+    let t = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs_f64();
+    let dir = match dir {
+        Direction::Rx => "rx",
+        Direction::Tx => "tx",
+    };
+    format!(
+        "{{\"t\":{t:.3},\"dir\":\"{dir}\",\"line\":{}}}",
+        quote(line)
+    )
+}
+
+/// `s` as a JSON string, with quotes, backslashes and control characters escaped.
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c < ' ' || c == '\u{7f}' => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,286 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::{FileTypeExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::Context;
+use nix::libc;
+
+use crate::timefmt;
+
+/// The most bytes held back for a FIFO whose reader doesn't keep up.
+const FIFO_BUFFER_LIMIT: usize = 1 << 20;
+/// How often opening a FIFO without a reader is retried.
+const FIFO_RETRY_INTERVAL: Duration = Duration::from_millis(500);
+/// Written to a FIFO where lines have been dropped.
+const FIFO_DATA_LOSS: &[u8] = "⚠ possible data loss (fifo full)\n".as_bytes();
+/// Given to the next reader of a FIFO in place of the rest of a line the
+/// previous reader only got the start of.
+const FIFO_READER_LOST: &[u8] = "⚠ possible data loss (fifo reader gone)\n".as_bytes();
+
+/// Where the log is written.
+pub enum LogSink {
+    File(File),
+    /// A named pipe read by another program. Writing to it never blocks.
+    Fifo(Fifo),
+}
+
+pub struct Fifo {
+    path: PathBuf,
+    /// Open while there's a reader.
+    file: Option<File>,
+    last_open_attempt: Option<Instant>,
+    /// What the reader hasn't gotten yet. Only complete lines are passed on.
+    buffer: Vec<u8>,
+    /// The rest of the current line is dropped since the buffer was full.
+    dropping: bool,
+    /// The reader has gotten the start of the first buffered line, since
+    /// writes to a FIFO can be partial.
+    partial: bool,
+}
+
+impl Fifo {
+    fn new(path: PathBuf) -> Self {
+        let mut fifo = Fifo {
+            path,
+            file: None,
+            last_open_attempt: None,
+            buffer: Vec::new(),
+            dropping: false,
+            partial: false,
+        };
+        fifo.drain();
+        fifo
+    }
+
+    /// Writes as many of the buffered lines as the reader takes right now.
+    fn drain(&mut self) {
+        if self.file.is_none() {
+            if self
+                .last_open_attempt
+                .is_some_and(|t| t.elapsed() < FIFO_RETRY_INTERVAL)
+            {
+                return;
+            }
+            self.last_open_attempt = Some(Instant::now());
+            // Fails as long as there's no reader instead of waiting for one.
+            self.file = OpenOptions::new()
+                .write(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(&self.path)
+                .ok();
+        }
+        let Some(file) = &mut self.file else {
+            return;
+        };
+        let complete = self
+            .buffer
+            .iter()
+            .rposition(|b| *b == b'\n')
+            .map_or(0, |i| i + 1);
+        let mut written = 0;
+        let mut lost = false;
+        while written < complete {
+            match file.write(&self.buffer[written..complete]) {
+                Ok(0) => break,
+                Ok(n) => written += n,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                // The reader went away, wait for the next one.
+                Err(_) => {
+                    self.file = None;
+                    lost = true;
+                    break;
+                }
+            }
+        }
+        if written > 0 {
+            self.partial = self.buffer[written - 1] != b'\n';
+        }
+        self.buffer.drain(..written);
+        if lost && self.partial {
+            // The next reader mustn't start in the middle of a line.
+            let rest = self.buffer.iter().position(|b| *b == b'\n').unwrap() + 1;
+            self.buffer.splice(..rest, FIFO_READER_LOST.iter().copied());
+            self.partial = false;
+        }
+    }
+}
+
+impl Write for Fifo {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.drain();
+        for &byte in buf {
+            if self.dropping {
+                if byte == b'\n' && self.buffer.len() + FIFO_DATA_LOSS.len() <= FIFO_BUFFER_LIMIT {
+                    self.buffer.extend_from_slice(FIFO_DATA_LOSS);
+                    self.dropping = false;
+                }
+                continue;
+            }
+            if self.buffer.len() >= FIFO_BUFFER_LIMIT {
+                // The start of the line goes as well so that the reader only gets whole lines.
+                let complete = self
+                    .buffer
+                    .iter()
+                    .rposition(|b| *b == b'\n')
+                    .map_or(0, |i| i + 1);
+                self.buffer.truncate(complete);
+                if self.buffer.is_empty() && self.partial {
+                    // Ends the line the reader has the start of.
+                    self.buffer.push(b'\n');
+                }
+                self.dropping = true;
+                continue;
+            }
+            self.buffer.push(byte);
+        }
+        self.drain();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.drain();
+        Ok(())
+    }
+}
+
+impl Write for LogSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            LogSink::File(file) => file.write(buf),
+            LogSink::Fifo(fifo) => fifo.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            LogSink::File(file) => file.flush(),
+            LogSink::Fifo(fifo) => fifo.flush(),
+        }
+    }
+}
+
+/// Opens the log file given with `-o`. A path ending in '/', or naming an
+/// existing directory, gets a file named after the device and the current
+/// time in it. A path containing '%' is expanded like strftime. Missing
+/// parent directories are created. An existing FIFO is written to without
+/// ever blocking.
+pub fn open(out: &str, device: Option<&Path>) -> anyhow::Result<(LogSink, PathBuf)> {
+    let now = SystemTime::now();
+    let (path, generated) = if out.ends_with('/') || Path::new(out).is_dir() {
+        let mut name = "rterm".to_string();
+        if let Some(device) = device.and_then(|d| d.file_name()) {
+            name.push('-');
+            name.push_str(&device.to_string_lossy());
+        }
+        let stamp = timefmt::format_local("%Y%m%d-%H%M%S", now);
+        (Path::new(out).join(format!("{name}-{stamp}.txt")), true)
+    } else if out.contains('%') {
+        (PathBuf::from(timefmt::format_local(out, now)), true)
+    } else {
+        (PathBuf::from(out), false)
+    };
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("creating the directory '{}'", parent.display()))?;
+    }
+    if fs::metadata(&path).is_ok_and(|m| m.file_type().is_fifo()) {
+        return Ok((LogSink::Fifo(Fifo::new(path.clone())), path));
+    }
+    if !generated {
+        let file = File::create(&path).with_context(|| format!("opening '{}'", path.display()))?;
+        return Ok((LogSink::File(file), path));
+    }
+    // Another session started within the same second must not be overwritten.
+    for n in 0.. {
+        let candidate = if n == 0 {
+            path.clone()
+        } else {
+            numbered(&path, n)
+        };
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&candidate)
+        {
+            Ok(file) => return Ok((LogSink::File(file), candidate)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e).with_context(|| format!("opening '{}'", candidate.display())),
+        }
+    }
+    unreachable!()
+}
+
+/// Opens a segment of a log that's split into one file per burst of data,
+/// like `open` but a plain file name gets the time added in front of the
+/// extension, e.g. "capture-20240611-093000.txt", so that every segment has
+/// a file of its own.
+pub fn open_segment(out: &str, device: Option<&Path>) -> anyhow::Result<(LogSink, PathBuf)> {
+    let path = Path::new(out);
+    if out.ends_with('/')
+        || out.contains('%')
+        || fs::metadata(path).is_ok_and(|m| m.is_dir() || m.file_type().is_fifo())
+    {
+        return open(out, device);
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}-%Y%m%d-%H%M%S.{}", ext.to_string_lossy()),
+        None => format!("{stem}-%Y%m%d-%H%M%S"),
+    };
+    open(&path.with_file_name(name).to_string_lossy(), device)
+}
+
+/// `path` with "-n" added in front of the extension, e.g. "log-2.txt".
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}-{n}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{n}"),
+    };
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::CString, io::Read, os::unix::ffi::OsStrExt, thread};
+
+    use super::*;
+
+    #[test]
+    fn the_next_fifo_reader_only_gets_whole_lines() {
+        let path = std::env::temp_dir().join(format!("rterm-fifo-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+        // Safety: mkfifo only reads the path it's given.
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        let open_reader = || {
+            OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(&path)
+                .unwrap()
+        };
+        let reader = open_reader();
+        let (mut sink, _) = open(&path.to_string_lossy(), None).unwrap();
+        // More than the pipe takes, so only its start is written.
+        let mut long = vec![b'a'; 256 * 1024];
+        long.push(b'\n');
+        sink.write_all(&long).unwrap();
+        drop(reader);
+
+        sink.write_all(b"next\n").unwrap();
+        let mut reader = open_reader();
+        thread::sleep(FIFO_RETRY_INTERVAL);
+        sink.flush().unwrap();
+        let mut read = [0; 256];
+        let n = reader.read(&mut read).unwrap();
+        let expected = "⚠ possible data loss (fifo reader gone)\nnext\n";
+        assert_eq!(String::from_utf8_lossy(&read[..n]), expected);
+        fs::remove_file(path).unwrap();
+    }
+}
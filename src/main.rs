@@ -1,11 +1,43 @@
+mod alert;
 mod app;
+mod benchmark;
+mod binary;
+mod clipboard;
+mod command;
+mod completion;
+mod config;
+mod csv;
+mod decoder;
+mod discovery;
+mod filter;
+mod gauge;
+mod graph;
+mod hexdump;
+mod jsonl;
+mod logfile;
+mod nmea;
+mod numfmt;
+mod picker;
+mod pipe;
+mod route;
+mod stats;
+mod svg;
 mod termdev;
+mod timefmt;
+mod trigger;
+mod udp;
+mod vim;
+mod vt100;
 mod wraptext;
 
 use std::{
-    io::Stdout,
+    fs::OpenOptions,
+    io::{IsTerminal, Write},
+    net::SocketAddr,
     panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
     sync::Mutex,
+    time::Duration,
 };
 
 use anyhow::{anyhow, Context};
@@ -15,12 +47,32 @@ use crossterm::{
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use discovery::Watcher;
 use nix::sys::termios::BaudRate;
 use regex::Regex;
-use termdev::TerminalDevice;
+use termdev::{Mode, OpenFlags, SerialConfig, TerminalDevice};
 use tui::{backend::CrosstermBackend, Terminal};
 
-use crate::app::Grapher;
+use crate::{
+    alert::Alerts,
+    app::{ColorChoice, EventLoop, ThresholdColor, TxSettings},
+    binary::{FrameDecoder, SampleFormat},
+    completion::Completer,
+    config::Config,
+    csv::CsvStream,
+    decoder::{ControlMode, TxEncoding},
+    filter::Combine,
+    graph::{Extractor, GraphStyle, Grapher, Radix},
+    jsonl::JsonlLog,
+    numfmt::NumberFormat,
+    pipe::{Pipe, Tap},
+    route::{Route, Routes},
+    stats::StatsFile,
+    trigger::{Rule, Triggers},
+    udp::UdpForward,
+    vt100::Screen,
+    wraptext::{LongWords, Timestamps, Wrap, Wrapping},
+};
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about=None)]
@@ -31,29 +83,591 @@ struct Cli {
     #[clap(short, long)]
     terminal_device: Option<String>,
 
+    /// The file to log the received data to. A directory, e.g. "logs/", gets a
+    /// file named after the device and the time, and strftime sequences like
+    /// "%d" are expanded.
     #[clap(short, long)]
     out_file: Option<String>,
 
+    /// Start a new out file whenever nothing has been received for this many
+    /// milliseconds, so that every burst of data gets a file of its own. The
+    /// files are named by the time they're started at.
+    #[clap(long, requires = "out-file")]
+    log_split_idle_ms: Option<u64>,
+
+    /// Also append the received lines matching a regex to a file of their own, given as
+    /// "regex=path", e.g. '^\[WIFI\]=wifi.txt'. The path is like the out file's, and a line
+    /// goes to every route it matches. Can be given more than once.
+    #[clap(long, multiple_occurrences = true, parse(try_from_str = Route::parse))]
+    route: Vec<Route>,
+
+    /// Write the received bytes to the out file exactly as they are, e.g. keeping CRs.
+    #[clap(long, requires = "out-file", conflicts_with = "dedup-log")]
+    raw_log: bool,
+
+    /// Write the sent lines to the out file as well, prefixed by --log-tx-prefix and
+    /// stamped with the time they were sent when timestamps are shown. A raw log only
+    /// gets what was received.
+    #[clap(long, requires = "out-file")]
+    log_tx: bool,
+
+    #[clap(long, default_value = ">> ", requires = "log-tx")]
+    log_tx_prefix: String,
+
+    /// Also log every sent and received line to this file as JSON, one object per
+    /// line like {"t":1718000000.123,"dir":"rx","line":"OK"}.
+    #[clap(long)]
+    jsonl: Option<PathBuf>,
+
+    /// Append a CSV row of statistics to this file every --stats-interval: the RX and TX
+    /// totals, the number of lines, reconnects, errors and stripped bytes, the number of
+    /// lines of every --route, and the minimum, maximum and mean of every series of the
+    /// graph over the interval.
+    #[clap(long)]
+    stats_file: Option<PathBuf>,
+
+    /// How often a row is added to the --stats-file, e.g. "60" seconds or "5m".
+    #[clap(long, default_value = "60", requires = "stats-file", parse(try_from_str = parse_secs))]
+    stats_interval: Duration,
+
     #[clap(short, long)]
     graph: bool,
 
     #[clap(long, default_value_t = 60)]
     graph_len: usize,
+
+    /// Draw the graph as steps holding each value until the next one, instead of lines between them.
+    #[clap(long)]
+    graph_steps: bool,
+
+    /// Plot the graph on a logarithmic y axis, leaving out values that aren't positive. Toggled with :logy.
+    #[clap(long)]
+    graph_log_y: bool,
+
+    /// Plot the rate of change of the first graphed value below the graph.
+    #[clap(long)]
+    graph_derivative: bool,
+
+    /// Average the plotted rate of change over this many samples, since it amplifies noise.
+    #[clap(long, default_value_t = 1, requires = "graph-derivative")]
+    graph_derivative_smoothing: usize,
+
+    /// Shrink the y axis of the graph only once the values have fit in narrower bounds for
+    /// this many samples, so that it doesn't change with every small fluctuation. Alt+a
+    /// fits it right away.
+    #[clap(long, default_value_t = 30)]
+    graph_shrink_after: usize,
+
+    /// Plot the values as digital signals, stacking a square wave between 0 and 1 for every
+    /// extractor like a logic analyzer. Besides numbers, where anything but 0 is high, the
+    /// levels can be high/low, on/off or true/false. The lanes are named after the extractors.
+    #[clap(long)]
+    graph_digital: bool,
+
+    /// How the graphed numbers are written. The default extractor then takes
+    /// the first hex number, e.g. "0x03FF" or "03FF". Extractors in the config
+    /// file can set their own radix.
+    #[clap(long, arg_enum)]
+    graph_radix: Option<Radix>,
+
+    /// Graph samples decoded from the received bytes, which are in this format, instead of
+    /// values in lines of text. The hex view (:hex) shows the bytes.
+    #[clap(long, arg_enum)]
+    graph_binary: Option<SampleFormat>,
+
+    /// A named series of the graph as 'name=regex', where the regex has one capture group
+    /// around the value, e.g. 'temp=T: (-?[\d.]+)'. Can be given several times, every line
+    /// is tried against all of them and lines that match none aren't graphed.
+    #[clap(long, multiple_occurrences = true, parse(try_from_str = parse_series), conflicts_with = "graph-binary")]
+    series: Vec<(String, Regex)>,
+
+    /// The number of interleaved channels in the binary stream. A frame has one sample of
+    /// every channel.
+    #[clap(long, default_value_t = 1, requires = "graph-binary")]
+    binary_channels: usize,
+
+    /// Hex bytes every binary frame starts with, e.g. "aa55". Bytes are skipped until it's
+    /// found again when a frame doesn't start with it. Without it the frames are aligned to
+    /// where the stream started and a dropped byte shifts the samples after it.
+    #[clap(long, requires = "graph-binary")]
+    frame_sync: Option<String>,
+
+    /// Draw the graph as a chart with axes, or as a sparkline of the first value that
+    /// fits in a few rows. Auto switches to the sparkline when the graph is too low.
+    #[clap(long, arg_enum, default_value = "auto")]
+    graph_style: GraphStyle,
+
+    /// Show the graph as a one-line sparkline of the first value in the status bar
+    /// instead of a pane, leaving all the rows to the output. Ctrl+g toggles it.
+    #[clap(long)]
+    graph_sparkline: bool,
+
+    /// Show the latest value of the first series in big characters with an arrow of
+    /// where it's heading, next to the graph or below the output. Alt+g toggles it.
+    #[clap(long)]
+    gauge: bool,
+
+    /// The unit shown after the value of the gauge, e.g. "°C".
+    #[clap(long, default_value = "")]
+    gauge_unit: String,
+
+    /// Plot this series, given by its name or index, against a y axis of its own on the
+    /// right, for values on a different scale than the others.
+    #[clap(long)]
+    y2: Option<String>,
+
+    /// Fixed bounds of the right y axis, e.g. "0,4095". Found from the values otherwise.
+    #[clap(long, requires = "y2", parse(try_from_str = parse_bounds))]
+    y2_bounds: Option<[f64; 2]>,
+
+    /// Fit the y axes to the given percentile of the visible values, e.g. 99, and the one as
+    /// far from the bottom, so that a few outliers don't flatten the rest. The values outside
+    /// are drawn at the edge of the graph, but are still exported.
+    #[clap(long, parse(try_from_str = parse_percentile))]
+    y_percentile: Option<f64>,
+
+    /// Fit the y axes of the graph to bounds as far below zero as above it, for signals
+    /// that swing both ways like audio or signed deltas.
+    #[clap(long)]
+    graph_symmetric: bool,
+
+    /// How the labels of the graph and its lowest and highest values are formatted: auto,
+    /// a number of decimals, e.g. "3", or printf-style, e.g. "%.2e" or "%8.3f V". Auto
+    /// picks the decimals from the range shown and uses SI prefixes like "1.52M" or "123µ".
+    #[clap(long, default_value = "auto", parse(try_from_str = NumberFormat::parse))]
+    y_format: NumberFormat,
+
+    /// Highlight the values the graph extracts in the output. Toggled with :highlight.
+    #[clap(long)]
+    highlight_values: bool,
+
+    /// Show lines in yellow when a value the graph extracts from them is above this, unless
+    /// the extractor sets its own warn threshold in the config file. Lines within the
+    /// thresholds are green.
+    #[clap(long)]
+    warn_above: Option<f64>,
+
+    /// Show lines in red when a value the graph extracts from them is above this, unless the
+    /// extractor sets its own critical threshold in the config file.
+    #[clap(long)]
+    critical_above: Option<f64>,
+
+    /// Whether the thresholds color the whole line or only the values.
+    #[clap(long, arg_enum, default_value = "line")]
+    threshold_color: ThresholdColor,
+
+    /// When to draw in color. Auto does unless the NO_COLOR environment variable is set or
+    /// TERM is dumb. Without colors highlights are shown reversed.
+    #[clap(long, arg_enum, default_value = "auto")]
+    color: ColorChoice,
+
+    /// Whether rterm sleeps until a key or received data wakes it up, or polls for them
+    /// every 10 ms like it used to, which takes in a chunk of data at a time.
+    #[clap(long, arg_enum, default_value = "wait")]
+    event_loop: EventLoop,
+
+    /// The file the graph is exported to as an SVG image with Alt+e.
+    #[clap(long, default_value = "graph.svg")]
+    graph_export: PathBuf,
+
+    /// Write every graphed sample to this CSV file as it's extracted, with the time
+    /// it was received. Graphs exported to a file ending with ".csv" get the same columns.
+    #[clap(long)]
+    graph_out: Option<PathBuf>,
+
+    /// Write the times in CSV files in UTC instead of the local time zone.
+    #[clap(long)]
+    csv_utc: bool,
+
+    /// The size of the exported graph in pixels, e.g. "1200x600".
+    #[clap(long, default_value = "800x480", parse(try_from_str = parse_size))]
+    graph_export_size: (u32, u32),
+
+    /// How to display received NUL bytes, which noisy lines are full of. The log file
+    /// always gets them as they are, and the stripped ones are counted in the status bar.
+    #[clap(long, arg_enum, default_value = "strip")]
+    nul: ControlMode,
+
+    /// How to display the other received control characters, which would be
+    /// interpreted by the terminal as they are. Tabs are expanded, backspace takes back
+    /// the character before it and BEL rings the bell instead.
+    #[clap(long, arg_enum, default_value = "caret")]
+    controls: ControlMode,
+
+    /// The byte that ends a line instead of newline, e.g. "0" or "0x03".
+    #[clap(long, parse(try_from_str = parse_byte))]
+    delimiter: Option<u8>,
+
+    /// Pick the first device found instead of the most recently plugged in one.
+    #[clap(long)]
+    first: bool,
+
+    /// Keep running when no device is connected and connect as soon as one is plugged in.
+    #[clap(short, long)]
+    watch: bool,
+
+    /// Wait for a device to be plugged in before starting instead of failing when
+    /// none is found. Ctrl+c gives up.
+    #[clap(long)]
+    wait_for_device: bool,
+
+    /// Give up waiting for a device after this long, e.g. "30s".
+    #[clap(long, requires = "wait-for-device", parse(try_from_str = parse_duration))]
+    wait_timeout: Option<Duration>,
+
+    /// Config file defining e.g. named extractors for the graph.
+    #[clap(long)]
+    config: Option<String>,
+
+    /// File with words, like the commands of the device, that Tab completes what's
+    /// typed to. One per line, repeated Tabs cycle through the ones that match.
+    #[clap(long)]
+    completions: Option<PathBuf>,
+
+    /// Delay between every sent byte, e.g. "500us" or "5ms". A plain number is in milliseconds.
+    #[clap(long, parse(try_from_str = parse_duration))]
+    char_delay: Option<Duration>,
+
+    /// Wait at least this many milliseconds after sending a line before sending the next
+    /// one, for devices that hang when flooded. Lines sent slower than that aren't delayed.
+    #[clap(long)]
+    min_line_interval_ms: Option<u64>,
+
+    /// Configure the device with a preset instead of only changing what an arduino needs.
+    #[clap(long, arg_enum)]
+    mode: Option<Mode>,
+
+    /// Open the device without O_SYNC, for adapters that stall or fail writes with it.
+    /// Written data may then sit in the kernel for a while before it's sent.
+    #[clap(long)]
+    no_open_sync: bool,
+
+    /// Open the device without O_NOCTTY. Only for drivers that misbehave with it, since
+    /// the device can then become the controlling terminal and a hangup on it kills rterm.
+    #[clap(long)]
+    no_open_noctty: bool,
+
+    /// A second device to show the modem status lines (CTS, DSR, DCD and RI) of in the status
+    /// bar, for setups where they go through another adapter. Set lines are in upper case.
+    #[clap(long)]
+    control_device: Option<PathBuf>,
+
+    /// Print the configuration of the device after it has been set up and exit.
+    #[clap(long)]
+    show_config: bool,
+
+    /// Read for this many milliseconds after connecting before the UI starts, to catch boot logs.
+    #[clap(long)]
+    banner_wait_ms: Option<u64>,
+
+    /// A line to send right after connecting, e.g. to set the device up. Can be given
+    /// several times, the lines are sent in order.
+    #[clap(long, multiple_occurrences = true)]
+    on_connect: Vec<String>,
+
+    /// Reset the device by pulling DTR and RTS low for a moment when connecting, before
+    /// the --on-connect lines are sent.
+    #[clap(long)]
+    reset_on_connect: bool,
+
+    /// Also reset the device and send the --on-connect lines when it's reconnected, so
+    /// that it's set up again after e.g. a power cycle.
+    #[clap(long)]
+    rerun_on_reconnect: bool,
+
+    /// Make the relative timestamps count from when the device is reconnected.
+    #[clap(long)]
+    zero_timestamps_on_reconnect: bool,
+
+    /// The number of rows the input box grows to. New lines are added with Alt+Enter.
+    #[clap(long, default_value_t = 1)]
+    input_height: usize,
+
+    /// The timestamps shown in front of received lines. Cycled through with Ctrl+t. Relative
+    /// ones count from the start, or from when Alt+z was last pressed like a stopwatch.
+    #[clap(long, arg_enum, default_value = "off")]
+    timestamps: Timestamps,
+
+    /// Gaps between lines longer than this are highlighted when showing deltas.
+    #[clap(long, default_value = "1s", parse(try_from_str = parse_duration))]
+    gap_threshold: Duration,
+
+    /// Fixed width of the line number column, including padding. 0 hides the line numbers.
+    #[clap(long)]
+    line_number_width: Option<usize>,
+
+    /// The number of the first received line.
+    #[clap(long, default_value_t = 0)]
+    line_number_start: usize,
+
+    /// Where lines wider than the output are broken.
+    #[clap(long, arg_enum, default_value = "char")]
+    wrap: Wrap,
+
+    /// What word wrapping does with words wider than the output, e.g. URLs.
+    /// Truncated words are scrolled sideways with Alt+Left and Alt+Right.
+    #[clap(long, arg_enum, default_value = "break")]
+    long_words: LongWords,
+
+    /// Indent the rows wrapped lines continue on past the line numbers, behind a '↳'.
+    #[clap(long)]
+    wrap_indent: bool,
+
+    /// Wrap the output at this many columns, not counting the line numbers and timestamps,
+    /// even if the terminal is wider. The whole width is used otherwise.
+    #[clap(long)]
+    wrap_column: Option<usize>,
+
+    /// Print the received output to stdout when exiting.
+    #[clap(long)]
+    dump_on_exit: bool,
+
+    /// Quit after this long, after flushing the out file, e.g. "90" seconds
+    /// or "500ms". Exits with 0 then and with 1 on errors, for timed captures.
+    #[clap(long, parse(try_from_str = parse_secs))]
+    duration: Option<Duration>,
+
+    /// Check the checksums of NMEA sentences and show the ones that are wrong in red.
+    #[clap(long)]
+    nmea_checksum: bool,
+
+    /// Draw the output on an 80x24 screen like a VT100 terminal does instead of as
+    /// lines, for menus that move the cursor around. Moving the cursor, clearing the
+    /// screen and erasing lines are understood. The log and the graph still get lines.
+    /// The device is configured raw unless --mode says otherwise.
+    #[clap(long)]
+    vt100: bool,
+
+    /// Pipe the received lines through this shell command and show what it writes instead.
+    /// It's given up on if it exits or doesn't write anything for a few seconds.
+    #[clap(long)]
+    pipe: Option<String>,
+
+    /// Show the received lines as well as the output of the pipe command.
+    #[clap(long, requires = "pipe")]
+    pipe_alongside: bool,
+
+    /// Which lines are written to the out file when piping.
+    #[clap(long, arg_enum, default_value = "raw", requires = "pipe", conflicts_with_all = &["raw-log", "dedup-log"])]
+    pipe_log: Tap,
+
+    /// Which lines the graph extracts values from when piping.
+    #[clap(long, arg_enum, default_value = "raw", requires = "pipe")]
+    pipe_graph: Tap,
+
+    /// Run a shell command whenever a received line matches a regex, given as
+    /// 'regex:command', e.g. 'FAILED:notify-send "board failed"'. The regex ends at the
+    /// first colon. The command gets the line in RTERM_LINE and is run at most once a
+    /// second. Can be given several times.
+    #[clap(long, multiple_occurrences = true, parse(try_from_str = Rule::parse))]
+    on_match: Vec<Rule>,
+
+    /// Ring the terminal bell, flash the border of the output and show the line
+    /// as an error until Alt+d dismisses it when a received line matches this regex.
+    /// Can be given more than once, :alert <number> off turns one off.
+    #[clap(long, multiple_occurrences = true, parse(try_from_str = Regex::new))]
+    alert: Vec<Regex>,
+
+    /// Append what the --on-match commands write to this file instead of discarding it.
+    #[clap(long, requires = "on-match")]
+    on_match_log: Option<PathBuf>,
+
+    /// Send every received line as a UDP datagram to this host:port, which can
+    /// be a broadcast address. Can be given more than once.
+    #[clap(long, multiple_occurrences = true, parse(try_from_str = udp::parse_target))]
+    udp_forward: Vec<SocketAddr>,
+
+    /// Send the lines as JSON objects with the time they were received, like --jsonl.
+    #[clap(long, requires = "udp-forward")]
+    udp_json: bool,
+
+    /// Whether lines have to contain all the filters set with :addfilter or any of them.
+    /// Changed with :filtermode.
+    #[clap(long, arg_enum, default_value = "all")]
+    filter_mode: Combine,
+
+    /// Show what is sent in the output, with control characters like ESC as "^[".
+    #[clap(short = 'e', long)]
+    local_echo: bool,
+
+    /// Send every key as it's typed instead of a line at a time. Alt+k toggles it.
+    /// Keys are paced by --char-delay like everything else that is sent.
+    #[clap(long)]
+    char_mode: bool,
+
+    /// The most bytes typed in char mode that may be waiting to be sent, keys
+    /// typed beyond that are dropped.
+    #[clap(long, default_value_t = 16)]
+    char_buffer: usize,
+
+    /// End what's entered in hex input mode (Alt+x) with a newline as well.
+    #[clap(long)]
+    hex_newline: bool,
+
+    /// Send a bare line ending when Enter is pressed with nothing typed,
+    /// which is otherwise ignored.
+    #[clap(long)]
+    allow_empty_send: bool,
+
+    /// How typed text is encoded when it's sent. Characters that latin1
+    /// doesn't have can't be sent with it.
+    #[clap(long, arg_enum, default_value = "utf8")]
+    tx_encoding: TxEncoding,
+
+    /// The number of recent lines copied to the clipboard with Alt+c.
+    #[clap(long, default_value_t = 20)]
+    copy_lines: usize,
+
+    /// Copy text selected with the mouse right away, to the primary selection
+    /// where there is one and else to the clipboard.
+    #[clap(long)]
+    copy_on_select: bool,
+
+    /// Make Esc switch to a vim-like normal mode where j, k, Ctrl+d, Ctrl+u,
+    /// gg and G move through the output, / and n, N search it, v and y select
+    /// and yank lines, . sends the last line again and i goes back to the
+    /// input. Quit with :q instead.
+    #[clap(long)]
+    vim: bool,
+
+    /// The number of rows a notch of the mouse wheel scrolls the output. With Shift
+    /// or Ctrl held it scrolls by half the height of the output instead.
+    #[clap(long, default_value_t = 3)]
+    scroll_step: u16,
+
+    /// Collapse consecutive identical lines into one with a count, like `uniq -c`.
+    #[clap(long)]
+    dedup: bool,
+
+    /// Collapse them in the out file as well instead of logging every line.
+    #[clap(long, requires = "dedup")]
+    dedup_log: bool,
+
+    /// Send this line and print the first reply matching --expect instead of
+    /// starting the UI. Exits with 2 if there is no such reply within --timeout
+    /// and with 1 on errors.
+    #[clap(long, requires = "expect")]
+    send: Option<String>,
+
+    #[clap(long, requires = "send", parse(try_from_str = Regex::new))]
+    expect: Option<Regex>,
+
+    #[clap(long, default_value = "1s", requires = "send", parse(try_from_str = parse_duration))]
+    timeout: Duration,
+
+    /// Check a device that echoes what it receives, or an adapter with TX
+    /// wired to RX, by sending a pseudorandom pattern and verifying the echo
+    /// instead of starting the UI. Reports the throughput, the round trip
+    /// latency and where bytes were corrupted or dropped, and exits with 2 if
+    /// any were.
+    #[clap(long, conflicts_with = "send")]
+    benchmark: bool,
+
+    /// How long the benchmark sends for, e.g. "30s".
+    #[clap(long, default_value = "10s", requires = "benchmark", parse(try_from_str = parse_duration))]
+    benchmark_duration: Duration,
+
+    /// The most bytes the benchmark has in flight. The default fits the
+    /// serial buffers of an Arduino running an echo sketch.
+    #[clap(long, default_value = "64", requires = "benchmark")]
+    benchmark_window: usize,
+
+    /// Show an idle banner when nothing has been received for this many milliseconds.
+    #[clap(long)]
+    idle_timeout_ms: Option<u64>,
+
+    /// Also write a timestamped marker to the out file when the device goes idle.
+    #[clap(long, requires_all = &["idle-timeout-ms", "out-file"])]
+    idle_log: bool,
 }
 
-fn find_possible_arduino_dev() -> Option<String> {
-    for dir_entry in std::fs::read_dir("/dev/").ok()? {
-        let dir_entry = dir_entry.ok()?;
-        let os_file_name = dir_entry.file_name();
-        let file_name = os_file_name.to_string_lossy();
-        if file_name.starts_with("tty")
-            && file_name.len() >= 6
-            && (&file_name[3..6] == "USB" || &file_name[3..6] == "ACM")
-        {
-            return Some("/dev/".to_string() + &file_name);
-        }
+/// Parses a duration such as "500us", "5ms", or "2s". Plain numbers are milliseconds.
+fn parse_duration(s: &str) -> anyhow::Result<Duration> {
+    let s = s.trim();
+    let (number, unit) = match s.find(|c: char| !(c.is_ascii_digit() || c == '.')) {
+        Some(idx) => s.split_at(idx),
+        None => (s, "ms"),
+    };
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow!("invalid duration '{s}'"))?;
+    let secs = match unit.trim() {
+        "us" | "µs" => number / 1_000_000.0,
+        "ms" => number / 1000.0,
+        "s" => number,
+        _ => return Err(anyhow!("unknown unit in duration '{s}'")),
+    };
+    Ok(Duration::from_secs_f64(secs))
+}
+
+/// Parses a number of seconds, e.g. "1.5", or a duration with a unit like "500ms".
+fn parse_secs(s: &str) -> anyhow::Result<Duration> {
+    match s.trim().parse::<f64>() {
+        Ok(secs) if secs.is_finite() && secs >= 0.0 => Ok(Duration::from_secs_f64(secs)),
+        Ok(_) => Err(anyhow!("invalid duration '{s}'")),
+        Err(_) => parse_duration(s),
     }
-    None
+}
+
+/// Parses a byte given in decimal or as hex starting with "0x".
+fn parse_byte(s: &str) -> anyhow::Result<u8> {
+    let s = s.trim();
+    let byte = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    byte.map_err(|_| anyhow!("invalid byte '{s}'"))
+}
+
+/// Parses a size given as "<width>x<height>".
+fn parse_size(s: &str) -> anyhow::Result<(u32, u32)> {
+    let invalid = || anyhow!("invalid size '{s}', expected e.g. 800x480");
+    let (width, height) = s.trim().split_once('x').ok_or_else(invalid)?;
+    let width: u32 = width.parse().map_err(|_| invalid())?;
+    let height: u32 = height.parse().map_err(|_| invalid())?;
+    if width == 0 || height == 0 {
+        return Err(invalid());
+    }
+    Ok((width, height))
+}
+
+/// Parses a percentile between 50 and 100, e.g. "99".
+fn parse_percentile(s: &str) -> anyhow::Result<f64> {
+    let invalid = || anyhow!("invalid percentile '{s}', expected a number between 50 and 100");
+    let percentile: f64 = s.trim().parse().map_err(|_| invalid())?;
+    if !(50.0..=100.0).contains(&percentile) {
+        return Err(invalid());
+    }
+    Ok(percentile)
+}
+
+/// Parses a named series of the graph, e.g. "temp=T: (-?[\d.]+)".
+fn parse_series(s: &str) -> anyhow::Result<(String, Regex)> {
+    let (name, regex) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("invalid series '{s}', expected name=regex"))?;
+    if name.trim().is_empty() {
+        return Err(anyhow!("invalid series '{s}', the name is empty"));
+    }
+    let pattern = Regex::new(regex).context(format!("invalid regex in series '{name}'"))?;
+    if pattern.captures_len() != 2 {
+        return Err(anyhow!(
+            "the regex of series '{name}' must have exactly one capture group around the value"
+        ));
+    }
+    Ok((name.trim().to_string(), pattern))
+}
+
+/// Parses the bounds of an axis, e.g. "0,4095".
+fn parse_bounds(s: &str) -> anyhow::Result<[f64; 2]> {
+    let invalid = || anyhow!("invalid bounds '{s}', expected e.g. 0,4095");
+    let (min, max) = s.trim().split_once(',').ok_or_else(invalid)?;
+    let min: f64 = min.trim().parse().map_err(|_| invalid())?;
+    let max: f64 = max.trim().parse().map_err(|_| invalid())?;
+    if min >= max {
+        return Err(invalid());
+    }
+    Ok([min, max])
 }
 
 fn string_to_baudrate(s: &str) -> Option<BaudRate> {
@@ -126,13 +740,18 @@ fn string_to_baudrate(s: &str) -> Option<BaudRate> {
 }
 
 struct TerminalHandler {
-    terminal: Terminal<CrosstermBackend<Stdout>>,
+    terminal: Terminal<CrosstermBackend<Box<dyn Write>>>,
 }
 
 impl TerminalHandler {
     fn new() -> anyhow::Result<Self> {
         enable_raw_mode()?;
-        let mut stdout = std::io::stdout();
+        // Leaves stdout to --dump-on-exit when it's piped somewhere.
+        let mut stdout: Box<dyn Write> = if std::io::stdout().is_terminal() {
+            Box::new(std::io::stdout())
+        } else {
+            Box::new(OpenOptions::new().write(true).open("/dev/tty")?)
+        };
         execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
         let backend = CrosstermBackend::new(stdout);
         let terminal = Terminal::new(backend)?;
@@ -164,37 +783,331 @@ static PANICINFO: Mutex<Option<String>> = Mutex::new(None);
 fn main() -> anyhow::Result<()> {
     let parser = Cli::parse();
 
+    let config = match &parser.config {
+        Some(path) => Config::load(Path::new(path)).context(format!("loading '{path}'"))?,
+        None => Config::default(),
+    };
+    if !parser.series.is_empty() && !config.extractors.is_empty() {
+        return Err(anyhow!("--series can't be used together with the extractors in the config file, define the series in one of them"));
+    }
+
     let baudrate =
         string_to_baudrate(&format!("{}", parser.baudrate)).ok_or(anyhow!("invaild baubrate"))?;
-    let tty_filepath = if let Some(path) = parser.terminal_device {
-        path
-    } else {
-        find_possible_arduino_dev().ok_or(anyhow!(
-            "Could not find any open serial port automatically, please specify port"
-        ))?
+    let device = match &parser.terminal_device {
+        Some(path) => match discovery::resolve_device(path) {
+            Ok(device) => Some(device),
+            Err(_) if parser.watch => None,
+            Err(e) => return Err(e),
+        },
+        None => {
+            let mut device = discovery::find_possible_arduino_dev(parser.first);
+            if device.is_none() && parser.wait_for_device {
+                eprintln!("Waiting for device…");
+                device = Watcher::new(None, parser.first).wait(parser.wait_timeout);
+                if device.is_none() {
+                    return Err(anyhow!(
+                        "No device appeared within {:?}",
+                        parser.wait_timeout.unwrap()
+                    ));
+                }
+            }
+            match &device {
+                Some(device) => eprintln!(
+                    "Connecting to {} ({})",
+                    device.path.display(),
+                    device.display_name()
+                ),
+                None if !parser.watch => {
+                    return Err(anyhow!(
+                        "Could not find any open serial port automatically, please specify port"
+                    ))
+                }
+                None => {}
+            }
+            device
+        }
+    };
+    let (outfile, log_path) = match &parser.out_file {
+        Some(out) => {
+            let device = device.as_ref().map(|d| d.path.as_path());
+            let (file, path) = match parser.log_split_idle_ms {
+                Some(_) => logfile::open_segment(out, device)?,
+                None => logfile::open(out, device)?,
+            };
+            (Some(file), Some(path))
+        }
+        None => (None, None),
     };
 
-    let out_filepath = parser.out_file;
+    let serial_config = SerialConfig {
+        baud_rate: baudrate,
+        // Carriage returns move the cursor and the benchmark sends every
+        // byte value, so nothing may be translated or dropped.
+        mode: parser
+            .mode
+            .or((parser.vt100 || parser.benchmark).then_some(Mode::Raw)),
+        open_flags: OpenFlags {
+            sync: !parser.no_open_sync,
+            noctty: !parser.no_open_noctty,
+        },
+    };
+    let td = match &device {
+        Some(device) => {
+            let tty_filepath = device.path.to_string_lossy();
+            Some(
+                TerminalDevice::open(device.path.clone(), &serial_config)
+                    .context(format!("opening '{tty_filepath}'"))?,
+            )
+        }
+        None => None,
+    };
+    if parser.show_config {
+        let td = td.ok_or(anyhow!("no device to show the configuration of"))?;
+        println!("{}", td.describe()?);
+        return Ok(());
+    }
 
-    let outfile = if let Some(fname) = out_filepath {
-        Some(std::fs::File::create(&fname).context(format!("opening '{}'", &fname))?)
+    let mut app = app::App::new(outfile, serial_config);
+    app.device_name = match &device {
+        Some(device) => device.display_name(),
+        None => "no device".to_string(),
+    };
+    app.device_path = device.as_ref().map(|device| device.path.clone());
+    app.log_path = log_path;
+    app.log_out = parser.out_file.clone();
+    app.log_split_idle = parser.log_split_idle_ms.map(Duration::from_millis);
+    if !parser.route.is_empty() {
+        let device = device.as_ref().map(|d| d.path.as_path());
+        app.routes = Some(Routes::new(
+            parser.route,
+            device,
+            parser.log_split_idle_ms.is_some(),
+        ));
+    }
+    if let Some(path) = &parser.control_device {
+        // Only the status lines are read, so it isn't configured.
+        let control_device = TerminalDevice::new(path, serial_config.open_flags)
+            .context(format!("opening '{}'", path.display()))?;
+        app.control_device = Some(control_device);
+    }
+    if parser.watch {
+        app.watcher = Some(Watcher::new(parser.terminal_device.clone(), parser.first));
+    }
+    app.decoder.nul = parser.nul;
+    app.decoder.controls = parser.controls;
+    if let Some(delimiter) = parser.delimiter {
+        app.delimiter = delimiter;
+    }
+    // Only created from the start if something uses the values, otherwise the
+    // first time the graph or the gauge is shown.
+    let mut extractors = if !parser.series.is_empty() {
+        parser
+            .series
+            .into_iter()
+            .enumerate()
+            .map(|(i, (name, pattern))| Extractor::series(name, pattern, i))
+            .collect()
+    } else if config.extractors.is_empty() && parser.graph_digital {
+        vec![Extractor::first_level()]
+    } else if config.extractors.is_empty() && parser.graph_radix == Some(Radix::Hex) {
+        vec![Extractor::first_hex_number()]
+    } else if config.extractors.is_empty() {
+        vec![Extractor::first_number()]
     } else {
-        None
+        config.extractors
     };
-
-    let mut td =
-        TerminalDevice::new(tty_filepath.clone()).context(format!("opening '{tty_filepath}'"))?;
-    td.configure_for_arduino(baudrate)?;
-
-    let mut app = app::App::new(outfile);
-    if parser.graph {
-        app.grapher = Some(Grapher {
-            data: Vec::new(),
-            value_pattern: Regex::new("(\\-?\\d+\\.?[\\d]*)").unwrap(),
-            window_len: parser.graph_len,
-            window: [0.0, parser.graph_len as f64],
-        });
+    for extractor in &mut extractors {
+        extractor.digital = parser.graph_digital;
+        extractor.radix = extractor.radix.or(parser.graph_radix);
+        let thresholds = &mut extractor.thresholds;
+        thresholds.warn = thresholds.warn.or(parser.warn_above);
+        thresholds.critical = thresholds.critical.or(parser.critical_above);
+    }
+    if let Some(format) = parser.graph_binary {
+        let sync = match &parser.frame_sync {
+            Some(sync) => {
+                let invalid = || anyhow!("invalid --frame-sync '{sync}', expected hex bytes");
+                if !sync.chars().all(hexdump::is_input_char) {
+                    return Err(invalid());
+                }
+                let bytes = hexdump::parse(sync).context(invalid())?;
+                if bytes.is_empty() {
+                    return Err(invalid());
+                }
+                bytes
+            }
+            None => Vec::new(),
+        };
+        app.frames = Some(FrameDecoder::new(format, parser.binary_channels, sync));
+    }
+    let thresholds = extractors
+        .iter()
+        .any(|e| e.thresholds.warn.is_some() || e.thresholds.critical.is_some());
+    let channels = parser.graph_binary.map(|_| parser.binary_channels.max(1));
+    let y2 = parser.y2.clone();
+    let new_grapher = move || {
+        let mut grapher = match channels {
+            Some(channels) => Grapher::with_channels(channels, parser.graph_len),
+            None => Grapher::new(extractors.clone(), parser.graph_len),
+        };
+        grapher.steps = parser.graph_steps;
+        grapher.log_y = parser.graph_log_y;
+        grapher.derivative = parser.graph_derivative;
+        grapher.derivative_smoothing = parser.graph_derivative_smoothing;
+        grapher.y2 = y2.as_ref().and_then(|y2| grapher.series_index(y2));
+        grapher.y2_bounds = parser.y2_bounds;
+        grapher.percentile = parser.y_percentile;
+        grapher.symmetric = parser.graph_symmetric;
+        grapher.number_format = parser.y_format.clone();
+        grapher.style = parser.graph_style;
+        grapher.digital = parser.graph_digital;
+        grapher.shrink_after = parser.graph_shrink_after;
+        grapher
+    };
+    if let Some(y2) = &parser.y2 {
+        if new_grapher().y2.is_none() {
+            return Err(anyhow!("there is no series '{y2}' for --y2"));
+        }
+    }
+    let values_used = parser.graph
+        || parser.graph_sparkline
+        || parser.gauge
+        || parser.graph_out.is_some()
+        || parser.graph_binary.is_some()
+        || parser.highlight_values
+        || thresholds;
+    if values_used {
+        let mut grapher = new_grapher();
+        if let Some(path) = &parser.graph_out {
+            let out = CsvStream::create(path, &grapher, parser.csv_utc)
+                .context(format!("creating '{}'", path.display()))?;
+            grapher.out = Some(out);
+        }
+        app.grapher = Some(grapher);
     }
+    app.new_grapher = Box::new(new_grapher);
+    if let Some(path) = &parser.stats_file {
+        let stats = StatsFile::open(
+            path,
+            parser.stats_interval,
+            app.grapher.as_ref(),
+            app.routes.as_ref(),
+            parser.csv_utc,
+        );
+        app.stats = Some(stats.context(format!("opening '{}'", path.display()))?);
+    }
+    app.graph_visible = parser.graph || parser.graph_sparkline;
+    app.graph_sparkline = parser.graph_sparkline;
+    app.gauge_visible = parser.gauge;
+    app.gauge_unit = parser.gauge_unit;
+    app.graph_export = parser.graph_export;
+    app.graph_export_size = parser.graph_export_size;
+    app.csv_utc = parser.csv_utc;
+    app.banner_wait = parser.banner_wait_ms.map(Duration::from_millis);
+    app.on_connect = parser.on_connect;
+    app.reset_on_connect = parser.reset_on_connect;
+    app.rerun_on_reconnect = parser.rerun_on_reconnect;
+    app.zero_timestamps_on_reconnect = parser.zero_timestamps_on_reconnect;
+    app.input_height = parser.input_height.max(1);
+    app.timestamps = parser.timestamps;
+    app.gap_threshold = parser.gap_threshold;
+    app.line_number_width = parser.line_number_width;
+    app.line_number_start = parser.line_number_start;
+    app.wrapping = Wrapping {
+        wrap: parser.wrap,
+        long_words: parser.long_words,
+        indent: parser.wrap_indent,
+        column: parser.wrap_column.map(|column| column.max(1)),
+    };
+    app.highlight_values = parser.highlight_values;
+    app.threshold_color = parser.threshold_color;
+    app.color = parser.color.enabled();
+    app.event_loop = parser.event_loop;
+    if parser.nmea_checksum {
+        app.nmea = Some(nmea::Stats::default());
+    }
+    app.screen = parser.vt100.then(Screen::new);
+    app.raw_log = parser.raw_log;
+    if let Some(command) = &parser.pipe {
+        app.pipe = Some(Pipe::spawn(command).context("starting the pipe command")?);
+    }
+    app.pipe_alongside = parser.pipe_alongside;
+    if !parser.on_match.is_empty() {
+        let output = match &parser.on_match_log {
+            Some(path) => Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .context(format!("opening '{}'", path.display()))?,
+            ),
+            None => None,
+        };
+        app.triggers = Some(Triggers::new(parser.on_match, output));
+    }
+    if !parser.alert.is_empty() {
+        app.alerts = Some(Alerts::new(parser.alert));
+    }
+    if !parser.udp_forward.is_empty() {
+        let udp = UdpForward::new(parser.udp_forward, parser.udp_json)
+            .context("opening the UDP socket")?;
+        app.udp = Some(udp);
+    }
+    app.pipe_log = parser.pipe_log;
+    app.pipe_graph = parser.pipe_graph;
+    app.hex_newline = parser.hex_newline;
+    app.allow_empty_send = parser.allow_empty_send;
+    app.tx_encoding = parser.tx_encoding;
+    app.filters.combine = parser.filter_mode;
+    if let Some(path) = &parser.completions {
+        app.completer =
+            Some(Completer::load(path).context(format!("loading '{}'", path.display()))?);
+    }
+    app.local_echo = parser.local_echo;
+    app.log_tx = parser.log_tx.then_some(parser.log_tx_prefix);
+    if let Some(path) = &parser.jsonl {
+        app.jsonl = Some(JsonlLog::create(path).context(format!("creating '{}'", path.display()))?);
+    }
+    app.char_mode = parser.char_mode;
+    app.char_buffer = parser.char_buffer;
+    app.copy_lines = parser.copy_lines;
+    app.copy_on_select = parser.copy_on_select;
+    app.vim = parser.vim;
+    app.scroll_step = parser.scroll_step;
+    app.dedup = parser.dedup;
+    app.dedup_log = parser.dedup_log;
+    app.idle_timeout = parser.idle_timeout_ms.map(Duration::from_millis);
+    app.idle_log = parser.idle_log;
+    app.duration = parser.duration;
+    app.tx_settings = TxSettings {
+        char_delay: parser.char_delay,
+        line_interval: parser.min_line_interval_ms.map(Duration::from_millis),
+    };
+    if let (Some(command), Some(expect)) = (&parser.send, &parser.expect) {
+        let td = td.ok_or(anyhow!("no device to send to"))?;
+        match app.query(td, command, expect, parser.timeout)? {
+            Some(reply) => println!("{reply}"),
+            None => {
+                eprintln!("No reply matching '{expect}' within {:?}", parser.timeout);
+                std::process::exit(2);
+            }
+        }
+        return Ok(());
+    }
+    if parser.benchmark {
+        let td = td.ok_or(anyhow!("no device to benchmark"))?;
+        let report = app.benchmark(
+            td,
+            parser.benchmark_duration,
+            parser.benchmark_window.max(1),
+        )?;
+        print!("{report}");
+        if !report.clean() {
+            std::process::exit(2);
+        }
+        return Ok(());
+    }
+
     std::panic::set_hook(Box::new(|e| {
         let mut info = PANICINFO.lock().unwrap();
         *info = Some(format!("{:?}", e));
@@ -204,12 +1117,32 @@ fn main() -> anyhow::Result<()> {
         let mut handler = TerminalHandler::new().unwrap();
         panic::catch_unwind(AssertUnwindSafe(|| app.run(td, &mut handler.terminal)))
     };
+    // The terminal has left the alternate screen by now.
+    if parser.dump_on_exit {
+        let lines = match app.output.split_last() {
+            Some((last, rest)) if last.text.is_empty() => rest,
+            _ => &app.output,
+        };
+        for line in lines {
+            println!("{}", line.saved_text());
+        }
+    }
+    // Errors go to stderr so that they don't end up in the dumped output.
+    if let Some(path) = &app.log_path {
+        eprintln!("Logged to {}", path.display());
+    }
+    if app.timed_out {
+        eprintln!("Stopped after {:?}", parser.duration.unwrap());
+    }
     match res {
+        Ok(Ok(())) => {}
         Ok(e) => {
-            println!("{:?}", e);
+            eprintln!("{:?}", e);
+            std::process::exit(1);
         }
         Err(_) => {
-            println!("{}", PANICINFO.lock().unwrap().as_mut().unwrap());
+            eprintln!("{}", PANICINFO.lock().unwrap().as_mut().unwrap());
+            std::process::exit(1);
         }
     }
     Ok(())
@@ -0,0 +1,33 @@
+/// Counts of the NMEA sentences that have been checked.
+#[derive(Clone, Copy, Default)]
+pub struct Stats {
+    pub good: usize,
+    pub bad: usize,
+}
+
+/// Checks the checksum of an NMEA sentence like `$GPGLL,...*1D`. Returns
+/// `None` if the line doesn't look like a sentence with a checksum.
+pub fn check(line: &str) -> Option<bool> {
+    let body = line.strip_prefix('$')?;
+    let (data, checksum) = body.rsplit_once('*')?;
+    let checksum = checksum.trim_end();
+    if checksum.len() != 2 {
+        return None;
+    }
+    let expected = u8::from_str_radix(checksum, 16).ok()?;
+    let actual = data.bytes().fold(0, |acc, b| acc ^ b);
+    Some(actual == expected)
+}
+
+impl Stats {
+    /// Checks `line` and counts the result if it's a sentence.
+    pub fn record(&mut self, line: &str) -> Option<bool> {
+        let valid = check(line)?;
+        if valid {
+            self.good += 1;
+        } else {
+            self.bad += 1;
+        }
+        Some(valid)
+    }
+}
@@ -0,0 +1,246 @@
+use anyhow::anyhow;
+
+/// The SI prefixes values are scaled to, by their power of ten.
+const SI_PREFIXES: &[(i32, &str)] = &[
+    (-12, "p"),
+    (-9, "n"),
+    (-6, "µ"),
+    (-3, "m"),
+    (3, "k"),
+    (6, "M"),
+    (9, "G"),
+    (12, "T"),
+];
+
+/// How the values in the graph are formatted.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NumberFormat {
+    /// As many decimals as tell the values apart, with an SI prefix for
+    /// very small or large ones, e.g. "123µ" or "1.52M".
+    Auto,
+    /// A fixed number of decimals.
+    Precision(usize),
+    /// Like printf with a single %f, %e or %g, e.g. "%.2e V".
+    Printf(Printf),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Printf {
+    prefix: String,
+    width: usize,
+    precision: usize,
+    conversion: char,
+    suffix: String,
+}
+
+impl NumberFormat {
+    /// Parses a number of decimals, e.g. "3", or a printf format, e.g. "%8.3f".
+    pub fn parse(s: &str) -> anyhow::Result<NumberFormat> {
+        if s == "auto" {
+            return Ok(NumberFormat::Auto);
+        }
+        if let Ok(precision) = s.parse() {
+            return Ok(NumberFormat::Precision(precision));
+        }
+        let invalid = || anyhow!("invalid number format '{s}', expected e.g. 3 or %.2e");
+        let (prefix, spec) = s.split_once('%').ok_or_else(invalid)?;
+        let end = spec
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .ok_or_else(invalid)?;
+        let (numbers, rest) = spec.split_at(end);
+        let mut chars = rest.chars();
+        let conversion = chars
+            .next()
+            .filter(|c| "feg".contains(*c))
+            .ok_or_else(invalid)?;
+        let (width, precision) = match numbers.split_once('.') {
+            Some((width, precision)) => (width, precision.parse().map_err(|_| invalid())?),
+            None => (numbers, 6),
+        };
+        let width = match width {
+            "" => 0,
+            width => width.parse().map_err(|_| invalid())?,
+        };
+        Ok(NumberFormat::Printf(Printf {
+            prefix: prefix.to_string(),
+            width,
+            precision,
+            conversion,
+            suffix: chars.as_str().to_string(),
+        }))
+    }
+
+    /// Formats `value`, one of the values within `range`, e.g. a label of
+    /// an axis with those bounds. A range of only the value itself gives
+    /// four significant digits.
+    pub fn format(&self, value: f64, range: [f64; 2]) -> String {
+        match self {
+            NumberFormat::Auto => auto(value, range),
+            NumberFormat::Precision(precision) => format!("{value:.precision$}"),
+            NumberFormat::Printf(printf) => printf.format(value),
+        }
+    }
+}
+
+impl Printf {
+    fn format(&self, value: f64) -> String {
+        let precision = self.precision;
+        let number = match self.conversion {
+            'e' => format!("{value:.precision$e}"),
+            'g' => general(value, precision),
+            _ => format!("{value:.precision$}"),
+        };
+        format!(
+            "{}{number:>width$}{}",
+            self.prefix,
+            self.suffix,
+            width = self.width
+        )
+    }
+}
+
+/// Like printf's %g, `precision` significant digits with the trailing zeros
+/// left out, in scientific notation if the exponent is too small or large.
+fn general(value: f64, precision: usize) -> String {
+    let precision = precision.max(1);
+    if value == 0.0 || !value.is_finite() {
+        return value.to_string();
+    }
+    let exponent = value.abs().log10().floor() as i32;
+    if exponent < -4 || exponent >= precision as i32 {
+        let formatted = format!("{value:.*e}", precision - 1);
+        let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+        return format!("{}e{exponent}", trim_zeros(mantissa));
+    }
+    let decimals = (precision as i32 - 1 - exponent).max(0) as usize;
+    trim_zeros(&format!("{value:.decimals$}")).to_string()
+}
+
+fn trim_zeros(number: &str) -> &str {
+    if number.contains('.') {
+        number.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        number
+    }
+}
+
+fn auto(value: f64, range: [f64; 2]) -> String {
+    if !value.is_finite() {
+        return value.to_string();
+    }
+    let [min, max] = range;
+    // The prefix is chosen by the largest value so that all labels get the same one.
+    let magnitude = min.abs().max(max.abs()).max(value.abs());
+    if magnitude == 0.0 {
+        return "0".to_string();
+    }
+    let exponent = if (0.01..10_000.0).contains(&magnitude) {
+        0
+    } else {
+        (magnitude.log10() / 3.0).floor() as i32 * 3
+    };
+    let prefix = match exponent {
+        0 => "",
+        _ => match SI_PREFIXES.iter().find(|(e, _)| *e == exponent) {
+            Some((_, prefix)) => prefix,
+            None => return format!("{value:.3e}"),
+        },
+    };
+    let scale = 10f64.powi(exponent);
+    let scaled = value / scale;
+    let span = (max - min) / scale;
+    let decimals = if span > 0.0 {
+        // Enough decimals to tell apart labels half the span apart.
+        (-(span / 2.0).log10()).ceil() as i32 + 1
+    } else if scaled != 0.0 {
+        3 - scaled.abs().log10().floor() as i32
+    } else {
+        0
+    };
+    let decimals = decimals.clamp(0, 6) as usize;
+    let formatted = format!("{scaled:.decimals$}");
+    // Small negative values would otherwise be shown as "-0".
+    let formatted = match formatted.trim_start_matches('-').trim_matches(['0', '.']) {
+        "" => formatted.trim_start_matches('-').to_string(),
+        _ => formatted,
+    };
+    format!("{formatted}{prefix}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_decimals_and_printf_formats() {
+        assert_eq!(NumberFormat::parse("auto").unwrap(), NumberFormat::Auto);
+        assert_eq!(
+            NumberFormat::parse("3").unwrap(),
+            NumberFormat::Precision(3)
+        );
+        assert_eq!(
+            NumberFormat::parse("%.2e V").unwrap(),
+            NumberFormat::Printf(Printf {
+                prefix: String::new(),
+                width: 0,
+                precision: 2,
+                conversion: 'e',
+                suffix: " V".to_string(),
+            })
+        );
+        for invalid in ["x", "%d", "%.ze", "%8.f", "%"] {
+            assert!(NumberFormat::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn formats_like_printf() {
+        let format = |s: &str, value| NumberFormat::parse(s).unwrap().format(value, [0.0, 1.0]);
+        assert_eq!(format("2", 1.23456), "1.23");
+        assert_eq!(format("%8.3f", 1.23456), "   1.235");
+        assert_eq!(format("%.2e V", 1234.5), "1.23e3 V");
+        assert_eq!(format("T=%g", 0.0001234), "T=0.0001234");
+        assert_eq!(format("%g", 1234567.0), "1.23457e6");
+        assert_eq!(format("%.3g", 2.5), "2.5");
+    }
+
+    #[test]
+    fn auto_scales_to_si_prefixes() {
+        assert_eq!(auto(1.5e-4, [0.0, 2e-4]), "150µ");
+        assert_eq!(auto(1.52e6, [1.52e6, 1.52e6]), "1.520M");
+        assert_eq!(auto(0.5, [0.0, 1.0]), "0.50");
+        assert_eq!(auto(-0.0001, [-1.0, 1.0]), "0.0");
+        assert_eq!(auto(0.0, [0.0, 0.0]), "0");
+    }
+
+    #[test]
+    fn auto_covers_nano_to_giga() {
+        let expected = [
+            ("1.500n", "-1.5n"),
+            ("15.00n", "-15n"),
+            ("150.0n", "-150n"),
+            ("1.500µ", "-1.5µ"),
+            ("15.00µ", "-15µ"),
+            ("150.0µ", "-150µ"),
+            ("1.500m", "-1.5m"),
+            ("0.01500", "-0.015"),
+            ("0.1500", "-0.15"),
+            ("1.500", "-1.5"),
+            ("15.00", "-15"),
+            ("150.0", "-150"),
+            ("1500", "-1500"),
+            ("15.00k", "-15k"),
+            ("150.0k", "-150k"),
+            ("1.500M", "-1.5M"),
+            ("15.00M", "-15M"),
+            ("150.0M", "-150M"),
+            ("1.500G", "-1.5G"),
+        ];
+        for (exponent, (alone, in_range)) in (-9..=9).zip(expected) {
+            let value = 1.5 * 10f64.powi(exponent);
+            assert_eq!(auto(value, [value, value]), alone);
+            // Within a range, only the decimals that tell labels apart are shown.
+            assert_eq!(auto(-value, [-value, value]), in_range);
+        }
+    }
+}
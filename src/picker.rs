@@ -0,0 +1,115 @@
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState},
+    Frame,
+};
+
+/// The most entries listed. Older ones are found by filtering.
+const MAX_LISTED: usize = 300;
+
+/// A popup listing the entered lines, the latest first, filtered by what's
+/// typed while it's open.
+pub struct HistoryPicker {
+    filter: String,
+    /// The indices into the history of the listed entries.
+    listed: Vec<usize>,
+    /// The index into `listed` of the selected entry.
+    selected: usize,
+}
+
+impl HistoryPicker {
+    pub fn new(history: &[&str]) -> Self {
+        let mut picker = HistoryPicker {
+            filter: String::new(),
+            listed: Vec::new(),
+            selected: 0,
+        };
+        picker.refilter(history);
+        picker
+    }
+
+    pub fn push(&mut self, c: char, history: &[&str]) {
+        self.filter.push(c);
+        self.refilter(history);
+    }
+
+    pub fn pop(&mut self, history: &[&str]) {
+        self.filter.pop();
+        self.refilter(history);
+    }
+
+    /// Lists the latest entries that contain the filter, ignoring case.
+    fn refilter(&mut self, history: &[&str]) {
+        let filter = self.filter.to_lowercase();
+        self.listed = history
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, text)| text.to_lowercase().contains(&filter))
+            .map(|(idx, _)| idx)
+            .take(MAX_LISTED)
+            .collect();
+        self.selected = 0;
+    }
+
+    /// Moves the selection `rows` entries down, or up if negative, towards older entries.
+    pub fn move_selection(&mut self, rows: i64) {
+        let last = self.listed.len().saturating_sub(1) as i64;
+        self.selected = (self.selected as i64 + rows).clamp(0, last) as usize;
+    }
+
+    /// The index into the history of the selected entry.
+    pub fn selected(&self) -> Option<usize> {
+        self.listed.get(self.selected).copied()
+    }
+
+    pub fn render<B: Backend>(&self, f: &mut Frame<B>, area: Rect, history: &[&str]) {
+        let title = match self.filter.as_str() {
+            "" => format!(
+                "History ({} of {}), type to filter, Enter inserts, Esc cancels",
+                self.listed.len(),
+                history.len()
+            ),
+            filter => format!(
+                "History ({} of {}), filter: {filter}",
+                self.listed.len(),
+                history.len()
+            ),
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .border_style(Style::default().fg(Color::Cyan));
+        let number_width = history.len().to_string().len();
+        // Leaves room for the borders, the highlight symbol and the number.
+        let text_width = (area.width as usize).saturating_sub(2 + 2 + number_width + 1);
+        let items: Vec<ListItem> = self
+            .listed
+            .iter()
+            .map(|&idx| {
+                let text = truncate(&history[idx].replace('\n', "⏎"), text_width);
+                ListItem::new(format!("{:>number_width$} {text}", idx + 1))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+        let mut state = ListState::default();
+        state.select((!self.listed.is_empty()).then_some(self.selected));
+        f.render_widget(Clear, area);
+        f.render_stateful_widget(list, area, &mut state);
+    }
+}
+
+/// Cuts `text` to `width` characters, ending it with an ellipsis if anything was cut.
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
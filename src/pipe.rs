@@ -0,0 +1,145 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    os::unix::process::CommandExt,
+    process::{Child, Command, Stdio},
+    sync::{
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use clap::ArgEnum;
+use nix::libc;
+
+/// The number of lines that can wait for the command to read them before
+/// it's considered stuck.
+const QUEUE_LEN: usize = 1024;
+/// How long the command gets to exit by itself once its input is closed.
+const EXIT_GRACE: Duration = Duration::from_secs(1);
+/// The command is considered stuck when writing a line to it takes longer
+/// than this. Commands that filter lines out can stay quiet for any time.
+const STUCK_AFTER: Duration = Duration::from_secs(3);
+
+/// Which lines something gets when received lines are piped through a command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum)]
+pub enum Tap {
+    /// The received lines.
+    Raw,
+    /// The lines written by the command.
+    Piped,
+}
+
+/// A command that received lines are piped through.
+pub struct Pipe {
+    child: Child,
+    /// Dropped to close the input of the command.
+    input: Option<SyncSender<String>>,
+    output: Receiver<String>,
+    /// When the write to the command that hasn't finished yet started.
+    writing_since: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Pipe {
+    /// Starts `command` with the shell.
+    pub fn spawn(command: &str) -> io::Result<Pipe> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            // Everything the shell starts can be killed together.
+            .process_group(0)
+            .spawn()?;
+        let mut stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        // The writes happen on their own thread so that a command that stops
+        // reading can't block the UI.
+        let (input_tx, input_rx) = mpsc::sync_channel::<String>(QUEUE_LEN);
+        let writing_since = Arc::new(Mutex::new(None));
+        let writing = writing_since.clone();
+        thread::spawn(move || {
+            for line in input_rx {
+                *writing.lock().unwrap() = Some(Instant::now());
+                if writeln!(stdin, "{line}")
+                    .and_then(|_| stdin.flush())
+                    .is_err()
+                {
+                    break;
+                }
+                *writing.lock().unwrap() = None;
+            }
+        });
+        let (output_tx, output_rx) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if output_tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Pipe {
+            child,
+            input: Some(input_tx),
+            output: output_rx,
+            writing_since,
+        })
+    }
+
+    /// Queues a line for the command. Fails if the command doesn't keep up
+    /// with reading them.
+    pub fn send(&mut self, line: &str) -> Result<(), String> {
+        let Some(input) = &self.input else {
+            return Err("the input of the pipe command is closed".to_string());
+        };
+        match input.try_send(line.to_string()) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => Err("the pipe command stopped reading".to_string()),
+            Err(TrySendError::Disconnected(_)) => {
+                Err("the pipe command closed its input".to_string())
+            }
+        }
+    }
+
+    /// The lines the command has written since the last call, and why the
+    /// command can't be used anymore if it has exited.
+    pub fn poll(&mut self) -> (Vec<String>, Option<String>) {
+        let exited = self.child.try_wait().ok().flatten();
+        let lines: Vec<String> = self.output.try_iter().collect();
+        let stuck = self
+            .writing_since
+            .lock()
+            .unwrap()
+            .is_some_and(|since| since.elapsed() > STUCK_AFTER);
+        let failure = match exited {
+            Some(status) => Some(format!("the pipe command exited with {status}")),
+            None if stuck => Some("the pipe command stopped reading".to_string()),
+            None => None,
+        };
+        (lines, failure)
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        self.input = None;
+        let deadline = Instant::now() + EXIT_GRACE;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        // Safety: kill only sends a signal, to the process group of the shell.
+        unsafe {
+            libc::kill(-(self.child.id() as libc::pid_t), libc::SIGKILL);
+        }
+        let _ = self.child.wait();
+    }
+}
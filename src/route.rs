@@ -0,0 +1,151 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use regex::Regex;
+
+use crate::logfile::{self, LogSink};
+
+/// Appends the received lines matching a regex to a log file of its own.
+pub struct Route {
+    pub pattern: Regex,
+    /// What the file is opened from, like the out file, e.g. "logs/wifi-%H%M.txt".
+    pub out: String,
+    /// Opened when the first line matches.
+    sink: Option<LogSink>,
+    /// Opening or writing the file failed, so no more lines are routed to it.
+    failed: bool,
+    /// The number of lines that have matched.
+    pub lines: usize,
+}
+
+impl Route {
+    /// Parses a route given as "regex=path", e.g. "^\[WIFI\]=wifi.txt". The
+    /// path starts after the last '='.
+    pub fn parse(s: &str) -> anyhow::Result<Route> {
+        let (regex, out) = s
+            .rsplit_once('=')
+            .ok_or_else(|| anyhow!("invalid route '{s}', expected regex=path"))?;
+        if out.trim().is_empty() {
+            return Err(anyhow!("invalid route '{s}', the path is empty"));
+        }
+        Ok(Route {
+            pattern: Regex::new(regex).context(format!("invalid regex in route '{s}'"))?,
+            out: out.to_string(),
+            sink: None,
+            failed: false,
+            lines: 0,
+        })
+    }
+}
+
+/// Demultiplexes the received lines into the files of the routes they
+/// match, independently of the out file.
+pub struct Routes {
+    pub routes: Vec<Route>,
+    /// The device the files are named after when they're opened in a directory.
+    device: Option<PathBuf>,
+    /// Every file is named by the time it's started at, since the log is split.
+    segments: bool,
+}
+
+impl Routes {
+    pub fn new(routes: Vec<Route>, device: Option<&Path>, segments: bool) -> Self {
+        Routes {
+            routes,
+            device: device.map(Path::to_path_buf),
+            segments,
+        }
+    }
+
+    /// Appends `line` to the file of every route it matches. Returns why
+    /// routes stopped working, which only happens once for each.
+    pub fn write(&mut self, line: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        for route in &mut self.routes {
+            if !route.pattern.is_match(line) {
+                continue;
+            }
+            route.lines += 1;
+            if route.failed {
+                continue;
+            }
+            let written = match &mut route.sink {
+                Some(sink) => sink
+                    .write_all(format!("{line}\n").as_bytes())
+                    .map_err(anyhow::Error::from),
+                None => {
+                    let opened = if self.segments {
+                        logfile::open_segment(&route.out, self.device.as_deref())
+                    } else {
+                        logfile::open(&route.out, self.device.as_deref())
+                    };
+                    opened.and_then(|(mut sink, _)| {
+                        sink.write_all(format!("{line}\n").as_bytes())?;
+                        route.sink = Some(sink);
+                        Ok(())
+                    })
+                }
+            };
+            if let Err(e) = written {
+                route.failed = true;
+                route.sink = None;
+                errors.push(format!("routing to '{}': {e:#}", route.out));
+            }
+        }
+        errors
+    }
+
+    /// Closes the files so that the next lines go to new ones, when the log is split.
+    pub fn split(&mut self) {
+        for route in &mut self.routes {
+            route.sink = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn lines_go_to_every_matching_file() {
+        let dir = std::env::temp_dir().join(format!("rterm-route-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let routes = ["^\\[WIFI\\]", "^\\[MOTOR\\]", "rpm=", "never"]
+            .iter()
+            .zip(["wifi.txt", "motor.txt", "rpm.txt", "never.txt"])
+            .map(|(regex, file)| {
+                let route = format!("{regex}={}", dir.join(file).display());
+                Route::parse(&route).unwrap()
+            })
+            .collect();
+        let mut routes = Routes::new(routes, None, false);
+        for line in ["[WIFI] up", "[MOTOR] rpm=100", "other", "[WIFI] down"] {
+            assert!(routes.write(line).is_empty());
+        }
+        let read = |file: &str| fs::read_to_string(dir.join(file)).unwrap();
+        assert_eq!(read("wifi.txt"), "[WIFI] up\n[WIFI] down\n");
+        assert_eq!(read("motor.txt"), "[MOTOR] rpm=100\n");
+        assert_eq!(read("rpm.txt"), "[MOTOR] rpm=100\n");
+        // Only created once a line matches.
+        assert!(!dir.join("never.txt").exists());
+        let counts: Vec<usize> = routes.routes.iter().map(|route| route.lines).collect();
+        assert_eq!(counts, [2, 1, 1, 0]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn parses_routes() {
+        let route = Route::parse("temp=\\d+=temp.txt").unwrap();
+        assert_eq!(route.pattern.as_str(), "temp=\\d+");
+        assert_eq!(route.out, "temp.txt");
+        for invalid in ["wifi.txt", "WIFI=", "(=x.txt"] {
+            assert!(Route::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{csv, graph::Grapher, route::Routes, timefmt};
+
+/// The totals that are written to the statistics file.
+pub struct Counters {
+    pub rx_bytes: usize,
+    pub tx_bytes: usize,
+    pub lines: usize,
+    pub reconnects: usize,
+    /// Error notifications and possible data losses.
+    pub errors: usize,
+    /// Received bytes that weren't displayed.
+    pub stripped: usize,
+    /// The lines that matched each route.
+    pub route_lines: Vec<usize>,
+}
+
+/// The values of a series since the last row.
+#[derive(Clone, Copy)]
+struct Running {
+    min: f64,
+    max: f64,
+    sum: f64,
+    count: usize,
+}
+
+impl Running {
+    const EMPTY: Running = Running {
+        min: f64::INFINITY,
+        max: f64::NEG_INFINITY,
+        sum: 0.0,
+        count: 0,
+    };
+}
+
+/// Appends a row of statistics to a CSV file at a fixed interval, for keeping
+/// an eye on long runs.
+pub struct StatsFile {
+    /// Unbuffered so that every row is a single write and the file never
+    /// ends with half a row, even if rterm is killed.
+    file: File,
+    interval: Duration,
+    next: Instant,
+    /// Every series of the grapher the file was opened with.
+    series: Vec<Running>,
+    utc: bool,
+}
+
+impl StatsFile {
+    /// Opens the file to append to it, writing the header if it's empty.
+    /// A column of the line count is added for every route of `routes`, and
+    /// of the minimum, maximum and mean for every series of `grapher`. A
+    /// file that has other columns is left alone.
+    pub fn open(
+        path: &Path,
+        interval: Duration,
+        grapher: Option<&Grapher>,
+        routes: Option<&Routes>,
+        utc: bool,
+    ) -> io::Result<StatsFile> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+        let header = {
+            let mut columns: Vec<String> = [
+                "time",
+                "rx_bytes",
+                "tx_bytes",
+                "lines",
+                "reconnects",
+                "errors",
+                "stripped",
+            ]
+            .iter()
+            .map(|column| column.to_string())
+            .collect();
+            for route in routes.map_or(&[][..], |r| &r.routes) {
+                columns.push(csv::field(&format!("{} lines", route.out)));
+            }
+            for (i, series) in grapher.map_or(&[][..], |g| &g.series).iter().enumerate() {
+                let name = match series.name.as_str() {
+                    "" => format!("series {i}"),
+                    name => name.to_string(),
+                };
+                for stat in ["min", "max", "mean"] {
+                    columns.push(csv::field(&format!("{name} {stat}")));
+                }
+            }
+            columns.join(",")
+        };
+        if file.metadata()?.len() == 0 {
+            file.write_all(format!("{header}\n").as_bytes())?;
+        } else {
+            let mut existing = String::new();
+            BufReader::new(&file).read_line(&mut existing)?;
+            if existing.trim_end_matches('\n') != header {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("it has other columns than {header}"),
+                ));
+            }
+        }
+        Ok(StatsFile {
+            file,
+            interval,
+            next: Instant::now() + interval,
+            series: vec![Running::EMPTY; grapher.map_or(0, |g| g.series.len())],
+            utc,
+        })
+    }
+
+    /// Whether a row should be written now.
+    pub fn due(&self) -> bool {
+        Instant::now() >= self.next
+    }
+
+    /// Takes the values of a graph sample into the statistics of the current
+    /// row. They are kept here since the grapher drops old samples.
+    pub fn record(&mut self, values: &[Option<f64>]) {
+        for (running, value) in self.series.iter_mut().zip(values) {
+            if let Some(y) = *value {
+                running.min = running.min.min(y);
+                running.max = running.max.max(y);
+                running.sum += y;
+                running.count += 1;
+            }
+        }
+    }
+
+    /// Writes the totals and the minimum, maximum and mean of every series
+    /// over the values recorded since the last row.
+    pub fn write(&mut self, counters: &Counters) -> io::Result<()> {
+        let mut columns = vec![
+            timefmt::iso8601(SystemTime::now(), self.utc),
+            counters.rx_bytes.to_string(),
+            counters.tx_bytes.to_string(),
+            counters.lines.to_string(),
+            counters.reconnects.to_string(),
+            counters.errors.to_string(),
+            counters.stripped.to_string(),
+        ];
+        columns.extend(counters.route_lines.iter().map(usize::to_string));
+        for running in &mut self.series {
+            let Running {
+                min,
+                max,
+                sum,
+                count,
+            } = std::mem::replace(running, Running::EMPTY);
+            if count == 0 {
+                columns.extend([String::new(), String::new(), String::new()]);
+            } else {
+                columns.extend([min, max, sum / count as f64].map(|v| v.to_string()));
+            }
+        }
+        // Counted from now, so a late row doesn't make the next one come early.
+        self.next = Instant::now() + self.interval;
+        self.file
+            .write_all(format!("{}\n", columns.join(",")).as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{
+        graph::{Extractor, KEPT_SAMPLES},
+        route::Route,
+    };
+
+    #[test]
+    fn appends_rows_under_one_header() {
+        let path = std::env::temp_dir().join(format!("rterm-stats-{}.csv", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut grapher = Grapher::new(vec![Extractor::first_number()], 60);
+        let counters = Counters {
+            rx_bytes: 10,
+            tx_bytes: 2,
+            lines: 3,
+            reconnects: 0,
+            errors: 1,
+            stripped: 5,
+            route_lines: vec![4],
+        };
+        let routes = Routes::new(vec![Route::parse("^W=wifi.txt").unwrap()], None, false);
+        for values in [["1", "4", "x 7"], ["2", "", "none"]] {
+            let mut stats =
+                StatsFile::open(&path, Duration::ZERO, Some(&grapher), Some(&routes), true)
+                    .unwrap();
+            for line in values {
+                let values = grapher.extract(line);
+                stats.record(&values);
+                grapher.add_values(&values);
+            }
+            stats.write(&counters).unwrap();
+        }
+        let content = fs::read_to_string(&path).unwrap();
+        let rows: Vec<&str> = content.lines().collect();
+        assert_eq!(rows[0], "time,rx_bytes,tx_bytes,lines,reconnects,errors,stripped,wifi.txt lines,series 0 min,series 0 max,series 0 mean");
+        assert!(rows[1].ends_with("Z,10,2,3,0,1,5,4,1,7,4"), "{}", rows[1]);
+        assert!(rows[2].ends_with("Z,10,2,3,0,1,5,4,2,2,2"), "{}", rows[2]);
+        assert_eq!(rows.len(), 3);
+
+        // The values of a row aren't lost when the grapher drops old samples.
+        let mut stats =
+            StatsFile::open(&path, Duration::ZERO, Some(&grapher), Some(&routes), true).unwrap();
+        for i in 0..2 * KEPT_SAMPLES {
+            let values = grapher.extract(&i.to_string());
+            stats.record(&values);
+            grapher.add_values(&values);
+        }
+        stats.write(&counters).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        let last = content.lines().last().unwrap();
+        let mean = (2 * KEPT_SAMPLES - 1) as f64 / 2.0;
+        let expected = format!(",0,{},{mean}", 2 * KEPT_SAMPLES - 1);
+        assert!(last.ends_with(&expected), "{last}");
+
+        // Rows with other columns aren't added under the header.
+        let grapher = Grapher::new(
+            vec![Extractor::first_number(), Extractor::first_number()],
+            60,
+        );
+        let opened = StatsFile::open(&path, Duration::ZERO, Some(&grapher), Some(&routes), true);
+        assert_eq!(opened.err().unwrap().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,168 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use tui::style::Color;
+
+use crate::graph::Grapher;
+
+const MARGIN_LEFT: f64 = 80.0;
+const MARGIN_RIGHT: f64 = 20.0;
+const MARGIN_TOP: f64 = 30.0;
+const MARGIN_BOTTOM: f64 = 50.0;
+const TICKS: usize = 5;
+
+/// The color used in the image for a terminal color.
+fn color(color: Color) -> String {
+    let hex = match color {
+        Color::Black => "#000000",
+        Color::Red => "#cd3131",
+        Color::Green => "#0dbc79",
+        Color::Yellow => "#c7a500",
+        Color::Blue => "#2472c8",
+        Color::Magenta => "#bc3fbc",
+        Color::Cyan => "#11a8cd",
+        Color::Gray => "#a0a0a0",
+        Color::DarkGray => "#666666",
+        Color::LightRed => "#f14c4c",
+        Color::LightGreen => "#23d18b",
+        Color::LightYellow => "#e5c000",
+        Color::LightBlue => "#3b8eea",
+        Color::LightMagenta => "#d670d6",
+        Color::LightCyan => "#29b8db",
+        Color::White => "#e5e5e5",
+        Color::Rgb(r, g, b) => return format!("#{r:02x}{g:02x}{b:02x}"),
+        _ => "#c7a500",
+    };
+    hex.to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Formats a label of the x axis without needless trailing zeros.
+fn label(value: f64) -> String {
+    let text = format!("{value:.4}");
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+/// The lowest and highest of `values`, widened if they are the same.
+fn bounds(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+        (min.min(v), max.max(v))
+    });
+    if !min.is_finite() {
+        (0.0, 1.0)
+    } else if min == max {
+        (min - 1.0, max + 1.0)
+    } else {
+        (min, max)
+    }
+}
+
+/// Renders every sample the grapher has kept, not only the visible window,
+/// as an SVG image of `width` x `height` pixels.
+pub fn render(grapher: &Grapher, width: u32, height: u32) -> String {
+    let (width, height) = (width as f64, height as f64);
+    let plot_width = (width - MARGIN_LEFT - MARGIN_RIGHT).max(1.0);
+    let plot_height = (height - MARGIN_TOP - MARGIN_BOTTOM).max(1.0);
+    let points = || grapher.series.iter().flat_map(|s| s.data.iter());
+    let (x_min, x_max) = bounds(points().map(|(x, _)| *x));
+    let (y_min, y_max) = bounds(points().map(|(_, y)| *y));
+    let to_x = |x: f64| MARGIN_LEFT + (x - x_min) / (x_max - x_min) * plot_width;
+    let to_y = |y: f64| MARGIN_TOP + (y_max - y) / (y_max - y_min) * plot_height;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}" font-family="sans-serif" font-size="12">"#
+    );
+    let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#);
+
+    // Grid lines and labels.
+    for i in 0..=TICKS {
+        let fraction = i as f64 / TICKS as f64;
+        let x = x_min + fraction * (x_max - x_min);
+        let y = y_min + fraction * (y_max - y_min);
+        let (px, py) = (to_x(x), to_y(y));
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{px:.1}" y1="{MARGIN_TOP}" x2="{px:.1}" y2="{:.1}" stroke="#e0e0e0"/>"##,
+            MARGIN_TOP + plot_height
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{px:.1}" y="{:.1}" text-anchor="middle">{}</text>"#,
+            MARGIN_TOP + plot_height + 18.0,
+            label(x)
+        );
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{MARGIN_LEFT}" y1="{py:.1}" x2="{:.1}" y2="{py:.1}" stroke="#e0e0e0"/>"##,
+            MARGIN_LEFT + plot_width
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{:.1}" y="{:.1}" text-anchor="end">{}</text>"#,
+            MARGIN_LEFT - 6.0,
+            py + 4.0,
+            grapher.number_format.format(y, [y_min, y_max])
+        );
+    }
+    let _ = writeln!(
+        svg,
+        r#"<rect x="{MARGIN_LEFT}" y="{MARGIN_TOP}" width="{plot_width:.1}" height="{plot_height:.1}" fill="none" stroke="black"/>"#
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="{:.1}" y="{:.1}" text-anchor="middle">Sample</text>"#,
+        MARGIN_LEFT + plot_width / 2.0,
+        height - 10.0
+    );
+
+    for series in &grapher.series {
+        let data = if grapher.steps {
+            crate::graph::steps(&series.data)
+        } else {
+            series.data.clone()
+        };
+        let points: Vec<String> = data
+            .iter()
+            .map(|(x, y)| format!("{:.2},{:.2}", to_x(*x), to_y(*y)))
+            .collect();
+        let _ = writeln!(
+            svg,
+            r#"<polyline fill="none" stroke="{}" stroke-width="1.5" points="{}"/>"#,
+            color(series.color),
+            points.join(" ")
+        );
+    }
+
+    // Legend for the named series.
+    let mut legend_x = MARGIN_LEFT;
+    for series in grapher.series.iter().filter(|s| !s.name.is_empty()) {
+        let _ = writeln!(
+            svg,
+            r#"<text x="{legend_x:.1}" y="{:.1}" fill="{}">{}</text>"#,
+            MARGIN_TOP - 10.0,
+            color(series.color),
+            escape(&series.name)
+        );
+        legend_x += 12.0 + series.name.chars().count() as f64 * 8.0;
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Writes the image made by `render` to `path`.
+pub fn export(grapher: &Grapher, path: &Path, width: u32, height: u32) -> std::io::Result<()> {
+    std::fs::write(path, render(grapher, width, height))
+}
@@ -1,12 +1,83 @@
+use clap::ArgEnum;
 use nix::fcntl::{open, OFlag};
+use nix::libc;
+use nix::poll::{poll, PollFd, PollFlags};
 use nix::sys::termios::{
-    cfsetispeed, cfsetospeed, tcflush, tcgetattr, tcsetattr, BaudRate, ControlFlags, FlushArg,
-    InputFlags, LocalFlags, OutputFlags, SetArg, SpecialCharacterIndices, Termios,
+    cfgetispeed, cfgetospeed, cfmakeraw, cfsetispeed, cfsetospeed, tcflush, tcgetattr, tcsetattr,
+    BaudRate, ControlFlags, FlushArg, InputFlags, LocalFlags, OutputFlags, SetArg,
+    SpecialCharacterIndices, Termios,
 };
 use nix::unistd::{close, read, write};
 use std::io;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// How long DTR and RTS are held low to reset a device.
+const RESET_PULSE: Duration = Duration::from_millis(100);
+
+/// Presets for how the device is configured.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum)]
+pub enum Mode {
+    /// No processing of the data at all, like cfmakeraw. 8N1.
+    Raw,
+    /// Raw, which suits arduinos using println. Their CRLF line endings are
+    /// taken apart when lines are assembled.
+    Arduino,
+}
+
+/// The settings a device is configured with.
+#[derive(Clone, Copy, Debug)]
+pub struct SerialConfig {
+    pub baud_rate: BaudRate,
+    /// The preset to use. Without one only the flags needed for an arduino
+    /// are changed and the rest are left as they were.
+    pub mode: Option<Mode>,
+    pub open_flags: OpenFlags,
+}
+
+/// Flags the device is opened with that some adapters don't get along with.
+/// O_RDWR and O_NONBLOCK are always used, the reading relies on the latter.
+/// O_NDELAY is the same as O_NONBLOCK on Linux.
+#[derive(Clone, Copy, Debug)]
+pub struct OpenFlags {
+    /// O_SYNC, every write waits until the data has been handed to the driver.
+    pub sync: bool,
+    /// O_NOCTTY, the device doesn't become the controlling terminal of rterm.
+    pub noctty: bool,
+}
+
+impl Default for OpenFlags {
+    fn default() -> Self {
+        OpenFlags {
+            sync: true,
+            noctty: true,
+        }
+    }
+}
+
+impl OpenFlags {
+    fn oflag(self) -> OFlag {
+        let mut oflag = OFlag::O_RDWR | OFlag::O_NONBLOCK;
+        if self.sync {
+            oflag |= OFlag::O_SYNC;
+        }
+        if self.noctty {
+            oflag |= OFlag::O_NOCTTY;
+        }
+        oflag
+    }
+}
+
+/// The states of the modem status lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ModemStatus {
+    pub cts: bool,
+    pub dsr: bool,
+    pub dcd: bool,
+    pub ri: bool,
+}
 
 pub struct TerminalDevice {
     fd: i32,
@@ -30,9 +101,15 @@ pub struct TerminalWriter {
 }
 
 impl TerminalDevice {
-    pub fn new<P: Into<PathBuf>>(filepath: P) -> anyhow::Result<TerminalDevice> {
-        let oflag = OFlag::O_RDWR | OFlag::O_NOCTTY | OFlag::O_SYNC | OFlag::O_NONBLOCK;
-        let fd = open(&filepath.into(), oflag, nix::sys::stat::Mode::empty())?;
+    pub fn new<P: Into<PathBuf>>(
+        filepath: P,
+        open_flags: OpenFlags,
+    ) -> anyhow::Result<TerminalDevice> {
+        let fd = open(
+            &filepath.into(),
+            open_flags.oflag(),
+            nix::sys::stat::Mode::empty(),
+        )?;
         let termios = tcgetattr(fd)?;
         let _drop_handler = Arc::new(TerminalCloser { fd });
         Ok(TerminalDevice {
@@ -42,6 +119,118 @@ impl TerminalDevice {
         })
     }
 
+    /// Opens the device and configures it according to `config`.
+    pub fn open<P: Into<PathBuf>>(
+        filepath: P,
+        config: &SerialConfig,
+    ) -> anyhow::Result<TerminalDevice> {
+        let mut td = TerminalDevice::new(filepath, config.open_flags)?;
+        td.configure(config)?;
+        Ok(td)
+    }
+
+    pub fn configure(&mut self, config: &SerialConfig) -> anyhow::Result<()> {
+        match config.mode {
+            None => self.configure_for_arduino(config.baud_rate),
+            Some(Mode::Raw | Mode::Arduino) => {
+                self.make_raw(config.baud_rate)?;
+                self.apply()
+            }
+        }
+    }
+
+    /// Sets up the termios settings like cfmakeraw with 8N1 and the given baud rate.
+    fn make_raw(&mut self, baud_rate: BaudRate) -> anyhow::Result<()> {
+        cfmakeraw(&mut self.termios);
+        cfsetispeed(&mut self.termios, baud_rate)?;
+        cfsetospeed(&mut self.termios, baud_rate)?;
+        self.termios.input_flags &= !(InputFlags::IXON | InputFlags::IXOFF | InputFlags::INPCK);
+        // Remnants of earlier configurations that don't matter without OPOST and
+        // ECHO but make it harder to see what's going on.
+        self.termios.output_flags = OutputFlags::empty();
+        self.termios.local_flags &=
+            !(LocalFlags::ECHOE | LocalFlags::ECHOK | LocalFlags::ECHOKE | LocalFlags::ECHOCTL);
+        self.termios.control_flags &= !(ControlFlags::CSTOPB | ControlFlags::CRTSCTS);
+        self.termios.control_flags |=
+            ControlFlags::CS8 | ControlFlags::CREAD | ControlFlags::CLOCAL;
+        self.termios.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+        self.termios.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+        Ok(())
+    }
+
+    fn apply(&mut self) -> anyhow::Result<()> {
+        tcsetattr(self.fd, SetArg::TCSAFLUSH, &self.termios)?;
+        Ok(())
+    }
+
+    /// Sets the settings the device was configured with again, for drivers
+    /// and devices that reset them. Unlike configuring it, nothing that has
+    /// been received or written is thrown away, so it can be done on a handle
+    /// while the device is being read and written.
+    pub fn reapply_config(&self) -> anyhow::Result<()> {
+        tcsetattr(self.fd, SetArg::TCSANOW, &self.termios)?;
+        Ok(())
+    }
+
+    /// Another handle to the device with the same settings. It's closed
+    /// when all of them, and the parts it's been split into, are dropped.
+    pub fn handle(&self) -> TerminalDevice {
+        TerminalDevice {
+            fd: self.fd,
+            termios: self.termios.clone(),
+            _drop_handler: self._drop_handler.clone(),
+        }
+    }
+
+    /// Describes the settings the device currently has.
+    pub fn describe(&self) -> anyhow::Result<String> {
+        let termios = tcgetattr(self.fd)?;
+        let ispeed = format!("{:?}", cfgetispeed(&termios));
+        let ospeed = format!("{:?}", cfgetospeed(&termios));
+        let char_size = match termios.control_flags & ControlFlags::CSIZE {
+            ControlFlags::CS5 => 5,
+            ControlFlags::CS6 => 6,
+            ControlFlags::CS7 => 7,
+            _ => 8,
+        };
+        let parity = if !termios.control_flags.contains(ControlFlags::PARENB) {
+            "N"
+        } else if termios.control_flags.contains(ControlFlags::PARODD) {
+            "O"
+        } else {
+            "E"
+        };
+        let stop_bits = if termios.control_flags.contains(ControlFlags::CSTOPB) {
+            2
+        } else {
+            1
+        };
+        let control_flags = termios.control_flags & !ControlFlags::CSIZE;
+        let cc = |idx: SpecialCharacterIndices| termios.control_chars[idx as usize];
+        // Some flags are zero and would otherwise be listed even when nothing is set.
+        let output_flags = if termios.output_flags.is_empty() {
+            "(empty)".to_string()
+        } else {
+            format!("{:?}", termios.output_flags)
+        };
+        Ok(format!(
+            "speed:         {} in, {} out\n\
+             framing:       {char_size}{parity}{stop_bits}\n\
+             input flags:   {:?}\n\
+             output flags:  {output_flags}\n\
+             control flags: {:?}\n\
+             local flags:   {:?}\n\
+             VMIN = {}, VTIME = {}",
+            ispeed.trim_start_matches('B'),
+            ospeed.trim_start_matches('B'),
+            termios.input_flags,
+            control_flags,
+            termios.local_flags,
+            cc(SpecialCharacterIndices::VMIN),
+            cc(SpecialCharacterIndices::VTIME),
+        ))
+    }
+
     pub fn configure_for_arduino(&mut self, baud_rate: BaudRate) -> anyhow::Result<()> {
         cfsetispeed(&mut self.termios, baud_rate)?;
         cfsetospeed(&mut self.termios, baud_rate)?;
@@ -50,8 +239,13 @@ impl TerminalDevice {
             !(OutputFlags::ONLCR | OutputFlags::ONOCR | OutputFlags::OCRNL);
         self.termios.output_flags |= OutputFlags::ONLRET;
         self.termios.local_flags &= !(LocalFlags::ECHO | LocalFlags::ICANON);
-        self.termios.input_flags |= InputFlags::IGNCR;
-        self.termios.input_flags &= !(InputFlags::INPCK | InputFlags::ISTRIP);
+        // Carriage returns reach the app as they are, which ends lines with
+        // them and logs them with --raw-log.
+        self.termios.input_flags &= !(InputFlags::IGNCR
+            | InputFlags::ICRNL
+            | InputFlags::INLCR
+            | InputFlags::INPCK
+            | InputFlags::ISTRIP);
 
         self.termios.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
         self.termios.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
@@ -59,6 +253,34 @@ impl TerminalDevice {
         Ok(())
     }
 
+    /// Resets the device, like an arduino, by pulling DTR and RTS low for a moment.
+    pub fn pulse_reset(&self) -> io::Result<()> {
+        let lines: libc::c_int = libc::TIOCM_DTR | libc::TIOCM_RTS;
+        // Safety: TIOCMBIC and TIOCMBIS only read the int they're given.
+        if unsafe { libc::ioctl(self.fd, libc::TIOCMBIC, &lines) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        thread::sleep(RESET_PULSE);
+        if unsafe { libc::ioctl(self.fd, libc::TIOCMBIS, &lines) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub fn modem_status(&self) -> io::Result<ModemStatus> {
+        let mut lines: libc::c_int = 0;
+        // Safety: TIOCMGET only writes to the int it's given.
+        if unsafe { libc::ioctl(self.fd, libc::TIOCMGET, &mut lines) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(ModemStatus {
+            cts: lines & libc::TIOCM_CTS != 0,
+            dsr: lines & libc::TIOCM_DSR != 0,
+            dcd: lines & libc::TIOCM_CAR != 0,
+            ri: lines & libc::TIOCM_RNG != 0,
+        })
+    }
+
     /// Splits the device into a read and a write part.
     pub fn split(self) -> (TerminalReader, TerminalWriter) {
         (
@@ -76,31 +298,88 @@ impl TerminalDevice {
 
 impl io::Read for TerminalDevice {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        read(self.fd, buf).map_err(|e| io::Error::try_from(e).unwrap())
+        read(self.fd, buf).map_err(io::Error::from)
     }
 }
 
 impl io::Write for TerminalDevice {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        write(self.fd, buf).map_err(|e| io::Error::try_from(e).unwrap())
+        write(self.fd, buf).map_err(io::Error::from)
     }
     fn flush(&mut self) -> io::Result<()> {
-        tcflush(self.fd, FlushArg::TCIOFLUSH).map_err(|e| io::Error::try_from(e).unwrap())
+        tcflush(self.fd, FlushArg::TCIOFLUSH).map_err(io::Error::from)
     }
 }
 
 impl io::Write for TerminalWriter {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        write(self.fd, buf).map_err(|e| io::Error::try_from(e).unwrap())
+        write(self.fd, buf).map_err(io::Error::from)
     }
     fn flush(&mut self) -> io::Result<()> {
-        tcflush(self.fd, FlushArg::TCIOFLUSH).map_err(|e| io::Error::try_from(e).unwrap())
+        tcflush(self.fd, FlushArg::TCIOFLUSH).map_err(io::Error::from)
     }
 }
 
 impl io::Read for TerminalReader {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        read(self.fd, buf).map_err(|e| io::Error::try_from(e).unwrap())
+        read(self.fd, buf).map_err(io::Error::from)
+    }
+}
+
+/// The serial_icounter_struct filled in by TIOCGICOUNT.
+#[repr(C)]
+#[derive(Default)]
+struct SerialIcounter {
+    cts: libc::c_int,
+    dsr: libc::c_int,
+    rng: libc::c_int,
+    dcd: libc::c_int,
+    rx: libc::c_int,
+    tx: libc::c_int,
+    frame: libc::c_int,
+    overrun: libc::c_int,
+    parity: libc::c_int,
+    brk: libc::c_int,
+    buf_overrun: libc::c_int,
+    reserved: [libc::c_int; 9],
+}
+
+impl TerminalWriter {
+    /// The number of bytes written that the device hasn't transmitted yet.
+    pub fn queued(&self) -> io::Result<usize> {
+        let mut queued: libc::c_int = 0;
+        // Safety: TIOCOUTQ only writes to the int it's given.
+        if unsafe { libc::ioctl(self.fd, libc::TIOCOUTQ, &mut queued) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(queued.max(0) as usize)
+    }
+}
+
+impl TerminalReader {
+    /// Waits until there is something to read, or the device has been hung
+    /// up, for at most `timeout`. Returns whether it was woken up before the
+    /// timeout.
+    pub fn wait(&self, timeout: Duration) -> io::Result<bool> {
+        let mut fds = [PollFd::new(self.fd, PollFlags::POLLIN)];
+        match poll(&mut fds, timeout.as_millis() as libc::c_int) {
+            Ok(n) => Ok(n > 0),
+            // A signal is taken as a timeout, so the caller checks again.
+            Err(nix::errno::Errno::EINTR) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The number of bytes the driver has reported lost to overruns so far,
+    /// or `None` if the driver doesn't count them, like ptys.
+    pub fn overruns(&self) -> Option<u64> {
+        let mut icount = SerialIcounter::default();
+        // Safety: TIOCGICOUNT only writes to the struct it's given, which has the kernel's layout.
+        let res = unsafe { libc::ioctl(self.fd, libc::TIOCGICOUNT, &mut icount) };
+        if res < 0 {
+            return None;
+        }
+        Some(icount.overrun as u64 + icount.buf_overrun as u64)
     }
 }
 
@@ -0,0 +1,162 @@
+use std::{
+    ffi::CString,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use nix::libc;
+
+/// Converts an `Instant` from this session to wall-clock time.
+pub fn to_system_time(instant: Instant) -> SystemTime {
+    let now = Instant::now();
+    if instant <= now {
+        SystemTime::now() - (now - instant)
+    } else {
+        SystemTime::now() + (instant - now)
+    }
+}
+
+/// Breaks down `time` into its parts in the local time zone.
+pub fn local_tm(time: SystemTime) -> libc::tm {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs() as libc::time_t;
+    // Safety: localtime_r only writes to the tm struct it's given.
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&secs, &mut tm);
+        tm
+    }
+}
+
+/// Breaks down `time` into its parts in UTC.
+fn utc_tm(time: SystemTime) -> libc::tm {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs() as libc::time_t;
+    // Safety: gmtime_r only writes to the tm struct it's given.
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::gmtime_r(&secs, &mut tm);
+        tm
+    }
+}
+
+extern "C" {
+    // Not bound by the libc crate.
+    fn strftime(
+        s: *mut libc::c_char,
+        max: libc::size_t,
+        format: *const libc::c_char,
+        tm: *const libc::tm,
+    ) -> libc::size_t;
+}
+
+/// Formats `time` in the local time zone like strftime, e.g. "%Y%m%d".
+pub fn format_local(format: &str, time: SystemTime) -> String {
+    format_tm(format, &local_tm(time))
+}
+
+fn format_tm(format: &str, tm: &libc::tm) -> String {
+    let Ok(c_format) = CString::new(format) else {
+        return format.to_string();
+    };
+    let mut buf = [0u8; 512];
+    // Safety: strftime writes at most buf.len() bytes, including the NUL.
+    let len = unsafe {
+        strftime(
+            buf.as_mut_ptr() as *mut libc::c_char,
+            buf.len(),
+            c_format.as_ptr(),
+            tm,
+        )
+    };
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+/// Formats `time` as ISO 8601 with milliseconds, e.g.
+/// "2024-06-10T12:03:01.123+02:00" or "2024-06-10T10:03:01.123Z" in UTC.
+pub fn iso8601(time: SystemTime, utc: bool) -> String {
+    let millis = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .subsec_millis();
+    if utc {
+        return format!(
+            "{}.{millis:03}Z",
+            format_tm("%Y-%m-%dT%H:%M:%S", &utc_tm(time))
+        );
+    }
+    let tm = local_tm(time);
+    // strftime gives the offset as e.g. "+0200".
+    let mut offset = format_tm("%z", &tm);
+    if offset.len() == 5 {
+        offset.insert(3, ':');
+    }
+    format!(
+        "{}.{millis:03}{offset}",
+        format_tm("%Y-%m-%dT%H:%M:%S", &tm)
+    )
+}
+
+/// Formats the time of day, e.g. "12:03:01.123".
+pub fn clock_time(time: SystemTime) -> String {
+    let tm = local_tm(time);
+    let millis = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .subsec_millis();
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        tm.tm_hour, tm.tm_min, tm.tm_sec, millis
+    )
+}
+
+/// Durations from this long are shown as hours, minutes and seconds, so that
+/// they fit in the timestamp columns.
+const LONG_DURATION: Duration = Duration::from_secs(10_000);
+
+/// Formats a duration as seconds, e.g. "73.120s", or as "2:46:40" once it's
+/// `LONG_DURATION` or longer.
+fn seconds(duration: Duration) -> String {
+    if duration < LONG_DURATION {
+        return format!("{:.3}s", duration.as_secs_f64());
+    }
+    let secs = duration.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Formats the time since `origin`, e.g. "+73.120s", or "-0.250s" before it.
+pub fn relative(time: Instant, origin: Instant) -> String {
+    match time.checked_duration_since(origin) {
+        Some(since) => format!("+{}", seconds(since)),
+        None => format!("-{}", seconds(origin - time)),
+    }
+}
+
+/// Formats the time between two lines, e.g. "+12.345ms" or "+2.500s".
+pub fn delta(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        format!("+{:.3}ms", duration.as_secs_f64() * 1000.0)
+    } else {
+        format!("+{}", seconds(duration))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_durations_are_shown_in_hours() {
+        let origin = Instant::now();
+        let after = |secs| relative(origin + Duration::from_secs_f64(secs), origin);
+        assert_eq!(after(73.12), "+73.120s");
+        assert_eq!(after(9999.5), "+9999.500s");
+        assert_eq!(after(10_000.0), "+2:46:40");
+        assert_eq!(after(360_000.0), "+100:00:00");
+        assert_eq!(delta(Duration::from_millis(250)), "+250.000ms");
+        assert_eq!(delta(Duration::from_secs(90_061)), "+25:01:01");
+    }
+}
@@ -0,0 +1,181 @@
+use std::{
+    fs::File,
+    io,
+    os::unix::process::CommandExt,
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context};
+use regex::Regex;
+
+/// The least time between two runs of the command of a rule. Lines that
+/// match in between are only counted.
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+/// The most commands that run at the same time. Lines that match while as
+/// many are running are only counted.
+const MAX_RUNNING: usize = 4;
+
+/// A command that is run when a received line matches a regex.
+#[derive(Debug)]
+pub struct Rule {
+    pub pattern: Regex,
+    pub command: String,
+    /// The number of lines that have matched.
+    pub matches: usize,
+    last_run: Option<Instant>,
+}
+
+impl Rule {
+    /// Parses a rule given as "regex:command", e.g. "FAILED:notify-send failed".
+    /// The regex ends at the first colon, `\x3A` matches one.
+    pub fn parse(s: &str) -> anyhow::Result<Rule> {
+        let (regex, command) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow!("invalid rule '{s}', expected regex:command"))?;
+        if command.trim().is_empty() {
+            return Err(anyhow!("invalid rule '{s}', the command is empty"));
+        }
+        Ok(Rule {
+            pattern: Regex::new(regex).context(format!("invalid regex in rule '{s}'"))?,
+            command: command.to_string(),
+            matches: 0,
+            last_run: None,
+        })
+    }
+}
+
+/// Runs the commands of the rules that received lines match, detached from
+/// the terminal.
+pub struct Triggers {
+    pub rules: Vec<Rule>,
+    /// Where the output of the commands is appended, it's discarded without one.
+    output: Option<File>,
+    running: Arc<AtomicUsize>,
+    failures_tx: Sender<String>,
+    failures: Receiver<String>,
+}
+
+impl Triggers {
+    pub fn new(rules: Vec<Rule>, output: Option<File>) -> Self {
+        let (failures_tx, failures) = mpsc::channel();
+        Triggers {
+            rules,
+            output,
+            running: Arc::new(AtomicUsize::new(0)),
+            failures_tx,
+            failures,
+        }
+    }
+
+    /// Counts the rules `line` matches and runs their commands with the line
+    /// in RTERM_LINE. Returns why commands couldn't be started.
+    pub fn check(&mut self, line: &str) -> Vec<String> {
+        let mut due = Vec::new();
+        for rule in &mut self.rules {
+            if !rule.pattern.is_match(line) {
+                continue;
+            }
+            rule.matches += 1;
+            let now = Instant::now();
+            if rule.last_run.is_some_and(|last| now - last < MIN_INTERVAL)
+                || self.running.load(Ordering::Relaxed) + due.len() >= MAX_RUNNING
+            {
+                continue;
+            }
+            rule.last_run = Some(now);
+            due.push(rule.command.clone());
+        }
+        due.into_iter()
+            .filter_map(|command| {
+                let e = self.spawn(&command, line).err()?;
+                Some(format!("couldn't run '{command}': {e}"))
+            })
+            .collect()
+    }
+
+    /// The commands that have failed since this was last called.
+    pub fn failures(&self) -> Vec<String> {
+        self.failures.try_iter().collect()
+    }
+
+    fn spawn(&self, command: &str, line: &str) -> io::Result<()> {
+        let (stdout, stderr) = match &self.output {
+            Some(file) => (file.try_clone()?.into(), file.try_clone()?.into()),
+            None => (Stdio::null(), Stdio::null()),
+        };
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("RTERM_LINE", line)
+            .stdin(Stdio::null())
+            .stdout(stdout)
+            .stderr(stderr)
+            // Signals meant for rterm's terminal, like the hangup when it's
+            // closed, don't reach it.
+            .process_group(0)
+            .spawn()?;
+        self.running.fetch_add(1, Ordering::Relaxed);
+        let running = self.running.clone();
+        let failures = self.failures_tx.clone();
+        let command = command.to_string();
+        thread::spawn(move || {
+            match child.wait() {
+                Ok(status) if status.success() => {}
+                Ok(status) => {
+                    let _ = failures.send(format!("'{command}' failed, {status}"));
+                }
+                Err(e) => {
+                    let _ = failures.send(format!("waiting for '{command}': {e}"));
+                }
+            }
+            running.fetch_sub(1, Ordering::Relaxed);
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, thread, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn parses_rules() {
+        let rule = Rule::parse(r"FAILED \d+:notify-send 'x: y'").unwrap();
+        assert_eq!(rule.pattern.as_str(), r"FAILED \d+");
+        assert_eq!(rule.command, "notify-send 'x: y'");
+        for invalid in ["FAILED", "FAILED: ", "(:true"] {
+            assert!(Rule::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn runs_commands_at_most_once_a_second() {
+        let path = std::env::temp_dir().join(format!("rterm-trigger-{}", std::process::id()));
+        let output = File::create(&path).unwrap();
+        let rules = vec![
+            Rule::parse(r#"FAIL:echo "got $RTERM_LINE""#).unwrap(),
+            Rule::parse("OK:exit 3").unwrap(),
+        ];
+        let mut triggers = Triggers::new(rules, Some(output));
+        for line in ["FAIL 1", "OK", "FAIL 2", "other"] {
+            assert!(triggers.check(line).is_empty());
+        }
+        let counts: Vec<usize> = triggers.rules.iter().map(|rule| rule.matches).collect();
+        assert_eq!(counts, [2, 1]);
+        while triggers.running.load(Ordering::Relaxed) > 0 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "got FAIL 1\n");
+        assert_eq!(triggers.failures(), ["'exit 3' failed, exit status: 3"]);
+        fs::remove_file(path).unwrap();
+    }
+}
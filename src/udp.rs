@@ -0,0 +1,130 @@
+use std::{
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::SystemTime,
+};
+
+use anyhow::anyhow;
+
+use crate::jsonl::{self, Direction};
+
+/// Parses a target given as "host:port", resolving the host.
+pub fn parse_target(s: &str) -> anyhow::Result<SocketAddr> {
+    s.to_socket_addrs()
+        .map_err(|e| anyhow!("invalid target '{s}': {e}"))?
+        .next()
+        .ok_or_else(|| anyhow!("'{s}' doesn't resolve to an address"))
+}
+
+/// Sends every received line as a UDP datagram to each target, for tools on
+/// other machines that only listen. Broadcast addresses can be targets too.
+pub struct UdpForward {
+    targets: Vec<SocketAddr>,
+    /// One for each address family the targets are in.
+    v4: Option<UdpSocket>,
+    v6: Option<UdpSocket>,
+    /// Send the lines as the JSON objects of the --jsonl log.
+    json: bool,
+    /// The datagrams that couldn't be sent.
+    pub failures: usize,
+}
+
+impl UdpForward {
+    pub fn new(targets: Vec<SocketAddr>, json: bool) -> io::Result<Self> {
+        let bind = |any: &str| -> io::Result<UdpSocket> {
+            let socket = UdpSocket::bind(any)?;
+            socket.set_broadcast(true)?;
+            // A datagram that can't be sent right away is dropped rather than
+            // holding up the reception.
+            socket.set_nonblocking(true)?;
+            Ok(socket)
+        };
+        let v4 = targets
+            .iter()
+            .any(SocketAddr::is_ipv4)
+            .then(|| bind("0.0.0.0:0"));
+        let v6 = targets
+            .iter()
+            .any(SocketAddr::is_ipv6)
+            .then(|| bind("[::]:0"));
+        Ok(UdpForward {
+            targets,
+            v4: v4.transpose()?,
+            v6: v6.transpose()?,
+            json,
+            failures: 0,
+        })
+    }
+
+    /// Sends `line`, received at `time`. Returns why sending failed if it's
+    /// the first failure, later ones are only counted.
+    pub fn send(&mut self, line: &str, time: SystemTime) -> Option<String> {
+        let datagram = if self.json {
+            jsonl::object(Direction::Rx, time, line)
+        } else {
+            line.to_string()
+        };
+        let mut error = None;
+        for target in &self.targets {
+            let socket = match target {
+                SocketAddr::V4(_) => &self.v4,
+                SocketAddr::V6(_) => &self.v6,
+            };
+            let Some(socket) = socket else {
+                continue;
+            };
+            if let Err(e) = socket.send_to(datagram.as_bytes(), target) {
+                if self.failures == 0 {
+                    error = Some(format!(
+                        "forwarding to {target}: {e}, further failures are only counted"
+                    ));
+                }
+                self.failures += 1;
+            }
+        }
+        error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+
+    #[test]
+    fn forwards_lines_to_every_target() {
+        let receivers: Vec<UdpSocket> = (0..2)
+            .map(|_| UdpSocket::bind("127.0.0.1:0").unwrap())
+            .collect();
+        let targets: Vec<SocketAddr> = receivers.iter().map(|r| r.local_addr().unwrap()).collect();
+        for (json, expected) in [
+            (false, "T=21.5"),
+            (true, r#"{"t":1.500,"dir":"rx","line":"T=21.5"}"#),
+        ] {
+            let mut forward = UdpForward::new(targets.clone(), json).unwrap();
+            let time = UNIX_EPOCH + Duration::from_millis(1500);
+            assert_eq!(forward.send("T=21.5", time), None);
+            for receiver in &receivers {
+                receiver
+                    .set_read_timeout(Some(Duration::from_secs(5)))
+                    .unwrap();
+                let mut buf = [0; 256];
+                let n = receiver.recv(&mut buf).unwrap();
+                assert_eq!(std::str::from_utf8(&buf[..n]).unwrap(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn failures_are_reported_once() {
+        let target = parse_target("127.0.0.1:9").unwrap();
+        let mut forward = UdpForward::new(vec![target], false).unwrap();
+        // Too long for a datagram.
+        let line = "x".repeat(70_000);
+        let error = forward.send(&line, SystemTime::now()).unwrap();
+        assert!(error.starts_with("forwarding to 127.0.0.1:9: "), "{error}");
+        assert_eq!(forward.send(&line, SystemTime::now()), None);
+        assert_eq!(forward.failures, 2);
+    }
+}
@@ -0,0 +1,96 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::wraptext::Lines;
+
+/// What the keys do with --vim.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Keys go to the input box, like without --vim.
+    Insert,
+    /// Keys move a cursor through the output and nothing is sent.
+    Normal,
+    /// Like normal mode, with the lines from `anchor` to the cursor selected.
+    Visual { anchor: usize },
+}
+
+/// What a key does in normal and visual mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Down,
+    Up,
+    HalfPageDown,
+    HalfPageUp,
+    Top,
+    Bottom,
+    /// Opens the search prompt.
+    Search,
+    NextMatch,
+    PreviousMatch,
+    /// Starts or stops selecting lines.
+    Visual,
+    /// Copies the selected lines, or the cursor line if none are.
+    Yank,
+    Insert,
+    /// Opens the command palette.
+    Command,
+    /// Sends the last entered line again.
+    SendAgain,
+    /// Stops selecting lines.
+    Cancel,
+}
+
+/// Maps keys to actions, remembering the first key of "gg".
+#[derive(Default)]
+pub struct Keymap {
+    pending_g: bool,
+}
+
+impl Keymap {
+    /// The action of `key`, or `None` if it does nothing on its own.
+    pub fn action(&mut self, key: KeyEvent) -> Option<Action> {
+        let pending_g = std::mem::take(&mut self.pending_g);
+        let ctrl = key.modifiers == KeyModifiers::CONTROL;
+        if !ctrl && !(key.modifiers - KeyModifiers::SHIFT).is_empty() {
+            return None;
+        }
+        let action = match key.code {
+            KeyCode::Char('d') if ctrl => Action::HalfPageDown,
+            KeyCode::Char('u') if ctrl => Action::HalfPageUp,
+            _ if ctrl => return None,
+            KeyCode::Char('j') | KeyCode::Down => Action::Down,
+            KeyCode::Char('k') | KeyCode::Up => Action::Up,
+            KeyCode::Char('g') if pending_g => Action::Top,
+            KeyCode::Char('g') => {
+                self.pending_g = true;
+                return None;
+            }
+            KeyCode::Char('G') => Action::Bottom,
+            KeyCode::Char('/') => Action::Search,
+            KeyCode::Char('n') => Action::NextMatch,
+            KeyCode::Char('N') => Action::PreviousMatch,
+            KeyCode::Char('v') | KeyCode::Char('V') => Action::Visual,
+            KeyCode::Char('y') => Action::Yank,
+            KeyCode::Char('i') => Action::Insert,
+            KeyCode::Char(':') => Action::Command,
+            KeyCode::Char('.') => Action::SendAgain,
+            KeyCode::Esc => Action::Cancel,
+            _ => return None,
+        };
+        Some(action)
+    }
+}
+
+/// The next line after `cursor` that contains `pattern`, or the previous one
+/// before it if not `forward`, continuing from the other end if needed.
+pub fn find(lines: Lines, pattern: &str, cursor: usize, forward: bool) -> Option<usize> {
+    let len = lines.len();
+    (1..=len)
+        .map(|step| {
+            if forward {
+                (cursor + step) % len
+            } else {
+                (cursor + len - step % len) % len
+            }
+        })
+        .find(|&idx| lines.get(idx).text.contains(pattern))
+}
@@ -0,0 +1,281 @@
+use tui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Modifier, Style},
+    widgets::{Block, Widget},
+};
+
+/// The size of the emulated screen, which menu-driven serial UIs assume.
+pub const COLUMNS: usize = 80;
+pub const ROWS: usize = 24;
+/// The most parameter bytes of an escape sequence that are kept. Longer
+/// sequences are skipped, so that noise can't grow them without end.
+const MAX_PARAMS: usize = 32;
+
+/// Where the parser is in an escape sequence.
+enum State {
+    Ground,
+    /// After ESC.
+    Escape,
+    /// After ESC [, with the parameter bytes so far.
+    Csi(String),
+    /// In a sequence with too many parameter bytes, until its final byte.
+    CsiIgnore,
+}
+
+/// A screen that received text is drawn on like on a VT100, for devices that
+/// position their output with escape sequences. Only moving the cursor,
+/// clearing the screen and erasing lines are understood, other sequences
+/// are skipped.
+pub struct Screen {
+    cells: Vec<Vec<char>>,
+    row: usize,
+    col: usize,
+    saved: (usize, usize),
+    state: State,
+    /// The bytes of a UTF-8 character that hasn't been completed yet.
+    utf8: Vec<u8>,
+}
+
+impl Screen {
+    pub fn new() -> Self {
+        Screen {
+            cells: vec![vec![' '; COLUMNS]; ROWS],
+            row: 0,
+            col: 0,
+            saved: (0, 0),
+            state: State::Ground,
+            utf8: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, byte: u8) {
+        match std::mem::replace(&mut self.state, State::Ground) {
+            State::Ground => self.ground(byte),
+            State::Escape => match byte {
+                b'[' => self.state = State::Csi(String::new()),
+                b'c' => *self = Screen::new(),
+                b'7' => self.saved = (self.row, self.col),
+                b'8' => (self.row, self.col) = self.saved,
+                // Other sequences are two bytes or are unlikely to matter.
+                _ => {}
+            },
+            State::Csi(mut params) => match byte {
+                0x20..=0x3F if params.len() >= MAX_PARAMS => self.state = State::CsiIgnore,
+                0x20..=0x3F => {
+                    params.push(byte as char);
+                    self.state = State::Csi(params);
+                }
+                0x40..=0x7E => self.csi(&params, byte),
+                // A control character cancels the sequence.
+                _ => self.ground(byte),
+            },
+            State::CsiIgnore => match byte {
+                0x20..=0x3F => self.state = State::CsiIgnore,
+                0x40..=0x7E => {}
+                _ => self.ground(byte),
+            },
+        }
+    }
+
+    fn ground(&mut self, byte: u8) {
+        match byte {
+            0x1B => self.state = State::Escape,
+            b'\r' => self.col = 0,
+            b'\n' | 0x0B | 0x0C => self.line_feed(),
+            0x08 => self.col = self.col.saturating_sub(1),
+            b'\t' => self.col = ((self.col / 8 + 1) * 8).min(COLUMNS - 1),
+            0x00..=0x1F | 0x7F => {}
+            0x20..=0x7E => self.put(byte as char),
+            _ => {
+                self.utf8.push(byte);
+                match std::str::from_utf8(&self.utf8) {
+                    Ok(s) => {
+                        let c = s.chars().next().unwrap();
+                        self.utf8.clear();
+                        self.put(c);
+                    }
+                    // Waits for the rest of the character.
+                    Err(e) if e.error_len().is_none() => {}
+                    Err(_) => {
+                        self.utf8.clear();
+                        self.put(char::REPLACEMENT_CHARACTER);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Writes `c` at the cursor, continuing on the next row at the end of one.
+    fn put(&mut self, c: char) {
+        if self.col >= COLUMNS {
+            self.col = 0;
+            self.line_feed();
+        }
+        self.cells[self.row][self.col] = c;
+        self.col += 1;
+    }
+
+    /// Moves the cursor down, scrolling the screen up at the bottom.
+    fn line_feed(&mut self) {
+        if self.row + 1 < ROWS {
+            self.row += 1;
+        } else {
+            self.cells.remove(0);
+            self.cells.push(vec![' '; COLUMNS]);
+        }
+    }
+
+    fn csi(&mut self, params: &str, final_byte: u8) {
+        // Private sequences, e.g. hiding the cursor with ESC [ ? 25 l.
+        if params.starts_with(['?', '<', '=', '>']) {
+            return;
+        }
+        let numbers: Vec<usize> = params.split(';').map(|n| n.parse().unwrap_or(0)).collect();
+        // Missing and zero counts and positions mean 1.
+        let n = |idx: usize| numbers.get(idx).copied().unwrap_or(0).max(1);
+        match final_byte {
+            b'A' => self.row = self.row.saturating_sub(n(0)),
+            b'B' => self.row = self.row.saturating_add(n(0)).min(ROWS - 1),
+            b'C' => self.col = self.col.saturating_add(n(0)).min(COLUMNS - 1),
+            b'D' => self.col = self.col.min(COLUMNS - 1).saturating_sub(n(0)),
+            b'H' | b'f' => {
+                self.row = n(0).min(ROWS) - 1;
+                self.col = n(1).min(COLUMNS) - 1;
+            }
+            b'G' => self.col = n(0).min(COLUMNS) - 1,
+            b'd' => self.row = n(0).min(ROWS) - 1,
+            b'J' => {
+                let (row, col) = (self.row, self.col.min(COLUMNS));
+                match numbers[0] {
+                    0 => {
+                        self.cells[row][col..].fill(' ');
+                        self.cells[row + 1..].iter_mut().for_each(|r| r.fill(' '));
+                    }
+                    1 => {
+                        self.cells[..row].iter_mut().for_each(|r| r.fill(' '));
+                        self.cells[row][..(col + 1).min(COLUMNS)].fill(' ');
+                    }
+                    _ => self.cells.iter_mut().for_each(|r| r.fill(' ')),
+                }
+            }
+            b'K' => {
+                let col = self.col.min(COLUMNS);
+                let line = &mut self.cells[self.row];
+                match numbers[0] {
+                    0 => line[col..].fill(' '),
+                    1 => line[..(col + 1).min(COLUMNS)].fill(' '),
+                    _ => line.fill(' '),
+                }
+            }
+            b's' => self.saved = (self.row, self.col),
+            b'u' => (self.row, self.col) = self.saved,
+            // Colors and everything else.
+            _ => {}
+        }
+    }
+
+    /// The text of every row of the screen.
+    pub fn rows(&self) -> impl Iterator<Item = String> + '_ {
+        self.cells.iter().map(|row| row.iter().collect())
+    }
+
+    pub fn widget<'a>(&'a self, block: Option<Block<'a>>) -> ScreenWidget<'a> {
+        ScreenWidget {
+            screen: self,
+            block,
+        }
+    }
+}
+
+pub struct ScreenWidget<'a> {
+    screen: &'a Screen,
+    block: Option<Block<'a>>,
+}
+
+impl Widget for ScreenWidget<'_> {
+    /// Draws the top left of the screen if the area is smaller than it.
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let area = match self.block {
+            Some(block) => {
+                let inner = block.inner(area);
+                block.render(area, buf);
+                inner
+            }
+            None => area,
+        };
+        for (y, row) in (area.top()..area.bottom()).zip(self.screen.rows()) {
+            buf.set_stringn(area.x, y, row, area.width as usize, Style::default());
+        }
+        let screen = self.screen;
+        let (x, y) = (screen.col.min(COLUMNS - 1), screen.row);
+        if x < area.width as usize && y < area.height as usize {
+            let cell = buf.get_mut(area.x + x as u16, area.y + y as u16);
+            cell.set_style(Style::default().add_modifier(Modifier::REVERSED));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen(bytes: &[u8]) -> Screen {
+        let mut screen = Screen::new();
+        bytes.iter().for_each(|&byte| screen.push(byte));
+        screen
+    }
+
+    fn row(screen: &Screen, idx: usize) -> String {
+        screen.rows().nth(idx).unwrap().trim_end().to_string()
+    }
+
+    #[test]
+    fn positions_the_text() {
+        let screen = screen(b"junk\x1b[2J\x1b[Hmenu\r\n1) run\x1b[5;10Hx\x1b[2AB");
+        assert_eq!(row(&screen, 0), "menu");
+        assert_eq!(row(&screen, 1), "1) run");
+        assert_eq!(row(&screen, 2), "          B");
+        assert_eq!(row(&screen, 4), "         x");
+        assert_eq!((screen.row, screen.col), (2, 11));
+    }
+
+    #[test]
+    fn erases_lines() {
+        let screen = screen(b"abcdef\x1b[3D\x1b[K\r\nabcdef\x1b[3D\x1b[1K\r\nabc\x1b[2K");
+        assert_eq!(row(&screen, 0), "abc");
+        assert_eq!(row(&screen, 1), "    ef");
+        assert_eq!(row(&screen, 2), "");
+    }
+
+    #[test]
+    fn wraps_and_scrolls() {
+        let mut bytes = vec![b'x'; COLUMNS + 1];
+        bytes.extend(b"\r\n".repeat(ROWS - 1));
+        bytes.extend("µ\x1b[31mend\x1b[0m\x1b[?25l".as_bytes());
+        let screen = screen(&bytes);
+        assert_eq!(row(&screen, 0), "x");
+        assert_eq!(row(&screen, ROWS - 1), "µend");
+    }
+
+    #[test]
+    fn huge_counts_stop_at_the_edges() {
+        let mut screen = screen(b"\x1b[18446744073709551615B\x1b[18446744073709551615C");
+        assert_eq!((screen.row, screen.col), (ROWS - 1, COLUMNS - 1));
+        screen.push(b'x');
+        assert_eq!(
+            row(&screen, ROWS - 1),
+            format!("{}x", " ".repeat(COLUMNS - 1))
+        );
+    }
+
+    #[test]
+    fn skips_sequences_with_too_many_parameters() {
+        let mut bytes = b"ab\x1b[".to_vec();
+        bytes.extend(b"1;".repeat(1000));
+        bytes.extend(b"Hc\x1b[1;1Hd");
+        let screen = screen(&bytes);
+        assert_eq!(row(&screen, 0), "dbc");
+        assert!(matches!(screen.state, State::Ground));
+    }
+}
@@ -1,9 +1,17 @@
+use std::{
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use clap::ArgEnum;
 use tui::{
     layout::Rect,
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     widgets::{Block, StatefulWidget, Widget},
 };
 
+use crate::timefmt;
+
 #[derive(Clone, Copy, Debug)]
 pub enum Position {
     At(i32, i32), // At(line index, offset from bottom of line)
@@ -12,31 +20,462 @@ pub enum Position {
 
 #[derive(Clone, Copy, Debug)]
 pub enum Movement {
-    ScrollUp,
-    ScrollDown,
+    /// Scrolls this many rows at once, stopping where single rows would.
+    ScrollUpN(u16),
+    ScrollDownN(u16),
     Follow,
+    JumpTo(usize),
+    /// Scrolls just far enough for the whole line to be shown.
+    Reveal(usize),
+    /// Follows the latest lines again if the view has been scrolled to the bottom.
+    FollowAtBottom,
+}
+
+/// Shown in front of bookmarked lines.
+pub const BOOKMARK: char = '▸';
+
+/// Why a row of the scrollbar is marked, by the most important of the lines
+/// it stands for.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Mark {
+    Highlighted,
+    /// Contains what was last searched for.
+    Match,
+    Bookmarked,
+}
+
+impl Mark {
+    fn color(self) -> Color {
+        match self {
+            Mark::Highlighted => Color::Cyan,
+            Mark::Match => Color::LightMagenta,
+            Mark::Bookmarked => Color::Yellow,
+        }
+    }
+}
+
+/// Marks where a truncated word is cut off.
+const ELLIPSIS: char = '…';
+
+/// The number of columns truncated words are scrolled at a time.
+const H_SCROLL_STEP: usize = 8;
+
+/// Shown in front of the rows a line continues on when they're indented.
+const CONTINUATION: char = '↳';
+
+/// The fewest columns left for the text before the line numbers and
+/// timestamps are left out.
+const MIN_TEXT_COLUMNS: usize = 8;
+
+/// Where lines wider than the output are broken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum)]
+pub enum Wrap {
+    /// After the last character that fits.
+    Char,
+    /// Between words.
+    Word,
+    /// Not at all, lines wider than the output are cut off and scrolled
+    /// sideways.
+    Off,
+}
+
+/// What word wrapping does with a word that's wider than a whole row.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum)]
+pub enum LongWords {
+    /// Break it over several rows.
+    Break,
+    /// Keep it on one row, cut off with an ellipsis, and scroll it sideways.
+    Truncate,
+}
+
+/// How lines are broken into rows. Used both for drawing and for the scroll
+/// math, so that the two agree on the height of every line.
+#[derive(Clone, Copy, Debug)]
+pub struct Wrapping {
+    pub wrap: Wrap,
+    pub long_words: LongWords,
+    /// Indent the rows a line continues on past the line numbers and
+    /// timestamps, behind a marker.
+    pub indent: bool,
+    /// Wrap the text at this column even if there's room for more, leaving
+    /// the rest of the output blank.
+    pub column: Option<usize>,
+}
+
+/// A row of a wrapped line, given as the range of characters shown on it.
+/// The characters in front of the text, e.g. the line number, are counted
+/// as part of the line.
+#[derive(Clone, Debug)]
+struct Row {
+    chars: Range<usize>,
+    /// The row holds a word that's too wide for it and only part of it is shown.
+    truncated: bool,
+    /// The number of columns left empty in front of the row, apart from the marker.
+    indent: usize,
+}
+
+impl Row {
+    fn new(chars: Range<usize>) -> Self {
+        Row {
+            chars,
+            truncated: false,
+            indent: 0,
+        }
+    }
+}
+
+/// What is shown in the timestamp column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum)]
+pub enum Timestamps {
+    Off,
+    /// The time of day the line arrived.
+    Absolute,
+    /// The time since the previous line arrived.
+    Delta,
+    /// The time since the program started or the origin was last reset with Alt+z.
+    Relative,
+}
+
+impl Timestamps {
+    pub fn next(self) -> Self {
+        match self {
+            Timestamps::Off => Timestamps::Absolute,
+            Timestamps::Absolute => Timestamps::Delta,
+            Timestamps::Delta => Timestamps::Relative,
+            Timestamps::Relative => Timestamps::Off,
+        }
+    }
+
+    fn width(self) -> usize {
+        match self {
+            Timestamps::Off => 0,
+            Timestamps::Absolute => 13,
+            Timestamps::Delta => 11,
+            Timestamps::Relative => 12,
+        }
+    }
+}
+
+/// A line of output together with how it's displayed.
+#[derive(Clone, Debug, Default)]
+pub struct Line {
+    pub text: String,
+    pub style: Style,
+    /// When the first byte of the line arrived.
+    pub time: Option<Instant>,
+    /// Styles applied on top of `style` to ranges of characters.
+    pub highlights: Vec<(Range<usize>, Style)>,
+    pub bookmarked: bool,
+}
+
+impl Line {
+    pub fn new(text: String, style: Style) -> Self {
+        Line {
+            text,
+            style,
+            time: None,
+            highlights: Vec::new(),
+            bookmarked: false,
+        }
+    }
+
+    /// The text of the line as it's written to files, with bookmarks flagged.
+    pub fn saved_text(&self) -> String {
+        if self.bookmarked {
+            format!("{BOOKMARK} {}", self.text)
+        } else {
+            self.text.clone()
+        }
+    }
+}
+
+/// The lines that are drawn and scrolled through: all of them, or only the
+/// ones at some indices, like those that match the filters.
+#[derive(Clone, Copy)]
+pub struct Lines<'a> {
+    all: &'a [Line],
+    shown: Option<&'a [usize]>,
+}
+
+impl<'a> Lines<'a> {
+    pub fn new(all: &'a [Line], shown: Option<&'a [usize]>) -> Self {
+        Lines { all, shown }
+    }
+
+    pub fn len(self) -> usize {
+        self.shown.map_or(self.all.len(), <[usize]>::len)
+    }
+
+    /// The line at `idx` among the shown ones.
+    pub fn get(self, idx: usize) -> &'a Line {
+        match self.shown {
+            Some(shown) => &self.all[shown[idx]],
+            None => &self.all[idx],
+        }
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(self) -> impl DoubleEndedIterator<Item = &'a Line> {
+        (0..self.len()).map(move |idx| self.get(idx))
+    }
+
+    /// Only the first `len` of the shown lines.
+    fn first(self, len: usize) -> Self {
+        match self.shown {
+            Some(shown) => Lines::new(self.all, Some(&shown[..len])),
+            None => Lines::new(&self.all[..len], None),
+        }
+    }
+}
+
+/// A character of the output, by the index of its line and its index in the text.
+type TextPos = (usize, usize);
+
+/// Text selected with the mouse, from where the button was pressed to
+/// where it is or was released.
+#[derive(Clone, Copy, Debug)]
+pub struct Selection {
+    anchor: TextPos,
+    head: TextPos,
+}
+
+impl Selection {
+    /// Selects the whole lines from `a` to `b`.
+    pub fn lines(a: usize, b: usize) -> Self {
+        Selection {
+            anchor: (a.min(b), 0),
+            head: (a.max(b), usize::MAX),
+        }
+    }
+
+    /// The first and last selected characters, or `None` if nothing is
+    /// selected because the mouse didn't move off the first one.
+    fn range(self) -> Option<(TextPos, TextPos)> {
+        match self.anchor.cmp(&self.head) {
+            std::cmp::Ordering::Less => Some((self.anchor, self.head)),
+            std::cmp::Ordering::Equal => None,
+            std::cmp::Ordering::Greater => Some((self.head, self.anchor)),
+        }
+    }
+
+    fn contains(self, pos: TextPos) -> bool {
+        self.range()
+            .is_some_and(|(start, end)| start <= pos && pos <= end)
+    }
+}
+
+/// Where the characters of a row were drawn, for mapping mouse positions to text.
+#[derive(Clone, Debug)]
+struct VisibleRow {
+    y: u16,
+    line: usize,
+    /// The column and index in the text of every character of the text shown.
+    cells: Vec<(u16, usize)>,
+    /// The index in the text the row ends at.
+    end: usize,
 }
 
 pub struct WrapTextState {
     pub position: Position,
     pub movement_queue: Vec<Movement>,
+    /// The line at the top of the view when it was last rendered.
+    pub top_line: usize,
+    /// How far truncated words are scrolled sideways.
+    pub h_scroll: usize,
+    pub selection: Option<Selection>,
+    /// The line the cursor of the vim normal mode is on.
+    pub cursor: Option<usize>,
+    /// The rows drawn when the view was last rendered.
+    visible: Vec<VisibleRow>,
+    /// Where the scrollbar was drawn when the view was last rendered, if it was.
+    scrollbar: Option<Rect>,
 }
 
 pub struct WrapText<'b> {
-    pub lines: Vec<String>,
+    pub lines: Vec<Line>,
     pub block: Option<Block<'b>>,
+    pub timestamps: Timestamps,
+    /// What relative timestamps count from.
+    pub origin: Instant,
+    /// Deltas larger than this are highlighted.
+    pub gap_threshold: Duration,
+    /// The width of the line number column, including the spaces around the
+    /// number. It grows with the number of lines if it's not set and 0 hides
+    /// the line numbers.
+    pub line_number_width: Option<usize>,
+    /// The number shown for the first line.
+    pub line_number_start: usize,
+    pub wrapping: Wrapping,
 }
 
 pub struct WrappableTextWidget<'a, 'b> {
-    pub lines: &'a Vec<String>,
+    pub lines: Lines<'a>,
     pub block: Option<Block<'b>>,
+    pub timestamps: Timestamps,
+    pub origin: Instant,
+    pub gap_threshold: Duration,
+    pub line_number_width: Option<usize>,
+    pub line_number_start: usize,
+    pub wrapping: Wrapping,
+    /// Lines containing this are marked on the scrollbar.
+    pub search: Option<&'a str>,
+}
+
+impl Default for Wrapping {
+    fn default() -> Self {
+        Wrapping {
+            wrap: Wrap::Char,
+            long_words: LongWords::Break,
+            indent: false,
+            column: None,
+        }
+    }
+}
+
+impl Wrapping {
+    /// The indent of the rows a line continues on, at least wide enough for
+    /// the marker and a space.
+    fn continuation_indent(self, prefix: usize, width: usize) -> usize {
+        let indent = if self.indent { prefix.max(2) } else { 0 };
+        // Every row needs room for some text.
+        if indent < width {
+            indent
+        } else {
+            0
+        }
+    }
+
+    /// Breaks `text`, preceded by `prefix` columns, into rows of `width` columns.
+    fn rows(self, text: &str, prefix: usize, width: usize) -> Vec<Row> {
+        let mut rows = self.unindented_rows(text, prefix, width);
+        let indent = self.continuation_indent(prefix, width);
+        for row in rows.iter_mut().skip(1) {
+            row.indent = indent;
+        }
+        rows
+    }
+
+    /// The rows of `text`, leaving room for the indent without setting it.
+    fn unindented_rows(self, text: &str, prefix: usize, width: usize) -> Vec<Row> {
+        let chars: Vec<char> = text.chars().collect();
+        let total = prefix + chars.len();
+        if width == 0 {
+            return vec![Row::new(0..total)];
+        }
+        if self.wrap == Wrap::Off {
+            return vec![Row {
+                chars: 0..total,
+                truncated: total > width,
+                indent: 0,
+            }];
+        }
+        let indent = self.continuation_indent(prefix, width);
+        let mut rows = Vec::new();
+        if self.wrap == Wrap::Char || width <= prefix {
+            let mut start = 0;
+            let mut room = width;
+            while start + room < total {
+                rows.push(Row::new(start..start + room));
+                start += room;
+                room = width - indent;
+            }
+            rows.push(Row::new(start..total));
+            return rows;
+        }
+
+        // The first character and the number of used columns of the current row.
+        let mut start = 0;
+        let mut col = prefix;
+        let mut i = 0;
+        while i < chars.len() {
+            if chars[i].is_whitespace() {
+                if col == width {
+                    // The space the line is broken at isn't shown.
+                    rows.push(Row::new(start..prefix + i));
+                    start = prefix + i + 1;
+                    col = indent;
+                } else {
+                    col += 1;
+                }
+                i += 1;
+                continue;
+            }
+            let end = chars[i..]
+                .iter()
+                .position(|c| c.is_whitespace())
+                .map_or(chars.len(), |len| i + len);
+            if col + end - i <= width {
+                col += end - i;
+                i = end;
+                continue;
+            }
+            let empty_col = if rows.is_empty() { prefix } else { indent };
+            if col > empty_col {
+                rows.push(Row::new(start..prefix + i));
+                start = prefix + i;
+                col = indent;
+                continue;
+            }
+            // The word doesn't fit even though it has the row to itself.
+            match self.long_words {
+                LongWords::Break => {
+                    i += width - col;
+                    rows.push(Row::new(start..prefix + i));
+                }
+                LongWords::Truncate => {
+                    rows.push(Row {
+                        chars: start..prefix + end,
+                        truncated: true,
+                        indent: 0,
+                    });
+                    // Like at other breaks, the space after the word isn't shown.
+                    i = if end < chars.len() { end + 1 } else { end };
+                }
+            }
+            start = prefix + i;
+            col = indent;
+        }
+        if start < total || rows.is_empty() {
+            rows.push(Row::new(start..total));
+        }
+        rows
+    }
+
+    /// The number of rows `line` takes up.
+    fn height(self, line: &Line, prefix: usize, width: u16) -> usize {
+        self.rows(&line.text, prefix, width as usize).len()
+    }
 }
 
 impl<'b> WrapText<'b> {
-    pub fn widget(&mut self) -> WrappableTextWidget {
+    pub fn new() -> Self {
+        WrapText {
+            lines: vec![Line::default()],
+            block: None,
+            timestamps: Timestamps::Off,
+            origin: Instant::now(),
+            gap_threshold: Duration::from_secs(1),
+            line_number_width: None,
+            line_number_start: 0,
+            wrapping: Wrapping::default(),
+        }
+    }
+
+    /// Draws the lines, or only the ones at the indices of `shown`.
+    pub fn widget<'a>(&'a mut self, shown: Option<&'a [usize]>) -> WrappableTextWidget<'a, 'b> {
         WrappableTextWidget {
-            lines: &self.lines,
+            lines: Lines::new(&self.lines, shown),
             block: self.block.take(),
+            timestamps: self.timestamps,
+            origin: self.origin,
+            gap_threshold: self.gap_threshold,
+            line_number_width: self.line_number_width,
+            line_number_start: self.line_number_start,
+            wrapping: self.wrapping,
+            search: None,
         }
     }
     pub fn set_block(&mut self, block: Block<'b>) {
@@ -45,15 +484,105 @@ impl<'b> WrapText<'b> {
 }
 
 impl WrapTextState {
-    pub fn scroll_up(&mut self) {
-        self.movement_queue.push(Movement::ScrollUp);
+    pub fn new() -> Self {
+        WrapTextState {
+            position: Position::Follow,
+            movement_queue: Vec::new(),
+            top_line: 0,
+            h_scroll: 0,
+            selection: None,
+            cursor: None,
+            visible: Vec::new(),
+            scrollbar: None,
+        }
+    }
+    pub fn scroll_up_by(&mut self, rows: u16) {
+        self.movement_queue.push(Movement::ScrollUpN(rows));
     }
-    pub fn scroll_down(&mut self) {
-        self.movement_queue.push(Movement::ScrollDown);
+    pub fn scroll_down_by(&mut self, rows: u16) {
+        self.movement_queue.push(Movement::ScrollDownN(rows));
     }
     pub fn follow(&mut self) {
         self.movement_queue.push(Movement::Follow);
     }
+    pub fn jump_to(&mut self, line: usize) {
+        self.movement_queue.push(Movement::JumpTo(line));
+    }
+    pub fn reveal(&mut self, line: usize) {
+        self.movement_queue.push(Movement::Reveal(line));
+    }
+    pub fn follow_at_bottom(&mut self) {
+        self.movement_queue.push(Movement::FollowAtBottom);
+    }
+    /// The line out of `total` that the given position stands for if it's on
+    /// the scrollbar as it was last rendered.
+    pub fn scrollbar_line(&self, column: u16, row: u16, total: usize) -> Option<usize> {
+        let area = self.scrollbar?;
+        if column != area.x || !(area.y..area.bottom()).contains(&row) {
+            return None;
+        }
+        Some(scrollbar_row_to_line(row - area.y, area.height, total))
+    }
+    /// The last line that was at least partly shown when the view was last rendered.
+    pub fn bottom_line(&self) -> Option<usize> {
+        self.visible.last().map(|row| row.line)
+    }
+    pub fn scroll_left(&mut self) {
+        self.h_scroll = self.h_scroll.saturating_sub(H_SCROLL_STEP);
+    }
+    pub fn scroll_right(&mut self) {
+        self.h_scroll += H_SCROLL_STEP;
+    }
+
+    /// The character drawn at `column` and `row` of the screen, or the
+    /// closest one on the closest row if there is none.
+    fn text_pos(&self, column: u16, row: u16) -> Option<TextPos> {
+        let visible = self
+            .visible
+            .iter()
+            .rev()
+            .find(|visible| visible.y <= row)
+            .or(self.visible.first())?;
+        let idx = match visible.cells.iter().rposition(|&(x, _)| x <= column) {
+            Some(idx) => visible.cells[idx].1,
+            None => visible.cells.first().map_or(visible.end, |&(_, idx)| idx),
+        };
+        Some((visible.line, idx))
+    }
+
+    /// Starts selecting from the character at `column` and `row` of the
+    /// screen. Returns whether there was any text to select.
+    pub fn start_selection(&mut self, column: u16, row: u16) -> bool {
+        self.selection = self.text_pos(column, row).map(|pos| Selection {
+            anchor: pos,
+            head: pos,
+        });
+        self.selection.is_some()
+    }
+
+    /// Moves the end of the selection to the character at `column` and `row` of the screen.
+    pub fn extend_selection(&mut self, column: u16, row: u16) {
+        let pos = self.text_pos(column, row);
+        if let (Some(selection), Some(pos)) = (&mut self.selection, pos) {
+            selection.head = pos;
+        }
+    }
+
+    /// The selected text of `lines`, with the lines separated by newlines.
+    pub fn selected_text(&self, lines: Lines) -> Option<String> {
+        let ((start_line, start), (end_line, end)) = self.selection?.range()?;
+        let mut selected = Vec::new();
+        for (line_idx, line) in lines.iter().enumerate().take(end_line + 1).skip(start_line) {
+            let from = if line_idx == start_line { start } else { 0 };
+            let to = if line_idx == end_line {
+                end.saturating_add(1)
+            } else {
+                usize::MAX
+            };
+            selected.push(line.text.chars().take(to).skip(from).collect::<String>());
+        }
+        (!selected.is_empty()).then(|| selected.join("\n"))
+    }
 }
 
 impl Position {
@@ -62,69 +591,168 @@ impl Position {
         mov: Movement,
         line_number_width: usize,
         text_area: Rect,
-        lines: &[String],
+        lines: Lines,
+        wrapping: Wrapping,
     ) {
         *self = match mov {
-            Movement::ScrollUp => match self {
-                Position::At(ref mut line, ref mut offset) => {
-                    if *offset == 0 {
-                        if *line != 0 {
-                            *line -= 1;
-                            let height = (lines[*line as usize].len() + line_number_width - 1)
-                                / text_area.width as usize
-                                + 1;
-                            *offset = height as i32 - 1;
-                        }
-                    } else {
-                        *offset -= 1
-                    }
-                    *self
+            Movement::ScrollUpN(rows) => {
+                self.scroll_up(rows, line_number_width, text_area, lines, wrapping)
+            }
+            Movement::ScrollDownN(rows) => {
+                self.scroll_down(rows, line_number_width, text_area, lines, wrapping)
+            }
+            Movement::Follow => Position::Follow,
+            Movement::JumpTo(line) => {
+                if line + 1 >= lines.len() {
+                    Position::Follow
+                } else {
+                    Position::At(line as i32, 0)
                 }
-                Position::Follow => {
-                    let (l, of) =
-                        Position::follow_get_start_pos(text_area, lines, line_number_width);
-                    Position::At(l, of)
+            }
+            Movement::FollowAtBottom => match *self {
+                Position::At(line, offset)
+                    if (line, offset)
+                        >= Position::follow_get_start_pos(
+                            text_area,
+                            lines,
+                            line_number_width,
+                            wrapping,
+                        ) =>
+                {
+                    Position::Follow
                 }
+                position => position,
             },
-            Movement::ScrollDown => match self {
-                Position::At(ref mut line, ref mut offset) => {
-                    let height = (lines[*line as usize].len() + line_number_width - 1)
-                        / text_area.width as usize
-                        + 1;
-                    if *offset + 1 >= height as i32 {
-                        if *line >= lines.len() as i32 - 1 {
-                            *offset = (text_area.height as i32 - 1).min(*offset + 1);
-                        } else {
-                            *line += 1;
-                            *offset = 0;
-                        }
-                    } else {
-                        *offset += 1;
-                    }
+            Movement::Reveal(line) => {
+                let line = line.min(lines.len().saturating_sub(1));
+                let start = match *self {
+                    Position::At(line, offset) => (line, offset),
+                    Position::Follow => Position::follow_get_start_pos(
+                        text_area,
+                        lines,
+                        line_number_width,
+                        wrapping,
+                    ),
+                };
+                // Where the view would start if the line was at its bottom.
+                let bottom = Position::follow_get_start_pos(
+                    text_area,
+                    lines.first(line + 1),
+                    line_number_width,
+                    wrapping,
+                );
+                if (line as i32, 0) < start {
+                    Position::At(line as i32, 0)
+                } else if bottom > start && line + 1 >= lines.len() {
+                    Position::Follow
+                } else if bottom > start {
+                    Position::At(bottom.0, bottom.1)
+                } else {
                     *self
                 }
-                Position::Follow => {
-                    let (l, of) =
-                        Position::follow_get_start_pos(text_area, lines, line_number_width);
-                    Position::At(l, of)
-                }
-            },
-            Movement::Follow => Position::Follow,
+            }
+        }
+    }
+
+    /// Moves `rows` up. Following only stops at the start of the view for the
+    /// first row, and the heights of the lines are only computed once each.
+    fn scroll_up(
+        self,
+        rows: u16,
+        line_number_width: usize,
+        text_area: Rect,
+        lines: Lines,
+        wrapping: Wrapping,
+    ) -> Position {
+        if lines.is_empty() {
+            return self;
+        }
+        let (mut line, mut offset) = match self {
+            Position::At(line, offset) => (line, offset),
+            Position::Follow if rows == 0 => return self,
+            Position::Follow => {
+                let (line, offset) =
+                    Position::follow_get_start_pos(text_area, lines, line_number_width, wrapping);
+                return Position::At(line, offset).scroll_up(
+                    rows - 1,
+                    line_number_width,
+                    text_area,
+                    lines,
+                    wrapping,
+                );
+            }
+        };
+        for _ in 0..rows {
+            if offset != 0 {
+                offset -= 1;
+            } else if line != 0 {
+                line -= 1;
+                let height =
+                    wrapping.height(lines.get(line as usize), line_number_width, text_area.width);
+                offset = height as i32 - 1;
+            }
+        }
+        Position::At(line, offset)
+    }
+
+    /// Moves `rows` down, like `scroll_up`. Within the last line the view
+    /// stops at most its height further down.
+    fn scroll_down(
+        self,
+        rows: u16,
+        line_number_width: usize,
+        text_area: Rect,
+        lines: Lines,
+        wrapping: Wrapping,
+    ) -> Position {
+        if lines.is_empty() {
+            return self;
+        }
+        let (mut line, mut offset) = match self {
+            Position::At(line, offset) => (line, offset),
+            Position::Follow if rows == 0 => return self,
+            Position::Follow => {
+                let (line, offset) =
+                    Position::follow_get_start_pos(text_area, lines, line_number_width, wrapping);
+                return Position::At(line, offset).scroll_down(
+                    rows - 1,
+                    line_number_width,
+                    text_area,
+                    lines,
+                    wrapping,
+                );
+            }
+        };
+        let height_of = |line: i32| {
+            wrapping.height(lines.get(line as usize), line_number_width, text_area.width) as i32
+        };
+        let mut height = height_of(line);
+        for _ in 0..rows {
+            if offset + 1 < height {
+                offset += 1;
+            } else if line >= lines.len() as i32 - 1 {
+                offset = (text_area.height as i32 - 1).min(offset + 1);
+            } else {
+                line += 1;
+                offset = 0;
+                height = height_of(line);
+            }
         }
+        Position::At(line, offset)
     }
 
     /// Computes the start position given that we follow.
     pub fn follow_get_start_pos(
         text_area: Rect,
-        lines: &[String],
+        lines: Lines,
         line_number_width: usize,
+        wrapping: Wrapping,
     ) -> (i32, i32) {
         let mut line_idx = -1;
         let mut offset = 0;
         let mut tot_height = 0;
         for line in lines.iter().rev() {
-            let height =
-                (line.len() as i32 + line_number_width as i32 - 1) / text_area.width as i32 + 1;
+            let height = wrapping.height(line, line_number_width, text_area.width) as i32;
             tot_height += height as u16;
             line_idx += 1;
             if tot_height > text_area.height {
@@ -142,6 +770,83 @@ impl Position {
     }
 }
 
+impl<'a, 'b> WrappableTextWidget<'a, 'b> {
+    /// The width of the line number column.
+    fn number_width(&self) -> usize {
+        self.line_number_width.unwrap_or_else(|| {
+            let last = self.line_number_start + self.lines.len().saturating_sub(1);
+            last.to_string().len().max(2) + 2
+        })
+    }
+
+    /// Marks the lines containing `search` on the scrollbar.
+    pub fn search(mut self, search: Option<&'a str>) -> Self {
+        self.search = search.filter(|search| !search.is_empty());
+        self
+    }
+
+    /// How every row of a scrollbar `height` rows high is marked, by the
+    /// lines it stands for.
+    fn scrollbar_marks(&self, height: usize) -> Vec<Option<Mark>> {
+        let mut marks = vec![None; height];
+        let total = self.lines.len();
+        for (idx, line) in self.lines.iter().enumerate() {
+            let mark = if line.bookmarked {
+                Mark::Bookmarked
+            } else if self.search.is_some_and(|search| line.text.contains(search)) {
+                Mark::Match
+            } else if !line.highlights.is_empty() {
+                Mark::Highlighted
+            } else {
+                continue;
+            };
+            let row = &mut marks[(idx * height / total).min(height - 1)];
+            *row = (*row).max(Some(mark));
+        }
+        marks
+    }
+
+    /// The line number column for a line, padded to `width`. Numbers that
+    /// don't fit only get their last digits shown.
+    fn line_number(&self, line_idx: usize, width: usize) -> String {
+        if width < 3 {
+            return " ".repeat(width);
+        }
+        let digits = width - 2;
+        let number = (self.line_number_start + line_idx).to_string();
+        let number = &number[number.len().saturating_sub(digits)..];
+        format!(" {number:0>digits$} ")
+    }
+
+    /// The content of the timestamp column for a line, padded to its width.
+    fn timestamp(&self, line_idx: usize) -> (String, Style) {
+        let width = self.timestamps.width();
+        let mut style = Style::default().fg(Color::DarkGray);
+        let text = match (self.timestamps, self.lines.get(line_idx).time) {
+            (Timestamps::Absolute, Some(time)) => {
+                timefmt::clock_time(timefmt::to_system_time(time))
+            }
+            (Timestamps::Delta, Some(time)) => {
+                let prev = self.lines.first(line_idx).iter().rev().find_map(|l| l.time);
+                match prev {
+                    Some(prev) => {
+                        let delta = time.saturating_duration_since(prev);
+                        if delta > self.gap_threshold {
+                            style = Style::default().fg(Color::LightRed);
+                        }
+                        timefmt::delta(delta)
+                    }
+                    None => String::new(),
+                }
+            }
+            (Timestamps::Relative, Some(time)) => timefmt::relative(time, self.origin),
+            _ => String::new(),
+        };
+        let text: String = text.chars().take(width.saturating_sub(1)).collect();
+        (format!("{text:<width$}"), style)
+    }
+}
+
 impl<'a, 'b> StatefulWidget for WrappableTextWidget<'a, 'b> {
     type State = WrapTextState;
 
@@ -151,9 +856,7 @@ impl<'a, 'b> StatefulWidget for WrappableTextWidget<'a, 'b> {
         buf: &mut tui::buffer::Buffer,
         state: &mut Self::State,
     ) {
-        let line_number_width = 4;
-
-        let text_area = match self.block.take() {
+        let mut text_area = match self.block.take() {
             Some(b) => {
                 let inner_area = b.inner(area);
                 b.render(area, buf);
@@ -162,63 +865,262 @@ impl<'a, 'b> StatefulWidget for WrappableTextWidget<'a, 'b> {
             None => area,
         };
 
+        if (text_area.width as usize)
+            < self.number_width() + self.timestamps.width() + MIN_TEXT_COLUMNS
+        {
+            self.line_number_width = Some(0);
+            self.timestamps = Timestamps::Off;
+        }
+        let number_width = self.number_width();
+        // Everything in front of the text is handled as line numbers by the scroll math.
+        let line_number_width = number_width + self.timestamps.width();
+
+        // The rightmost column is used for the scrollbar.
+        let scrollbar = if text_area.width > line_number_width as u16 + 1 {
+            text_area.width -= 1;
+            Some(Rect::new(
+                text_area.right(),
+                text_area.y,
+                1,
+                text_area.height,
+            ))
+        } else {
+            None
+        };
+        state.scrollbar = scrollbar;
+        // Everything is laid out as if the output was only this wide.
+        if let Some(column) = self.wrapping.column {
+            let width = (line_number_width + column).min(u16::MAX as usize) as u16;
+            text_area.width = text_area.width.min(width);
+        }
+
         for movement in &state.movement_queue {
-            state
-                .position
-                .do_movement(*movement, line_number_width, text_area, self.lines);
+            state.position.do_movement(
+                *movement,
+                line_number_width,
+                text_area,
+                self.lines,
+                self.wrapping,
+            );
         }
         state.movement_queue.clear();
 
         let (start_line_idx, offset) = match state.position {
             Position::At(line_idx, offset) => (line_idx, offset),
-            Position::Follow => {
-                Position::follow_get_start_pos(text_area, self.lines, line_number_width)
-            }
+            Position::Follow => Position::follow_get_start_pos(
+                text_area,
+                self.lines,
+                line_number_width,
+                self.wrapping,
+            ),
         };
+        // The first line that is at least partly visible.
+        state.top_line = start_line_idx as usize;
+        let mut top_found = false;
+        // How far the widest visible truncated word can be scrolled.
+        let mut max_h_scroll = 0;
         let mut cur_row: i32 = -offset;
-        for (line_idx_rel, line) in self.lines[start_line_idx as usize..].iter().enumerate() {
-            let mut cur_col = 0;
-            let mut tmp_string = String::new();
-            if cur_row >= 0
+        state.visible.clear();
+        let mut end_line_idx = start_line_idx as usize;
+        let lines = self.lines;
+        let shown = (start_line_idx as usize..lines.len()).map(|idx| lines.get(idx));
+        for (line_idx_rel, line) in shown.enumerate() {
+            if cur_row >= text_area.height as i32 {
+                break;
+            }
+            end_line_idx = start_line_idx as usize + line_idx_rel + 1;
+            if number_width > 0
+                && cur_row >= 0
                 && cur_row < text_area.height as i32
-                && text_area.width >= line_number_width as u16
+                && text_area.width >= number_width as u16
             {
                 buf.set_style(
                     Rect::new(
                         text_area.x,
                         (text_area.y as i32 + cur_row) as u16,
-                        line_number_width as u16,
+                        number_width as u16,
                         1,
                     ),
                     Style::default().fg(Color::Yellow),
                 );
             }
-            for (i, ch) in format!(" {:0>2} ", (start_line_idx as usize + line_idx_rel) % 100)
+            let line_idx = start_line_idx as usize + line_idx_rel;
+            let (timestamp, timestamp_style) = self.timestamp(line_idx);
+            let mut line_number = self.line_number(line_idx, number_width);
+            if line.bookmarked && number_width > 0 {
+                line_number.replace_range(..1, &BOOKMARK.to_string());
+            }
+            let chars: Vec<char> = line_number
                 .chars()
-                .chain(line.chars())
-                .enumerate()
-            {
-                if text_area.bottom() as i32 <= text_area.y as i32 + cur_row {
+                .chain(timestamp.chars())
+                .chain(line.text.chars())
+                .collect();
+            let rows = self
+                .wrapping
+                .rows(&line.text, line_number_width, text_area.width as usize);
+            for row in rows {
+                if cur_row >= text_area.height as i32 {
                     break;
                 }
-
-                if cur_row >= 0 {
-                    tmp_string.push(ch);
-                    buf.get_mut(text_area.x + cur_col, text_area.y + cur_row as u16)
-                        .set_symbol(&tmp_string);
-                    tmp_string.clear();
-                }
-
-                let is_last = i == line_number_width + line.len() - 1;
-                cur_col += 1;
-                if cur_col >= text_area.width && !is_last {
-                    cur_col = 0;
+                if cur_row < 0 {
                     cur_row += 1;
+                    continue;
+                }
+                let end = row.chars.end.saturating_sub(line_number_width);
+                let cells = if row.truncated {
+                    let (cells, overflow) = truncated_cells(
+                        row.chars,
+                        line_number_width,
+                        text_area.width as usize - row.indent,
+                        state.h_scroll,
+                    );
+                    max_h_scroll = max_h_scroll.max(overflow);
+                    cells
+                } else {
+                    row.chars.map(|i| (i, None)).collect()
+                };
+                let y = text_area.y + cur_row as u16;
+                if state.cursor == Some(line_idx) {
+                    buf.set_style(
+                        Rect::new(text_area.x, y, text_area.width, 1),
+                        Style::default().bg(Color::DarkGray),
+                    );
+                }
+                let mut visible = VisibleRow {
+                    y,
+                    line: line_idx,
+                    cells: Vec::new(),
+                    end,
+                };
+                if row.indent > 0 {
+                    let marker_x = text_area.x + row.indent as u16 - 2;
+                    buf.get_mut(marker_x, y)
+                        .set_symbol(&CONTINUATION.to_string())
+                        .set_style(Style::default().fg(Color::DarkGray));
+                }
+                for (col, (i, symbol)) in cells.into_iter().enumerate() {
+                    let x = text_area.x + (row.indent + col) as u16;
+                    let cell = buf.get_mut(x, y);
+                    cell.set_symbol(&symbol.unwrap_or(chars[i]).to_string());
+                    if i >= line_number_width {
+                        cell.set_style(line.style);
+                        let char_idx = i - line_number_width;
+                        for (range, style) in &line.highlights {
+                            if range.contains(&char_idx) {
+                                cell.set_style(*style);
+                            }
+                        }
+                        if state
+                            .selection
+                            .is_some_and(|s| s.contains((line_idx, char_idx)))
+                        {
+                            cell.set_style(Style::default().add_modifier(Modifier::REVERSED));
+                        }
+                        visible.cells.push((x, char_idx));
+                    } else if i >= number_width {
+                        cell.set_style(timestamp_style);
+                    }
                 }
+                state.visible.push(visible);
+                cur_row += 1;
             }
-            cur_row += 1;
+            if !top_found && cur_row > 0 {
+                top_found = true;
+                state.top_line = line_idx;
+            }
+        }
+
+        state.h_scroll = state.h_scroll.min(max_h_scroll);
+
+        if let Some(scrollbar) = scrollbar {
+            let marks = self.scrollbar_marks(scrollbar.height as usize);
+            render_scrollbar(
+                scrollbar,
+                buf,
+                start_line_idx as usize,
+                end_line_idx,
+                &marks,
+                self.lines.len(),
+            );
+        }
+    }
+}
+
+/// The characters shown on a row with a truncated word, scrolled sideways by
+/// `h_scroll` columns. Ellipses replace the first and last characters where
+/// the word is cut off. Also returns how far the word can be scrolled.
+fn truncated_cells(
+    chars: Range<usize>,
+    prefix: usize,
+    width: usize,
+    h_scroll: usize,
+) -> (Vec<(usize, Option<char>)>, usize) {
+    // The columns in front of the text stay put.
+    let word_start = chars.start.max(prefix);
+    let mut cells: Vec<_> = (chars.start..word_start).map(|i| (i, None)).collect();
+    let space = width.saturating_sub(cells.len());
+    let overflow = (chars.end - word_start).saturating_sub(space);
+    let shift = h_scroll.min(overflow);
+    cells.extend(
+        (word_start + shift..chars.end)
+            .take(space)
+            .map(|i| (i, None)),
+    );
+    if shift > 0 {
+        if let Some(cell) = cells.get_mut(word_start - chars.start) {
+            cell.1 = Some(ELLIPSIS);
         }
     }
+    if shift < overflow {
+        if let Some(cell) = cells.last_mut() {
+            cell.1 = Some(ELLIPSIS);
+        }
+    }
+    (cells, overflow)
+}
+
+/// Draws a scrollbar where the thumb covers the lines `start..end` out of
+/// `total`. Rows with marks are drawn in their color, as a dot off the thumb.
+fn render_scrollbar(
+    area: Rect,
+    buf: &mut tui::buffer::Buffer,
+    start: usize,
+    end: usize,
+    marks: &[Option<Mark>],
+    total: usize,
+) {
+    if area.height == 0 || total == 0 {
+        return;
+    }
+    let height = area.height as usize;
+    let thumb_start = (start * height / total).min(height - 1);
+    let thumb_end = (end * height)
+        .div_ceil(total)
+        .clamp(thumb_start + 1, height);
+    for (row, mark) in marks.iter().enumerate().take(height) {
+        let cell = buf.get_mut(area.x, area.y + row as u16);
+        let on_thumb = row >= thumb_start && row < thumb_end;
+        let symbol = match (on_thumb, mark) {
+            (true, _) => "█",
+            (false, Some(_)) => "•",
+            (false, None) => "│",
+        };
+        let color = match (on_thumb, mark) {
+            (_, Some(mark)) => mark.color(),
+            (true, None) => Color::Gray,
+            (false, None) => Color::DarkGray,
+        };
+        cell.set_symbol(symbol)
+            .set_style(Style::default().fg(color));
+    }
+}
+
+/// Maps a row of the scrollbar to the line it represents.
+fn scrollbar_row_to_line(row: u16, height: u16, total: usize) -> usize {
+    if height == 0 {
+        return 0;
+    }
+    (row as usize * total / height as usize).min(total.saturating_sub(1))
 }
 
 // aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa